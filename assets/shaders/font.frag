@@ -1,7 +1,7 @@
 #version 130
 
 in vec2 t_uv;
-in vec3 t_color;
+in vec4 t_color;
 
 out vec4 color;
 
@@ -9,5 +9,5 @@ uniform sampler2D tex;
 
 void main() {
 	float v = texture(tex, t_uv).r;
-	color = vec4(t_color, v);
+	color = vec4(t_color.rgb, t_color.a * v);
 }
@@ -4,10 +4,10 @@ in vec2 pos;
 in vec2 uv;
 
 out vec2 t_uv;
-out vec3 t_color;
+out vec4 t_color;
 
 uniform mat4 mat;
-uniform vec3 color;
+uniform vec4 color;
 
 void main() {
 	t_uv = uv;
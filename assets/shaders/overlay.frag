@@ -0,0 +1,13 @@
+#version 130
+
+in vec2 t_uv;
+
+out vec4 color;
+
+uniform sampler2D tex;
+uniform vec4 tint;
+
+void main() {
+	vec4 c = texture(tex, t_uv);
+	color = c * tint;
+}
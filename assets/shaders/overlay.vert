@@ -0,0 +1,13 @@
+#version 130
+
+in vec2 pos;
+in vec2 uv;
+
+out vec2 t_uv;
+
+uniform mat4 mat;
+
+void main() {
+	t_uv = uv;
+	gl_Position = mat * vec4(pos, 0.0, 1.0);
+}
@@ -0,0 +1,9 @@
+#version 130
+
+out vec4 frag_color;
+
+uniform vec4 color;
+
+void main() {
+	frag_color = color;
+}
@@ -0,0 +1,9 @@
+#version 130
+
+in vec2 pos;
+
+uniform mat4 mat;
+
+void main() {
+	gl_Position = mat * vec4(pos, 0.0, 1.0);
+}
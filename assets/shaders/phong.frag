@@ -8,14 +8,30 @@ uniform vec4 ambient;
 
 uniform mat4 v_inv;
 
+// Shadow map of the main light
+uniform sampler2D shadow_map;
+// Transforms world space to the main light's clip space
+uniform mat4 light_space;
+// If the main light casts shadows
+uniform bool shadows_enabled;
+
+// If the texture and output are sRGB encoded, so lighting should be done after converting to linear space
+uniform bool srgb;
+
+// Must match MAX_LIGHTS in render.rs
+#define MAX_LIGHTS 8
+
+// The number of lights in use
+uniform int light_count;
+
 //struct Light {
-	uniform vec4 light_pos;
-	uniform vec4 light_diffuse;
-	uniform vec4 light_specular;
-	uniform float light_constant_attenuation, light_linear_attenuation, light_quadratic_attenuation;
-	uniform float light_spot_cutoff, light_spot_exponent;
-	uniform vec3 light_spot_direction;
-//} light;
+	uniform vec4 light_pos[MAX_LIGHTS];
+	uniform vec4 light_diffuse[MAX_LIGHTS];
+	uniform vec4 light_specular[MAX_LIGHTS];
+	uniform float light_constant_attenuation[MAX_LIGHTS], light_linear_attenuation[MAX_LIGHTS], light_quadratic_attenuation[MAX_LIGHTS];
+	uniform float light_spot_cutoff[MAX_LIGHTS], light_spot_exponent[MAX_LIGHTS];
+	uniform vec3 light_spot_direction[MAX_LIGHTS];
+//} light[MAX_LIGHTS];
 
 //struct Material {
 	uniform vec4 material_ambient;
@@ -28,40 +44,69 @@ in vec4 t_pos;
 in vec3 t_normal;
 in vec2 t_uv;
 
-void main() {
-	vec3 normal_dir = normalize(t_normal);
-	vec3 view_dir = normalize(vec3(v_inv * vec4(0.0, 0.0, 0.0, 1.0) - t_pos));
+// Converts an sRGB encoded color to linear space, if sRGB is enabled
+vec3 to_linear(vec3 c) {
+	return srgb ? pow(max(c, 0.0), vec3(2.2)) : c;
+}
+
+// Converts an sRGB encoded color to linear space, keeping its alpha, if sRGB is enabled
+vec4 to_linear(vec4 c) {
+	return vec4(to_linear(c.rgb), c.a);
+}
+
+// Converts a linear color to sRGB encoding, if sRGB is enabled
+vec3 to_srgb(vec3 c) {
+	return srgb ? pow(max(c, 0.0), vec3(1.0 / 2.2)) : c;
+}
+
+// Calculates how much of the main light reaches the fragment, from 0.0 (in shadow) to 1.0 (lit)
+float calc_shadow(vec3 normal_dir) {
+	if (!shadows_enabled) {
+		return 1.0;
+	}
+	vec4 light_pos_clip = light_space * t_pos;
+	vec3 coords = (light_pos_clip.xyz / light_pos_clip.w) * 0.5 + 0.5;
+	if (coords.x < 0.0 || coords.x > 1.0 || coords.y < 0.0 || coords.y > 1.0 || coords.z > 1.0) {
+		// Outside of the shadow map
+		return 1.0;
+	}
+	// Bias the depth to avoid shadow acne, more so on surfaces facing away from the light
+	float bias = max(0.005 * (1.0 - dot(normal_dir, normalize(vec3(light_pos[0])))), 0.0005);
+	float closest = texture(shadow_map, coords.xy).r;
+	return coords.z - bias > closest ? 0.0 : 1.0;
+}
+
+// Calculates the diffuse and specular lighting from light `i`
+vec4 calc_light(int i, vec3 normal_dir, vec3 view_dir) {
 	vec3 dir_light;
 	float attenuation;
 	
-	if (light_pos.w == 0.0) { // Directional light?
+	if (light_pos[i].w == 0.0) { // Directional light?
 		attenuation = 1.0; // no attenuation
-		dir_light = normalize(vec3(light_pos));
+		dir_light = normalize(vec3(light_pos[i]));
 	} else {
 		// point light or spotlight (or other kind of light) 
-		vec3 pos_to_light = vec3(light_pos - t_pos);
+		vec3 pos_to_light = vec3(light_pos[i] - t_pos);
 		float distance = length(pos_to_light);
 		dir_light = normalize(pos_to_light);
-		attenuation = 1.0 / (light_constant_attenuation
-			+ light_linear_attenuation * distance
-			+ light_quadratic_attenuation * distance * distance);
+		attenuation = 1.0 / (light_constant_attenuation[i]
+			+ light_linear_attenuation[i] * distance
+			+ light_quadratic_attenuation[i] * distance * distance);
 		
-		if (light_spot_cutoff <= radians(90.0)) {
+		if (light_spot_cutoff[i] <= radians(90.0)) {
 			// spotlight?
-			float clamped_cos = max(0.0, dot(-dir_light, light_spot_direction));
-			if (clamped_cos < cos(light_spot_cutoff)) {
-				// outside of spotlight cone?
-				attenuation = 0.0;
-			} else {
-				attenuation = attenuation * pow(clamped_cos, light_spot_exponent);   
-			}
+			float clamped_cos = max(0.0, dot(-dir_light, light_spot_direction[i]));
+			// fade out over the outer 10% of the cone, rather than cutting off sharply at the edge
+			float outer_cos = cos(light_spot_cutoff[i]);
+			float inner_cos = cos(light_spot_cutoff[i] * 0.9);
+			float edge = smoothstep(outer_cos, inner_cos, clamped_cos);
+			attenuation = attenuation * edge * pow(clamped_cos, light_spot_exponent[i]);
 		}
 	}
 	
-	vec4 ambient_lighting = ambient * material_ambient;
-	
+	// Light and material colors are sRGB encoded, like textures
 	vec4 diffuse_reflection = attenuation 
-		* light_diffuse * material_diffuse
+		* to_linear(light_diffuse[i]) * to_linear(material_diffuse)
 		* max(0.0, dot(normal_dir, dir_light));
 	
 	vec4 specular_reflection;
@@ -70,9 +115,27 @@ void main() {
 		specular_reflection = vec4(0.0, 0.0, 0.0, 1.0); // no specular reflection
 	} else {
 		// light source on the right side
-		specular_reflection = attenuation * light_specular * material_specular 
+		specular_reflection = attenuation * to_linear(light_specular[i]) * to_linear(material_specular) 
 			* pow(max(0.0, dot(reflect(-dir_light, normal_dir), view_dir)), material_shininess);
 	}
 	
-	gl_FragColor = (ambient_lighting + diffuse_reflection + specular_reflection) * texture(tex, t_uv);
+	return diffuse_reflection + specular_reflection;
+}
+
+void main() {
+	vec3 normal_dir = normalize(t_normal);
+	vec3 view_dir = normalize(vec3(v_inv * vec4(0.0, 0.0, 0.0, 1.0) - t_pos));
+	
+	vec4 lighting = to_linear(ambient) * to_linear(material_ambient);
+	for (int i = 0; i < light_count && i < MAX_LIGHTS; i++) {
+		vec4 light = calc_light(i, normal_dir, view_dir);
+		if (i == 0) {
+			light *= calc_shadow(normal_dir);
+		}
+		lighting += light;
+	}
+	
+	vec4 texel = texture(tex, t_uv);
+	vec4 color = lighting * vec4(to_linear(texel.rgb), texel.a);
+	gl_FragColor = vec4(to_srgb(color.rgb), color.a);
 }
@@ -0,0 +1,10 @@
+#version 130
+
+in vec3 pos;
+
+// Light space * model matrix
+uniform mat4 mvp;
+
+void main() {
+	gl_Position = mvp * vec4(pos, 1.0);
+}
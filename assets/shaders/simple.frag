@@ -1,9 +1,9 @@
 #version 130
 
-in vec3 t_color;
+in vec4 t_color;
 
 out vec4 color;
 
 void main() {
-	color = vec4(t_color, 1.0);
+	color = t_color;
 }
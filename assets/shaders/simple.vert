@@ -2,11 +2,11 @@
 
 in vec3 pos;
 
-out vec3 t_color;
+out vec4 t_color;
 
 uniform mat4 mvp;
 
-uniform vec3 color;
+uniform vec4 color;
 
 void main() {
 	t_color = color;
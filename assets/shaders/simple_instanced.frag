@@ -0,0 +1,9 @@
+#version 130
+
+in vec4 t_color;
+
+out vec4 color;
+
+void main() {
+	color = t_color;
+}
@@ -0,0 +1,14 @@
+#version 130
+
+in vec3 pos;
+in mat4 model;
+in vec4 instance_color;
+
+out vec4 t_color;
+
+uniform mat4 vp;
+
+void main() {
+	t_color = instance_color;
+	gl_Position = vp * model * vec4(pos, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 130
+
+in vec3 t_dir;
+
+out vec4 color;
+
+uniform samplerCube skybox;
+
+void main() {
+	color = texture(skybox, t_dir);
+}
@@ -0,0 +1,13 @@
+#version 130
+
+in vec3 pos;
+
+out vec3 t_dir;
+
+uniform mat4 vp;
+
+void main() {
+	t_dir = pos;
+	// Set z to w, so that the skybox is always at the maximum depth
+	gl_Position = (vp * vec4(pos, 1.0)).xyww;
+}
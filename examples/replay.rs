@@ -0,0 +1,55 @@
+extern crate neat;
+
+use std::env;
+use std::process::exit;
+use std::io::{self, Write};
+
+use neat::prelude::*;
+use neat::game::{EntityId, Game, GameState, GameStateBuilder};
+use neat::settings::Settings;
+
+/// Gets the position and velocity of each entity in the simulation.
+fn snapshot(state: &GameState) -> Vec<(EntityId, Vector3<f32>, Vector3<f32>)> {
+	state.entity_ids().into_iter().filter_map(|id| {
+		state.get_entity_rigid_body(id).map(|body| (id, body.position().translation.vector, body.velocity().linear))
+	}).collect()
+}
+
+/// Runs the `balls` scene while recording the input, and then replays the recording. Once the replay has finished,
+/// the positions and velocities of the entities are checked against those at the end of the recording.
+fn run() -> Result<bool, String> {
+	let path = env::temp_dir().join("neat_replay_example.json");
+	
+	println!("Recording input to '{}'. Close the window to start the replay.", path.display());
+	let mut settings = Settings::from_args();
+	settings.record_input = Some(path.clone());
+	settings.replay_input = None;
+	let mut game = Game::with_state_generator(settings, Box::new(GameStateBuilder::build_balls))?;
+	game.main_loop();
+	let recorded = snapshot(game.current_state());
+	drop(game);
+	
+	println!("Replaying input from '{}'", path.display());
+	let mut settings = Settings::from_args();
+	settings.record_input = None;
+	settings.replay_input = Some(path.clone());
+	let mut game = Game::with_state_generator(settings, Box::new(GameStateBuilder::build_balls))?;
+	game.main_loop();
+	let replayed = snapshot(game.current_state());
+	
+	Ok(recorded == replayed)
+}
+
+pub fn main() {
+	match run() {
+		Ok(true) => println!("The replay ended in the same state as the recording"),
+		Ok(false) => {
+			writeln!(io::stderr(), "Error: The replay ended in a different state to the recording").ok();
+			exit(1);
+		},
+		Err(e) => {
+			writeln!(io::stderr(), "Error: {}", e).ok();
+			exit(1);
+		}
+	}
+}
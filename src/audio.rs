@@ -0,0 +1,76 @@
+//! Audio playback.
+//! 
+//! Only available when the `audio` feature is enabled.
+use std::io::BufReader;
+use std::path::Path;
+
+use rodio::{self, Decoder, Device, Sink, Source};
+
+use vfs;
+
+/// Streams music from the `assets/` folder in the background.
+pub struct MusicPlayer {
+	device: Device,
+	sink: Option<Sink>,
+	volume: f32,
+}
+impl MusicPlayer {
+	/// Constructs a new music player that outputs to the default audio device.
+	/// 
+	/// Returns an `Err` if there is no audio device available.
+	pub fn new() -> Result<MusicPlayer, String> {
+		let device = rodio::default_output_device()
+			.ok_or_else(|| "no audio output device available".to_string())?;
+		Ok(MusicPlayer {
+			device,
+			sink: None,
+			volume: 1.0,
+		})
+	}
+	
+	/// Starts playing the audio file at `path` in the `assets/` folder, stopping any music that is currently playing.
+	/// 
+	/// If `looping` is true, the music will repeat until it is stopped.
+	pub fn play<P: AsRef<Path>>(&mut self, path: P, looping: bool) -> Result<(), String> {
+		let path = path.as_ref();
+		let file = vfs::try_open_data_file(path)?;
+		let source = Decoder::new(BufReader::new(file))
+			.map_err(|e| format!("could not decode audio file '{}': {:?}", path.display(), e))?;
+		
+		self.stop();
+		let sink = Sink::new(&self.device);
+		sink.set_volume(self.volume);
+		if looping {
+			sink.append(source.repeat_infinite());
+		} else {
+			sink.append(source);
+		}
+		self.sink = Some(sink);
+		Ok(())
+	}
+	
+	/// Stops the music that is currently playing, if any.
+	pub fn stop(&mut self) {
+		if let Some(sink) = self.sink.take() {
+			sink.stop();
+		}
+	}
+	
+	/// Returns true if music is currently playing.
+	pub fn is_playing(&self) -> bool {
+		self.sink.as_ref().map(|sink| !sink.empty()).unwrap_or(false)
+	}
+	
+	/// Gets the volume of the music. 1.0 is the volume of the audio file.
+	pub fn volume(&self) -> f32 {
+		self.volume
+	}
+	
+	/// Sets the volume of the music. 1.0 is the volume of the audio file.
+	pub fn set_volume(&mut self, volume: f32) {
+		self.volume = volume;
+		if let Some(sink) = self.sink.as_ref() {
+			sink.set_volume(volume);
+		}
+	}
+}
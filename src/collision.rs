@@ -0,0 +1,219 @@
+//! Simple axis-aligned bounding box collision, for prototypes that don't want to use the full physics solver.
+use prelude::*;
+
+use nc::shape::Shape;
+use np::object::BodyStatus;
+use np::world::World;
+
+use game::Entity;
+
+/// An axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+	pub min: Vector3<f32>,
+	pub max: Vector3<f32>,
+}
+impl Aabb {
+	/// Constructs a new bounding box from its minimum and maximum corners.
+	pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+		Aabb {
+			min,
+			max,
+		}
+	}
+	
+	/// Gets the bounding box of the collider of an entity, in world space.
+	/// 
+	/// Returns `None` if the entity is not in `world`.
+	pub fn from_entity(world: &World<f32>, e: &Entity) -> Option<Aabb> {
+		let collider = world.collider(e.collider())?;
+		let aabb = collider.shape().aabb(collider.position());
+		Some(Aabb::new(aabb.mins().coords, aabb.maxs().coords))
+	}
+	
+	/// Gets the bounding box of the collider of an entity, in world space, when it is interpolated by `alpha` like it
+	/// is when rendered. See `Entity::interpolated_pose`.
+	/// 
+	/// Returns `None` if the entity is not in `world`.
+	pub fn from_entity_interpolated(world: &World<f32>, e: &Entity, alpha: f32) -> Option<Aabb> {
+		let collider = world.collider(e.collider())?;
+		let body_pos = world.rigid_body(e.body())?.position();
+		// Move the collider along with its body
+		let pos = e.interpolated_pose(world, alpha)? * body_pos.inverse() * collider.position();
+		let aabb = collider.shape().aabb(&pos);
+		Some(Aabb::new(aabb.mins().coords, aabb.maxs().coords))
+	}
+	
+	/// Gets the center of the bounding box.
+	pub fn center(&self) -> Vector3<f32> {
+		(self.min + self.max) * 0.5
+	}
+	
+	/// Returns true if the bounding boxes overlap. Boxes that are just touching are counted as overlapping.
+	pub fn intersects(&self, other: &Aabb) -> bool {
+		self.min.x <= other.max.x && other.min.x <= self.max.x &&
+		self.min.y <= other.max.y && other.min.y <= self.max.y &&
+		self.min.z <= other.max.z && other.min.z <= self.max.z
+	}
+	
+	/// Returns true if any part of the bounding box could be inside the view frustum described by `view_projection`,
+	/// the matrix that transforms from world space to clip space.
+	/// 
+	/// This is conservative: some boxes near the corners of the frustum are counted as visible even if they aren't.
+	pub fn in_frustum(&self, view_projection: &Matrix4<f32>) -> bool {
+		let m = view_projection;
+		let row = |i: usize| Vector4::new(m[(i, 0)], m[(i, 1)], m[(i, 2)], m[(i, 3)]);
+		let w = row(3);
+		// The left, right, bottom, top, near and far planes, with their normals pointing into the frustum
+		let planes = [w + row(0), w - row(0), w + row(1), w - row(1), w + row(2), w - row(2)];
+		for plane in planes.iter() {
+			// The corner of the box furthest along the normal of the plane
+			let corner = Vector3::new(
+				if plane.x >= 0.0 { self.max.x } else { self.min.x },
+				if plane.y >= 0.0 { self.max.y } else { self.min.y },
+				if plane.z >= 0.0 { self.max.z } else { self.min.z });
+			if plane.x * corner.x + plane.y * corner.y + plane.z * corner.z + plane.w < 0.0 {
+				return false;
+			}
+		}
+		true
+	}
+	
+	/// Gets the smallest translation of `other` that would separate it from this bounding box.
+	/// 
+	/// The translation is along the axis with the least overlap. Returns `None` if the boxes don't overlap.
+	pub fn penetration(&self, other: &Aabb) -> Option<Vector3<f32>> {
+		if !self.intersects(other) {
+			return None;
+		}
+		let direction = other.center() - self.center();
+		let mut best: Option<Vector3<f32>> = None;
+		for i in 0..3 {
+			let overlap = self.max[i].min(other.max[i]) - self.min[i].max(other.min[i]);
+			if best.map(|b| overlap < b.norm()).unwrap_or(true) {
+				let mut v = Vector3::zero();
+				v[i] = if direction[i] < 0.0 { -overlap } else { overlap };
+				best = Some(v);
+			}
+		}
+		best
+	}
+}
+
+/// Detects if the bounding boxes of `a` and `b` overlap, and if they are moving towards each other, applies an
+/// impulse to separate them along the axis of least penetration.
+/// 
+/// Only dynamic bodies are affected. Returns true if the entities were colliding.
+pub fn calc_collision(world: &mut World<f32>, a: &Entity, b: &Entity) -> bool {
+	let (aabb_a, aabb_b) = match (Aabb::from_entity(world, a), Aabb::from_entity(world, b)) {
+		(Some(aabb_a), Some(aabb_b)) => (aabb_a, aabb_b),
+		_ => return false,
+	};
+	let normal = match aabb_a.penetration(&aabb_b) {
+		Some(p) => {
+			let len = p.norm();
+			if len == 0.0 { return true; }
+			p / len
+		},
+		None => return false,
+	};
+	
+	// Gets the velocity and inverse mass of an entity. Non-dynamic bodies have an infinite mass.
+	let get = |world: &World<f32>, e: &Entity| {
+		world.rigid_body(e.body()).map(|rb| {
+			let inv_mass = if rb.status() == BodyStatus::Dynamic && rb.augmented_mass().mass() > 0.0 {
+				1.0 / rb.augmented_mass().mass()
+			} else {
+				0.0
+			};
+			(*rb.velocity(), inv_mass)
+		})
+	};
+	let ((vel_a, inv_mass_a), (vel_b, inv_mass_b)) = match (get(world, a), get(world, b)) {
+		(Some(a), Some(b)) => (a, b),
+		_ => return true,
+	};
+	
+	let inv_mass_sum = inv_mass_a + inv_mass_b;
+	let approach = (vel_b.linear - vel_a.linear).dot(&normal);
+	if inv_mass_sum == 0.0 || approach >= 0.0 {
+		// Static or already separating
+		return true;
+	}
+	
+	// Cancel the velocity along the normal
+	let impulse = normal * (-approach / inv_mass_sum);
+	if let Some(rb) = world.rigid_body_mut(a.body()) {
+		rb.set_velocity(Velocity3::new(vel_a.linear - impulse * inv_mass_a, vel_a.angular));
+	}
+	if let Some(rb) = world.rigid_body_mut(b.body()) {
+		rb.set_velocity(Velocity3::new(vel_b.linear + impulse * inv_mass_b, vel_b.angular));
+	}
+	true
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn unit_box(x: f32, y: f32, z: f32) -> Aabb {
+		Aabb::new(Vector3::new(x, y, z), Vector3::new(x + 1.0, y + 1.0, z + 1.0))
+	}
+	
+	#[test]
+	pub fn test_intersects_overlapping() {
+		let a = unit_box(0.0, 0.0, 0.0);
+		let b = unit_box(0.5, 0.5, 0.5);
+		assert!(a.intersects(&b));
+		assert!(b.intersects(&a));
+	}
+	
+	#[test]
+	pub fn test_intersects_touching() {
+		let a = unit_box(0.0, 0.0, 0.0);
+		let b = unit_box(1.0, 0.0, 0.0);
+		assert!(a.intersects(&b));
+		assert!(b.intersects(&a));
+	}
+	
+	#[test]
+	pub fn test_intersects_contained() {
+		let a = Aabb::new(Vector3::new(-2.0, -2.0, -2.0), Vector3::new(2.0, 2.0, 2.0));
+		let b = unit_box(0.0, 0.0, 0.0);
+		assert!(a.intersects(&b));
+		assert!(b.intersects(&a));
+	}
+	
+	#[test]
+	pub fn test_intersects_disjoint() {
+		let a = unit_box(0.0, 0.0, 0.0);
+		assert!(!a.intersects(&unit_box(1.5, 0.0, 0.0)));
+		assert!(!a.intersects(&unit_box(0.0, -1.5, 0.0)));
+		assert!(!a.intersects(&unit_box(0.0, 0.0, 1.5)));
+		// Overlapping on two axes isn't enough
+		assert!(!a.intersects(&unit_box(0.5, 0.5, 2.0)));
+	}
+	
+	#[test]
+	pub fn test_in_frustum() {
+		// Looking down -z from the origin, with a 90 degree field of view
+		let view_projection = Perspective3::new(1.0, ::std::f32::consts::FRAC_PI_2, 0.1, 100.0).to_homogeneous();
+		assert!(unit_box(-0.5, -0.5, -5.0).in_frustum(&view_projection));
+		// Partially inside
+		assert!(unit_box(4.5, -0.5, -5.0).in_frustum(&view_projection));
+		// Behind the camera
+		assert!(!unit_box(-0.5, -0.5, 5.0).in_frustum(&view_projection));
+		// Off to the side
+		assert!(!unit_box(10.0, -0.5, -5.0).in_frustum(&view_projection));
+		// Past the far plane
+		assert!(!unit_box(-0.5, -0.5, -200.0).in_frustum(&view_projection));
+	}
+	
+	#[test]
+	pub fn test_penetration() {
+		let a = unit_box(0.0, 0.0, 0.0);
+		assert_eq!(Some(Vector3::new(0.0, 0.25, 0.0)), a.penetration(&unit_box(0.0, 0.75, 0.0)));
+		assert_eq!(Some(Vector3::new(-0.25, 0.0, 0.0)), a.penetration(&unit_box(-0.75, 0.0, 0.0)));
+		assert_eq!(None, a.penetration(&unit_box(0.0, 0.0, 2.0)));
+	}
+}
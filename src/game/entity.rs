@@ -9,9 +9,14 @@ use np::world::World;
 use np::volumetric::Volumetric;
 
 use game::{GameState, EntityId};
-use render::{Render, RenderableMesh};
+use render::{Render, RenderableMesh, InstanceBatch};
+use util;
+
+/// The default margin of the colliders of entities, in world units.
+pub const DEFAULT_COLLIDER_MARGIN: f32 = 0.01;
 
 /// Collision type of an entity.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Collision {
 	Box,
 	Compound,
@@ -23,6 +28,8 @@ pub struct Component {
 	iso: Isometry3<f32>,
 	shape: ShapeHandle<f32>,
 	mesh: Rc<RenderableMesh>,
+	mesh_name: Option<String>,
+	wireframe: bool,
 }
 impl Component {
 	/// Constructs a new component from a shape and a mesh. The position will be at 0,0,0
@@ -32,6 +39,8 @@ impl Component {
 			iso : Isometry3::one(),
 			shape: ShapeHandle::new(shape),
 			mesh: mesh,
+			mesh_name: None,
+			wireframe: false,
 		}
 	}
 	
@@ -41,6 +50,8 @@ impl Component {
 			iso : Isometry3::one(),
 			shape: shape,
 			mesh: mesh,
+			mesh_name: None,
+			wireframe: false,
 		}
 	}
 	
@@ -51,6 +62,8 @@ impl Component {
 			iso : iso,
 			shape: ShapeHandle::new(shape),
 			mesh: mesh,
+			mesh_name: None,
+			wireframe: false,
 		}
 	}
 	
@@ -59,6 +72,8 @@ impl Component {
 			iso : iso,
 			shape: shape,
 			mesh: mesh,
+			mesh_name: None,
+			wireframe: false,
 		}
 	}
 	
@@ -73,6 +88,46 @@ impl Component {
 		self.iso.rotation = na::convert(rot);
 		self
 	}
+	
+	/// Returns the component with the specified mesh name.
+	/// 
+	/// Meshes can't be saved, so the name is saved instead, and used to find the mesh again when the component is
+	/// restored. See `GameState::save`.
+	pub fn mesh_name<S: Into<String>>(mut self, name: S) -> Component {
+		self.mesh_name = Some(name.into());
+		self
+	}
+	
+	/// Returns the component rendered in wireframe mode if `wireframe` is true, regardless of the global wireframe mode.
+	pub fn wireframe(mut self, wireframe: bool) -> Component {
+		self.wireframe = wireframe;
+		self
+	}
+	
+	/// Gets the position of the component relative to its entity.
+	pub fn iso(&self) -> Isometry3<f32> {
+		self.iso
+	}
+	
+	/// Gets the collision shape of the component.
+	pub fn shape(&self) -> &ShapeHandle<f32> {
+		&self.shape
+	}
+	
+	/// Gets the mesh of the component.
+	pub fn mesh(&self) -> &Rc<RenderableMesh> {
+		&self.mesh
+	}
+	
+	/// Gets the mesh name of the component, if it has one.
+	pub fn get_mesh_name(&self) -> Option<&str> {
+		self.mesh_name.as_ref().map(|s| s.as_str())
+	}
+	
+	/// Returns true if the component is rendered in wireframe mode, regardless of the global wireframe mode.
+	pub fn is_wireframe(&self) -> bool {
+		self.wireframe
+	}
 }
 
 /// Helper struct to build an entity.
@@ -82,13 +137,22 @@ pub struct EntityBuilder {
 	rot: Rotation3<f32>,
 	ang_vel: Vector3<f32>,
 	
-	// If None, is a static object
+	// If None, is a static or kinematic object
 	density: Option<f32>,
+	// Overrides the mass given by the density
+	mass: Option<f32>,
+	status: BodyStatus,
 	restitution: f32,
 	friction: f32,
 	
+	linear_damping: f32,
+	angular_damping: f32,
+	
 	collision: Collision,
+	sensor: bool,
 	components: Vec<Component>,
+	tag: Option<String>,
+	depth_bias: Option<(f32, f32)>,
 }
 impl EntityBuilder {
 	/// Creates a new dynamic EntityBuilder.
@@ -100,11 +164,18 @@ impl EntityBuilder {
 			ang_vel: Vector3::zero(),
 			
 			density: Some(density),
+			mass: None,
+			status: BodyStatus::Dynamic,
 			restitution: restitution,
 			friction: friction,
+			linear_damping: 0.0,
+			angular_damping: 0.0,
 			
 			collision: Collision::Compound,
+			sensor: false,
 			components: vec![],
+			tag: None,
+			depth_bias: None,
 		}
 	}
 	
@@ -117,11 +188,33 @@ impl EntityBuilder {
 			ang_vel: Vector3::zero(),
 			
 			density: None,
+			mass: None,
+			status: BodyStatus::Static,
 			restitution: restitution,
 			friction: friction,
+			linear_damping: 0.0,
+			angular_damping: 0.0,
 			
 			collision: Collision::Compound,
+			sensor: false,
 			components: vec![],
+			tag: None,
+			depth_bias: None,
+		}
+	}
+	
+	/// Creates a new kinematic EntityBuilder.
+	/// 
+	/// Kinematic entities aren't affected by gravity, forces or collisions, but move according to their velocity, and
+	/// push dynamic entities out of the way. They are moved by setting their velocity every tick, using
+	/// `GameState::set_velocity`, e.g. from a `TickCallback`.
+	/// 
+	/// Kinematic entities have infinite mass and inertia, so the impulses from collisions with them are resolved
+	/// as if they were static.
+	pub fn new_kinematic(restitution: f32, friction: f32) -> EntityBuilder {
+		EntityBuilder {
+			status: BodyStatus::Kinematic,
+			..EntityBuilder::new_static(restitution, friction)
 		}
 	}
 	
@@ -149,6 +242,35 @@ impl EntityBuilder {
 		self
 	}
 	
+	/// Sets the total mass of the entity, regardless of the volume of its shape.
+	/// 
+	/// The density is scaled so that the entity has this mass, so the mass is still distributed through the shape in
+	/// the same way. This has no effect on static and kinematic entities, which always have infinite mass.
+	pub fn mass(mut self, mass: f32) -> EntityBuilder {
+		if mass > 0.0 && mass.is_finite() {
+			self.mass = Some(mass);
+		} else {
+			warn!("Ignoring invalid entity mass: {}", mass);
+		}
+		self
+	}
+	
+	/// Sets the linear damping of the entity. (Default = 0.0)
+	/// 
+	/// See `Entity::set_linear_damping`.
+	pub fn linear_damping(mut self, damping: f32) -> EntityBuilder {
+		self.linear_damping = damping;
+		self
+	}
+	
+	/// Sets the angular damping of the entity. (Default = 0.0)
+	/// 
+	/// See `Entity::set_angular_damping`.
+	pub fn angular_damping(mut self, damping: f32) -> EntityBuilder {
+		self.angular_damping = damping;
+		self
+	}
+	
 	/// Adds a component to the entity.
 	pub fn component(mut self, component: Component) -> EntityBuilder {
 		self.components.push(component);
@@ -175,6 +297,30 @@ impl EntityBuilder {
 		self
 	}
 	
+	/// The entity will be a sensor. Sensors detect when they overlap other entities, but don't collide with them.
+	/// 
+	/// The overlaps are reported through `GameState::set_collision_callback` when they start.
+	/// The shape of the sensor is still determined by the collision type.
+	pub fn sensor(mut self) -> EntityBuilder {
+		self.sensor = true;
+		self
+	}
+	
+	/// Sets a human-readable tag for the entity, used for debugging.
+	pub fn tag<S: Into<String>>(mut self, tag: S) -> EntityBuilder {
+		self.tag = Some(tag.into());
+		self
+	}
+	
+	/// Sets the depth bias of the entity when it is rendered, as in `glPolygonOffset`.
+	/// 
+	/// Negative values pull the entity towards the camera, which resolves flicker where it is coplanar with
+	/// another surface, without moving the geometry.
+	pub fn depth_bias(mut self, factor: f32, units: f32) -> EntityBuilder {
+		self.depth_bias = Some((factor, units));
+		self
+	}
+	
 	/// Builds the entity by adding it to a GameState.
 	/// Returns the new entity ID.
 	pub fn build(self, state: &mut GameState) -> EntityId {
@@ -183,28 +329,61 @@ impl EntityBuilder {
 	
 	/// Builds the entity by adding it to the world.
 	pub fn build_world(self, world: &mut World<f32>) -> Entity {
-		Entity::with_matrix(world, self.components, self.collision, self.pos, self.vel, self.rot, self.ang_vel, self.density, self.restitution, self.friction)
+		self.build_world_with_margin(world, DEFAULT_COLLIDER_MARGIN)
+	}
+	
+	/// Builds the entity by adding it to the world, with a custom collider margin.
+	pub fn build_world_with_margin(self, world: &mut World<f32>, margin: f32) -> Entity {
+		let mut e = Entity::with_matrix(world, self.components, self.collision, self.sensor, self.pos, self.vel, self.rot, self.ang_vel, self.density, self.mass, self.status, self.restitution, self.friction, margin);
+		e.tag = self.tag;
+		e.depth_bias = self.depth_bias;
+		e.set_linear_damping(self.linear_damping);
+		e.set_angular_damping(self.angular_damping);
+		e
 	}
 }
 
 pub struct Entity {
-	meshes: Vec<(Isometry3<f32>, Rc<RenderableMesh>)>,
+	components: Vec<Component>,
+	collision: Collision,
+	/// If None, the entity has infinite mass.
+	density: Option<f32>,
+	restitution: f32,
+	friction: f32,
+	linear_damping: f32,
+	angular_damping: f32,
 	collider: ColliderHandle,
+	/// If the collider is a sensor.
+	sensor: bool,
 	body: BodyHandle,
+	/// If frozen, the status and velocity the body had before it was frozen.
+	frozen: Option<(BodyStatus, Velocity3<f32>)>,
+	/// The pose of the body before the latest physics tick, used to interpolate between ticks when rendering.
+	previous_pose: Option<Isometry3<f32>>,
+	/// If the entity should be rendered in wireframe mode.
+	wireframe: bool,
+	/// Human-readable tag, used for debugging.
+	tag: Option<String>,
+	/// The depth bias of the entity when rendered, as `(factor, units)`.
+	depth_bias: Option<(f32, f32)>,
 }
 impl Entity {
+	/// Constructs a new entity from a single component. If `density` is `None`, the entity is static.
 	pub fn new(world: &mut World<f32>, component: Component, collision: Collision, density: Option<f32>, restitution: f32, friction: f32) -> Entity {
-		Entity::with_matrix(world, vec![component], collision, Vector3::zero(), Vector3::zero(), Rotation3::identity(), Vector3::zero(), density, restitution, friction)
+		let status = if density.is_some() { BodyStatus::Dynamic } else { BodyStatus::Static };
+		Entity::with_matrix(world, vec![component], collision, false, Vector3::zero(), Vector3::zero(), Rotation3::identity(), Vector3::zero(), density, None, status, restitution, friction, DEFAULT_COLLIDER_MARGIN)
 	}
 	
-	pub fn with_matrix(world: &mut World<f32>, mut components: Vec<Component>, collision: Collision, pos: Vector3<f32>, vel: Vector3<f32>, rot: Rotation3<f32>, ang_vel: Vector3<f32>, density: Option<f32>, restitution: f32, friction: f32) -> Entity {
+	/// Constructs a new entity with the body status `status`.
+	/// 
+	/// If `sensor` is true, the entity's collider is a sensor, which detects overlaps without colliding.
+	/// If `density` is `None`, the entity has infinite mass and inertia. This should be the case for static and kinematic
+	/// entities. Otherwise, if `mass` is given, the density is scaled so that the entity has that total mass.
+	pub fn with_matrix(world: &mut World<f32>, components: Vec<Component>, collision: Collision, sensor: bool, pos: Vector3<f32>, vel: Vector3<f32>, rot: Rotation3<f32>, ang_vel: Vector3<f32>, density: Option<f32>, mass: Option<f32>, status: BodyStatus, restitution: f32, friction: f32, margin: f32) -> Entity {
 		
-		let mut bodies = Vec::new();
-		let mut meshes = Vec::new();
-		for c in components.drain(..) {
-			meshes.push((c.iso, c.mesh));
-			bodies.push((c.iso, c.shape));
-		}
+		let bodies: Vec<_> = components.iter()
+			.map(|c| (c.iso, c.shape.clone()))
+			.collect();
 		
 		let collision_shape = match collision {
 			Collision::Box => {
@@ -225,6 +404,20 @@ impl Entity {
 			}
 		};
 		
+		// Scale the density to give the requested mass
+		let density = match (density, mass) {
+			(Some(density), Some(mass)) => {
+				let unit_mass = collision_shape.mass(1.0);
+				if unit_mass > 0.0 {
+					Some(mass / unit_mass)
+				} else {
+					warn!("Can't set the mass of an entity with no volume");
+					Some(density)
+				}
+			},
+			(density, _) => density,
+		};
+		
 		// Construct rigid body
 		let body = world.add_rigid_body(
 			Isometry3::from_parts(Translation::from_vector(pos), na::convert(rot)),
@@ -239,28 +432,39 @@ impl Entity {
 			// Set linear & angular velocity
 			rbody.set_velocity(Velocity3::new(vel, ang_vel));
 			
-			// Set static status if density hasn't been given
-			if density.is_none() {
-				rbody.set_status(BodyStatus::Static);
-			} else {
-				rbody.set_status(BodyStatus::Dynamic);
-			}
+			rbody.set_status(status);
 		}
 		
 		// Add collider to world
-		let collider = world.add_collider(
-			0.01,
-			collision_shape,
-			body,
-			Isometry3::identity(),
-			Material::new(restitution, friction)
-		);
+		let collider = if sensor {
+			world.add_sensor(collision_shape, body, Isometry3::identity())
+		} else {
+			world.add_collider(
+				margin,
+				collision_shape,
+				body,
+				Isometry3::identity(),
+				Material::new(restitution, friction)
+			)
+		};
 		
 		// Create entity
 		Entity {
-			meshes: meshes,
+			components,
+			collision,
+			density,
+			restitution,
+			friction,
+			linear_damping: 0.0,
+			angular_damping: 0.0,
 			collider,
+			sensor,
 			body: body,
+			frozen: None,
+			previous_pose: None,
+			wireframe: false,
+			tag: None,
+			depth_bias: None,
 		}
 	}
 	
@@ -270,22 +474,196 @@ impl Entity {
 		world.remove_bodies(&[self.body]);
 	}
 	
-	/// Renders the entity
-	pub fn render(&self, r: &mut Render, world: &World<f32>) {
-		if let Some(model_mat) = world.rigid_body(self.body).map(|body| body.position().to_homogeneous()) {
-			for &(ref iso, ref mesh) in self.meshes.iter() {
-				mesh.render(r, model_mat * iso.to_homogeneous());
+	/// Freezes or unfreezes the entity in `world`.
+	/// 
+	/// Freezing forces the body to be static, remembering its previous status and velocity.
+	/// Unfreezing restores them.
+	pub fn set_frozen(&mut self, world: &mut World<f32>, frozen: bool) {
+		if let Some(rbody) = world.rigid_body_mut(self.body) {
+			if frozen {
+				if self.frozen.is_none() {
+					self.frozen = Some((rbody.status(), *rbody.velocity()));
+					rbody.set_velocity(Velocity3::new(Vector3::zero(), Vector3::zero()));
+					rbody.set_status(BodyStatus::Static);
+				}
+			} else if let Some((status, vel)) = self.frozen.take() {
+				rbody.set_status(status);
+				rbody.set_velocity(vel);
 			}
+		}
+	}
+	
+	/// Returns true if the entity is frozen.
+	pub fn is_frozen(&self) -> bool {
+		self.frozen.is_some()
+	}
+	
+	/// If the entity is frozen, gets the status and velocity its body had before it was frozen.
+	pub fn frozen_state(&self) -> Option<(BodyStatus, Velocity3<f32>)> {
+		self.frozen
+	}
+	
+	/// Gets the components of the entity.
+	pub fn components(&self) -> &[Component] {
+		&self.components
+	}
+	
+	/// Gets the collision type of the entity.
+	pub fn collision(&self) -> Collision {
+		self.collision
+	}
+	
+	/// Gets the density of the entity, or `None` if the entity has infinite mass.
+	pub fn density(&self) -> Option<f32> {
+		self.density
+	}
+	
+	/// Gets the restitution of the entity.
+	pub fn restitution(&self) -> f32 {
+		self.restitution
+	}
+	
+	/// Gets the friction of the entity.
+	pub fn friction(&self) -> f32 {
+		self.friction
+	}
+	
+	/// Gets the linear damping of the entity.
+	pub fn linear_damping(&self) -> f32 {
+		self.linear_damping
+	}
+	
+	/// Sets the linear damping of the entity, which acts like air resistance. (Default = 0.0)
+	/// 
+	/// Every tick the linear velocity of the entity is scaled by `1 / (1 + dt * damping)`, so without other forces
+	/// the velocity decays like `e^(-damping * t)`. Negative values are treated as 0.
+	pub fn set_linear_damping(&mut self, damping: f32) {
+		self.linear_damping = damping.max(0.0);
+	}
+	
+	/// Gets the angular damping of the entity.
+	pub fn angular_damping(&self) -> f32 {
+		self.angular_damping
+	}
+	
+	/// Sets the angular damping of the entity, which slows its rotation in the same way as `set_linear_damping`
+	/// slows its movement. (Default = 0.0)
+	pub fn set_angular_damping(&mut self, damping: f32) {
+		self.angular_damping = damping.max(0.0);
+	}
+	
+	/// Applies the damping of the entity to its velocity in `world`, over a timestep of `dt` seconds.
+	pub fn apply_damping(&self, world: &mut World<f32>, dt: f32) {
+		if self.linear_damping == 0.0 && self.angular_damping == 0.0 {
+			return;
+		}
+		if let Some(rbody) = world.rigid_body_mut(self.body) {
+			if rbody.status() != BodyStatus::Dynamic {
+				return;
+			}
+			let vel = *rbody.velocity();
+			rbody.set_velocity(Velocity3::new(
+				vel.linear / (1.0 + dt * self.linear_damping),
+				vel.angular / (1.0 + dt * self.angular_damping)));
+		}
+	}
+	
+	/// Returns true if the entity is rendered in wireframe mode, regardless of the global wireframe mode.
+	pub fn wireframe(&self) -> bool {
+		self.wireframe
+	}
+	
+	/// Sets if the entity is rendered in wireframe mode, regardless of the global wireframe mode.
+	pub fn set_wireframe(&mut self, wireframe: bool) {
+		self.wireframe = wireframe;
+	}
+	
+	/// Gets the human-readable tag of the entity, if it has one.
+	pub fn tag(&self) -> Option<&str> {
+		self.tag.as_ref().map(|s| s.as_str())
+	}
+	
+	/// Sets the human-readable tag of the entity.
+	pub fn set_tag(&mut self, tag: Option<String>) {
+		self.tag = tag;
+	}
+	
+	/// Gets the depth bias of the entity, as `(factor, units)`.
+	pub fn depth_bias(&self) -> Option<(f32, f32)> {
+		self.depth_bias
+	}
+	
+	/// Sets the depth bias of the entity, as `(factor, units)`, or `None` for no bias.
+	pub fn set_depth_bias(&mut self, depth_bias: Option<(f32, f32)>) {
+		self.depth_bias = depth_bias;
+	}
+	
+	/// Remembers the current pose of the entity in `world`. This should be called before each physics tick.
+	pub fn store_pose(&mut self, world: &World<f32>) {
+		self.previous_pose = world.rigid_body(self.body).map(|body| *body.position());
+	}
+	
+	/// Gets the pose of the entity, interpolated between its pose before the latest physics tick and its current pose.
+	/// 
+	/// `alpha` is how far between the two poses to interpolate, from 0 to 1.
+	pub fn interpolated_pose(&self, world: &World<f32>, alpha: f32) -> Option<Isometry3<f32>> {
+		let current = *world.rigid_body(self.body)?.position();
+		Some(match self.previous_pose {
+			Some(previous) if alpha < 1.0 => {
+				let alpha = alpha.max(0.0);
+				let translation = util::lerp(previous.translation.vector, current.translation.vector, alpha);
+				let rotation = previous.rotation.slerp(&current.rotation, alpha);
+				Isometry3::from_parts(Translation::from_vector(translation), rotation)
+			},
+			_ => current,
+		})
+	}
+	
+	/// Renders the entity, interpolated between the latest two physics ticks by `alpha`. See `interpolated_pose`.
+	pub fn render(&self, r: &mut Render, world: &World<f32>, alpha: f32) {
+		if let Some(model_mat) = self.interpolated_pose(world, alpha).map(|pose| pose.to_homogeneous()) {
+			r.set_entity_depth_bias(self.depth_bias);
+			for c in self.components.iter() {
+				c.mesh.render(r, model_mat * c.iso.to_homogeneous(), self.wireframe || c.wireframe);
+			}
+			r.set_entity_depth_bias(None);
 		} else {
 			warn!("Entity.render() called when Entity has invalid BodyHandle: bhandle: {:?}, chandle: {:?}", self.body, self.collider);
 		}
 	}
 	
+	/// Renders the entity like `render`, but adds the components that can be instanced to `batch` instead of drawing
+	/// them straight away. See `RenderableMesh::instance`.
+	/// 
+	/// Components are only batched if they aren't drawn in wireframe, and the entity has no depth bias.
+	pub fn render_batched(&self, r: &mut Render, world: &World<f32>, alpha: f32, batch: &mut InstanceBatch) {
+		if self.wireframe || self.depth_bias.is_some() {
+			self.render(r, world, alpha);
+			return;
+		}
+		if let Some(model_mat) = self.interpolated_pose(world, alpha).map(|pose| pose.to_homogeneous()) {
+			for c in self.components.iter() {
+				let model = model_mat * c.iso.to_homogeneous();
+				match c.mesh.instance(r, model) {
+					Some((mesh, model, color)) if !c.wireframe => batch.push(mesh, model, color),
+					_ => c.mesh.render(r, model, c.wireframe),
+				}
+			}
+		} else {
+			warn!("Entity.render_batched() called when Entity has invalid BodyHandle: bhandle: {:?}, chandle: {:?}", self.body, self.collider);
+		}
+	}
+	
 	// Gets the ColliderHandle of the Entity
 	pub fn collider(&self) -> ColliderHandle {
 		self.collider
 	}
 	
+	/// Returns true if the entity is a sensor, which detects overlaps without colliding.
+	pub fn is_sensor(&self) -> bool {
+		self.sensor
+	}
+	
 	/// Gets the BodyHandle of the Entity
 	pub fn body(&self) -> BodyHandle {
 		self.body
@@ -3,12 +3,18 @@ use std::rc::Rc;
 use std::thread::sleep;
 use std::cell::RefCell;
 
-use glutin::{VirtualKeyCode, Event, EventsLoop, MouseButton, ElementState, KeyboardInput, WindowEvent};
+use glutin::{VirtualKeyCode, Event, EventsLoop, MouseButton, MouseScrollDelta, ElementState, KeyboardInput, WindowEvent};
 use glutin::dpi::{LogicalPosition, LogicalSize};
+use rand;
 
-use game::{GameState, GameStateBuilder, KeyboardState};
+use game::{FrameStats, GameState, GameStateBuilder, KeyboardState, GamepadState};
+use game::replay::{InputFrame, InputHeader, InputRecorder, InputReplayer, RecordedEvent};
+#[cfg(feature = "gamepad")]
+use game::gamepad::GamepadPoller;
 use render::{Render, Camera};
-use settings::Settings;
+use settings::{Action, Settings, DEFAULT_PHYSICS_HZ};
+use util;
+use vfs;
 
 /// The structure that keeps track of game-wide state.
 pub struct Game {
@@ -17,14 +23,30 @@ pub struct Game {
 	settings: Settings,
 	
 	state_generator: Box<Fn(&Rc<Context>) -> GameState>,
+	/// The seed of the scene RNG, which is reseeded every time the state is generated
+	seed: u64,
 	current_state: GameState,
 	keyboard_state: KeyboardState,
+	gamepad_state: GamepadState,
+	/// Polls the gamepads, if they could be initialized
+	#[cfg(feature = "gamepad")]
+	gamepad_poller: Option<GamepadPoller>,
+	/// Records the input of each frame, if `Settings::record_input` is set
+	recorder: Option<InputRecorder>,
+	/// Replays the input of each frame, if `Settings::replay_input` is set
+	replayer: Option<InputReplayer>,
+	/// The input of the current frame, which is being recorded or was replayed
+	input_frame: InputFrame,
 	running: bool,
 	focused: bool,
 	step: bool,
 	ignore_next_mouse_movement: bool,
+	/// How many lines the mouse wheel has scrolled up since the last frame
+	scroll: f32,
 	skip_next_tick: bool,
 	rerender: bool,
+	/// If set, the next key pressed is bound to this action
+	capture_key: Option<Action>,
 }
 impl Game {
 	/// Constructs a game with the specified settings, and the default game state.
@@ -38,6 +60,27 @@ impl Game {
 		let mut render = Render::new(&events_loop, Camera::new(Vector3::new(0.0, 0.0, 0.0)), &settings)?;
 		info!("Initialized renderer");
 		
+		let replayer = match settings.replay_input {
+			Some(ref path) => {
+				info!("Replaying input from '{}'", path.display());
+				Some(InputReplayer::open(path)?)
+			},
+			None => None,
+		};
+		// Replays use the seed they were recorded with
+		let seed = match replayer {
+			Some(ref replayer) => replayer.header().seed,
+			None => settings.seed.unwrap_or_else(|| rand::thread_rng().gen()),
+		};
+		info!("Scene seed: {}", seed);
+		util::seed_scene_rng(seed);
+		let recorder = match settings.record_input {
+			Some(ref path) => {
+				info!("Recording input to '{}'", path.display());
+				Some(InputRecorder::create(path, &InputHeader { seed })?)
+			},
+			None => None,
+		};
 		let state = generator(render.context());
 		render.set_camera(state.camera().clone());
 		info!("Initialized game state");
@@ -47,27 +90,52 @@ impl Game {
 			settings,
 			
 			state_generator: generator,
+			seed,
 			current_state: state,
 			keyboard_state: KeyboardState::new(),
+			gamepad_state: GamepadState::new(),
+			#[cfg(feature = "gamepad")]
+			gamepad_poller: GamepadPoller::new().map_err(|e| warn!("{}", e)).ok(),
+			recorder,
+			replayer,
+			input_frame: InputFrame::default(),
 			running: true,
 			focused: true,
 			step: false,
 			ignore_next_mouse_movement: false,
+			scroll: 0.0,
 			skip_next_tick: true,
 			rerender: false,
+			capture_key: None,
 		})
 	}
 	
+	/// Gets the current game state.
+	pub fn current_state(&self) -> &GameState {
+		&self.current_state
+	}
+	
+	/// Binds the next key that is pressed to `action`. Pressing Escape cancels this.
+	pub fn capture_next_key(&mut self, action: Action) {
+		info!("Press a key to bind to {:?}", action);
+		self.capture_key = Some(action);
+	}
+	
 	/// Performs the main loop.
 	/// 
 	/// This will only return when the user has exited the game.
 	pub fn main_loop(&mut self) {
 		// How long each physics timestep should be.
-		const PHYSICS_HZ: u32 = 120;
-		// Maximum lag in ms simulated.
-		const MAX_LAG_MS: u64 = 500;
+		let physics_hz = if self.settings.physics_hz == 0 {
+			warn!("Invalid physics tick rate: 0Hz. Using {}Hz instead.", DEFAULT_PHYSICS_HZ);
+			DEFAULT_PHYSICS_HZ
+		} else {
+			self.settings.physics_hz
+		};
+		// Maximum lag simulated.
+		let max_lag = Duration::from_millis(self.settings.max_lag_ms as u64);
 		let sec = Duration::new(1, 0);
-		let physics_dt = sec / PHYSICS_HZ;
+		let physics_dt = sec / physics_hz;
 		
 		// Minimum amount of time to wait between ticks
 		let min_elapsed = Duration::from_millis(5);
@@ -81,10 +149,26 @@ impl Game {
 		let mut lag = Duration::from_millis(0);
 		let mut previous = Instant::now();
 		
+		// The last time a stutter was warned about. Warnings are limited to one per second.
+		let mut last_stutter_warning: Option<Instant> = None;
+		let mut stutters = 0;
+		
 		let mut previous_fps_count = Instant::now();
 		let mut frames = 0;
 		let mut fps = 0;
 		
+		// The number of physics ticks since the game was last rendered
+		let mut ticks_since_render = 0;
+		
+		// The total time from reading the input to the frame being finished by the GPU, over the last second
+		let mut total_latency = Duration::from_millis(0);
+		// The latency is only measured if it is going to be logged, as measuring it has to wait for the GPU
+		let measure_latency = log_enabled!(::log::Level::Debug);
+		
+		// The durations of the latest rendered frames
+		let mut frame_stats = FrameStats::default();
+		let mut frame_time = Stopwatch::start();
+		
 		self.render.show();
 		let mut events = Vec::new();
 		info!("Starting game main loop");
@@ -102,19 +186,25 @@ impl Game {
 			
 			// Make sure lag doesn't get bigger and bigger when the program is slow
 			// This makes the simulation kinda non-deteministic
-			if lag > Duration::from_millis(MAX_LAG_MS) {
-				lag = Duration::from_millis(MAX_LAG_MS);
+			if lag > max_lag {
+				lag = max_lag;
 			}
 			
 			// Calculate fps
 			if current - previous_fps_count >= sec {
 				previous_fps_count = Instant::now();
+				if frames > 0 {
+					let latency = total_latency.as_secs_partial() / frames as f64;
+					debug!("Average input to display latency: {:.2}ms (low latency mode {})", latency * 1000.0,
+						if self.settings.low_latency { "on" } else { "off" });
+				}
 				fps = frames;
 				frames = 0;
+				total_latency = Duration::from_millis(0);
 			}
 			
-			// Process events
-			events.clear();
+			// Process events. Events that weren't passed on last frame are kept.
+			let input_time = Instant::now();
 			let mouse_moved = self.process_events(&mut events);
 			if !self.running {
 				break;
@@ -123,34 +213,85 @@ impl Game {
 			if self.skip_next_tick {
 				lag = Duration::from_millis(0);
 				self.skip_next_tick = false;
+				events.clear();
 			}
 			
 			// Tick game
+			let force_render = self.rerender || self.settings.paused;
+			let mut n = 0;
 			if !self.rerender {
-				let mut n = 0;
 				while lag >= physics_dt {
 					n += 1;
 					lag -= physics_dt;
 				}
-				if n > 4 {
-					warn!("Stutter detected ({}ms): {} iterations needed to catch up", elapsed.as_millis_u64(), n);
-				}
-				if !self.skip_next_tick {
-					self.tick(physics_dt.as_secs_partial() as f32, n, &mut events, mouse_moved);
+				if n > self.settings.stutter_warn_iterations {
+					stutters += 1;
+					if last_stutter_warning.map(|t| current - t >= sec).unwrap_or(true) {
+						if stutters == 1 {
+							warn!("Stutter detected ({}ms): {} iterations needed to catch up", elapsed.as_millis_u64(), n);
+						} else {
+							warn!("Stutter detected ({}ms): {} iterations needed to catch up ({} stutters in the last second)", elapsed.as_millis_u64(), n, stutters);
+						}
+						last_stutter_warning = Some(current);
+						stutters = 0;
+					}
 				}
 			} else {
 				self.rerender = false;
 			}
 			
+			// Move the camera once per frame, independently of the physics rate
+			let mut camera_dt = ::std::cmp::min(elapsed, max_lag).as_secs_partial() as f32;
+			if self.replayer.is_some() {
+				// Simulate the frame for as long as it was when it was recorded, however long it actually took
+				n = self.input_frame.ticks;
+				camera_dt = self.input_frame.dt;
+			} else if self.recorder.is_some() {
+				self.input_frame.ticks = n;
+				self.input_frame.dt = camera_dt;
+				let result = self.recorder.as_mut().unwrap().record(&self.input_frame);
+				if let Err(e) = result {
+					error!("{}", e);
+					self.recorder = None;
+				}
+			}
+			
+			if !self.skip_next_tick {
+				self.tick(physics_dt.as_secs_partial() as f32, n, &mut events, mouse_moved);
+				ticks_since_render += n;
+			}
+			
 			if self.skip_next_tick {
 				lag = Duration::from_millis(0);
 				self.skip_next_tick = false;
 			}
 			
+			self.current_state.update_camera(camera_dt, &self.settings, &self.keyboard_state, &self.gamepad_state, mouse_moved, self.scroll);
+			
 			// Render to screen
 			// TODO: Render using seperate thread (mutexes?).
-			self.current_state.render(&mut self.render, fps);
-			frames += 1;
+			if force_render || ticks_since_render >= self.settings.render_every_n_ticks {
+				// Interpolate the entities by how far through the next tick we are
+				let alpha = if self.settings.paused {
+					1.0
+				} else {
+					(lag.as_secs_partial() / physics_dt.as_secs_partial()) as f32
+				};
+				let stats = if self.settings.show_stats { Some(&frame_stats) } else { None };
+				self.current_state.render(&mut self.render, fps, alpha, stats);
+				// Wait for the frame to be displayed, so that the input for the next frame is read as late as possible.
+				// Otherwise the driver can queue up frames, which are displayed a few frames after their input was read.
+				// When measuring the latency this is done in both modes, so that the time that the frame spent queued
+				// in the driver is included.
+				if self.settings.low_latency || measure_latency {
+					self.render.flush();
+					total_latency += input_time.elapsed();
+				}
+				frame_stats.push(frame_time.elapsed());
+				frame_time = Stopwatch::start();
+				frames += 1;
+				ticks_since_render = 0;
+			}
 		}
 	}
 	
@@ -172,14 +313,50 @@ impl Game {
 			self.settings.paused = true;
 		}
 		
+		if let Some(action) = self.current_state.take_capture_key() {
+			self.capture_next_key(action);
+		}
+		
+		self.scroll = 0.0;
+		
 		let mut reload_shaders = false;
+		let mut reload_textures = false;
+		let mut toggle_vsync = false;
 		let mut rerender = false;
 		let mut resized = false;
 		let mut mouse_pos = mid;
 		let ctx = self.render.context().clone();
 		
+		// Read the input of this frame from the recording, instead of from the window
+		let replayed = match self.replayer.as_mut().map(|r| r.next_frame()) {
+			Some(Ok(Some(frame))) => Some(frame),
+			Some(Ok(None)) => {
+				info!("Replay finished");
+				self.running = false;
+				return Vector2::zero();
+			},
+			Some(Err(e)) => {
+				error!("{}", e);
+				self.running = false;
+				return Vector2::zero();
+			},
+			None => None,
+		};
+		
 		let events_loop = self.events_loop.clone();
-		events_loop.borrow_mut().poll_events(|event| {
+		let mut frame_events = Vec::new();
+		events_loop.borrow_mut().poll_events(|event| frame_events.push(event));
+		let mut recorded_events = Vec::new();
+		if let Some(ref frame) = replayed {
+			// Input from the window is ignored while replaying
+			let window_id = self.render.window().id();
+			frame_events.retain(|event| RecordedEvent::from_event(event).is_none());
+			frame_events.extend(frame.events.iter().map(|event| event.to_event(window_id)));
+		} else if self.recorder.is_some() {
+			recorded_events = frame_events.iter().filter_map(RecordedEvent::from_event).collect();
+		}
+		
+		for event in frame_events {
 			// Filter out 'noisy' events from the log.
 			let uninportant = match &event {
 				Event::WindowEvent{event, ..} => match &event {
@@ -202,6 +379,20 @@ impl Game {
 				debug!("Event recieved: {:?}", event);
 			}
 			
+			// The key pressed while capturing is swallowed, so that it doesn't also do whatever it is already bound to
+			if let Some(action) = self.capture_key {
+				if let Event::WindowEvent{event: WindowEvent::KeyboardInput{input: KeyboardInput{state: ElementState::Pressed, virtual_keycode: Some(code), ..}, ..}, ..} = event {
+					self.capture_key = None;
+					if code == VirtualKeyCode::Escape {
+						info!("Cancelled binding a key to {:?}", action);
+					} else {
+						self.settings.rebind(action, code);
+						info!("Bound {:?} to {:?}", action, code);
+					}
+					continue;
+				}
+			}
+			
 			let push = match &event {
 				Event::WindowEvent{event, ..} => match &event {
 					WindowEvent::CursorMoved{..} => true,
@@ -217,6 +408,17 @@ impl Game {
 				events.push(event.clone());
 			}
 			
+			// Keep track of the modifier keys
+			match &event {
+				Event::WindowEvent{event: WindowEvent::KeyboardInput{input: KeyboardInput{modifiers, ..}, ..}, ..}
+				| Event::WindowEvent{event: WindowEvent::MouseInput{modifiers, ..}, ..}
+				| Event::WindowEvent{event: WindowEvent::MouseWheel{modifiers, ..}, ..}
+				| Event::WindowEvent{event: WindowEvent::CursorMoved{modifiers, ..}, ..} => {
+					self.keyboard_state.process_modifiers(*modifiers);
+				},
+				_ => {},
+			}
+			
 			match &event {
 				Event::WindowEvent{event, ..} => match &event {
 					WindowEvent::Destroyed => {
@@ -252,6 +454,15 @@ impl Game {
 							}
 						}
 					},
+					WindowEvent::MouseWheel{delta, ..} => {
+						if self.focused {
+							// Pixel deltas come from touchpads, so treat a few pixels as a line
+							self.scroll += match delta {
+								MouseScrollDelta::LineDelta(_, y) => *y,
+								MouseScrollDelta::PixelDelta(LogicalPosition{y, ..}) => *y as f32 / 20.0,
+							};
+						}
+					},
 					WindowEvent::Resized{..} => {
 						resized = true;
 					},
@@ -265,14 +476,14 @@ impl Game {
 						if key_state == ElementState::Pressed {
 							if code == VirtualKeyCode::Escape {
 								self.focused = false;
-							} else if Some(code) == self.settings.physics_pause {
+							} else if Some(code) == self.settings.binding(Action::Pause) {
 								self.settings.paused = !self.settings.paused;
 								if self.settings.paused {
 									info!("Game paused");
 								} else {
 									info!("Game resumed");
 								}
-							} else if Some(code) == self.settings.physics_step {
+							} else if Some(code) == self.settings.binding(Action::Step) {
 								if self.settings.paused {
 									self.settings.paused = false;
 									self.step = true;
@@ -280,9 +491,14 @@ impl Game {
 								}
 							} else if Some(code) == self.settings.reload_shaders {
 								reload_shaders = true;
+							} else if Some(code) == self.settings.reload_textures {
+								reload_textures = true;
+							} else if Some(code) == self.settings.vsync_toggle {
+								toggle_vsync = true;
 							} else if Some(code) == self.settings.reset_state {
 								info!("Resetting game state...");
 								let sw = Stopwatch::start();
+								util::seed_scene_rng(self.seed);
 								self.current_state = (self.state_generator)(&ctx);
 								info!("Reset game state ({}ms)", sw.elapsed_ms());
 								self.skip_next_tick = true;
@@ -293,7 +509,7 @@ impl Game {
 				},
 				_ => {},
 			}
-		});
+		}
 		
 		// Reload shaders
 		if reload_shaders {
@@ -306,6 +522,44 @@ impl Game {
 			self.skip_next_tick = true;
 		}
 		
+		// Reload textures
+		if reload_textures {
+			info!("Reloading textures");
+			let s = Stopwatch::start();
+			match vfs::reload_cached_textures(&ctx) {
+				Ok(n) => info!("Reloaded {} textures ({}ms)", n, s.elapsed_ms()),
+				Err(e) => error!("Error reloading textures: {}", e),
+			}
+			self.skip_next_tick = true;
+		}
+		
+		// Toggle vsync
+		if toggle_vsync {
+			let vsync = !self.settings.vsync;
+			let s = Stopwatch::start();
+			match self.render.set_vsync(&events_loop.borrow(), vsync) {
+				Ok(()) => {
+					self.settings.vsync = vsync;
+					info!("Turned vsync {} ({}ms)", if vsync { "on" } else { "off" }, s.elapsed_ms());
+				},
+				Err(e) => error!("Error toggling vsync: {}", e),
+			}
+			self.skip_next_tick = true;
+		}
+		
+		// Poll gamepads
+		if let Some(ref frame) = replayed {
+			self.gamepad_state = frame.gamepad.clone();
+		} else {
+			#[cfg(feature = "gamepad")]
+			{
+				if let Some(ref mut poller) = self.gamepad_poller {
+					poller.poll(&mut self.gamepad_state);
+				}
+			}
+		}
+		self.current_state.set_gamepad_state(self.gamepad_state.clone());
+		
 		if resized {
 			debug!("Resizing renderer");
 			rerender = true;
@@ -321,16 +575,38 @@ impl Game {
 			self.render.input_normal();
 		}
 		
-		if self.focused {
+		let mouse_moved = if self.focused {
 			mouse_pos - mid
 		} else {
 			Vector2::new(0.0, 0.0)
+		};
+		
+		if let Some(frame) = replayed {
+			self.settings.paused = frame.paused;
+			let mouse_moved = Vector2::new(frame.mouse_moved[0], frame.mouse_moved[1]);
+			self.input_frame = frame;
+			mouse_moved
+		} else {
+			if self.recorder.is_some() {
+				self.input_frame = InputFrame {
+					ticks: 0,
+					dt: 0.0,
+					paused: self.settings.paused,
+					mouse_moved: [mouse_moved.x, mouse_moved.y],
+					gamepad: self.gamepad_state.clone(),
+					events: recorded_events,
+				};
+			}
+			mouse_moved
 		}
 	}
 	
 	/// Ticks the game.
 	/// `dt` is the number of seconds since last frame.
 	/// `n` is the number of iterations to do.
+	/// 
+	/// `events` are the events that have occured since they were last passed on, in the order they occured.
+	/// They are distributed over the iterations, and removed from `events`. If `n` is 0, they are kept until the next tick.
 	pub fn tick(&mut self, dt: f32, n: u32, events: &mut Vec<Event>, mouse_moved: Vector2<f64>) {
 		if n == 0 {
 			return;
@@ -340,12 +616,7 @@ impl Game {
 		} else {
 			trace!("Game tick: {}s ({} iterations)", dt, n);
 		}
-		// TODO: Interpolate mouse_moved.
-		// TODO: Extension: Interpolate events.
-		// Tick next state
-		self.current_state.tick(dt, &self.settings, events, mouse_moved);
-		for _ in 1..n {
-			self.current_state.tick(dt, &self.settings, &mut Vec::with_capacity(0), Vector2::zero());
-		}
+		
+		self.current_state.tick_frame(dt, n, &self.settings, events, mouse_moved);
 	}
 }
@@ -0,0 +1,204 @@
+use prelude::*;
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "gamepad")]
+use gilrs::{self, Gilrs, EventType};
+
+/// An analog axis of a gamepad. The values of the stick axes go from -1 to 1, with up and right being positive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+	LeftStickX,
+	LeftStickY,
+	RightStickX,
+	RightStickY,
+}
+impl GamepadAxis {
+	#[cfg(feature = "gamepad")]
+	fn from_gilrs(axis: gilrs::Axis) -> Option<GamepadAxis> {
+		match axis {
+			gilrs::Axis::LeftStickX  => Some(GamepadAxis::LeftStickX),
+			gilrs::Axis::LeftStickY  => Some(GamepadAxis::LeftStickY),
+			gilrs::Axis::RightStickX => Some(GamepadAxis::RightStickX),
+			gilrs::Axis::RightStickY => Some(GamepadAxis::RightStickY),
+			_ => None,
+		}
+	}
+}
+
+/// A button of a gamepad. The face buttons are named by their position, e.g. `South` is A on an Xbox controller.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+	South,
+	East,
+	North,
+	West,
+	LeftTrigger,
+	LeftTrigger2,
+	RightTrigger,
+	RightTrigger2,
+	Select,
+	Start,
+	LeftThumb,
+	RightThumb,
+	DPadUp,
+	DPadDown,
+	DPadLeft,
+	DPadRight,
+}
+impl GamepadButton {
+	#[cfg(feature = "gamepad")]
+	fn from_gilrs(button: gilrs::Button) -> Option<GamepadButton> {
+		match button {
+			gilrs::Button::South         => Some(GamepadButton::South),
+			gilrs::Button::East          => Some(GamepadButton::East),
+			gilrs::Button::North         => Some(GamepadButton::North),
+			gilrs::Button::West          => Some(GamepadButton::West),
+			gilrs::Button::LeftTrigger   => Some(GamepadButton::LeftTrigger),
+			gilrs::Button::LeftTrigger2  => Some(GamepadButton::LeftTrigger2),
+			gilrs::Button::RightTrigger  => Some(GamepadButton::RightTrigger),
+			gilrs::Button::RightTrigger2 => Some(GamepadButton::RightTrigger2),
+			gilrs::Button::Select        => Some(GamepadButton::Select),
+			gilrs::Button::Start         => Some(GamepadButton::Start),
+			gilrs::Button::LeftThumb     => Some(GamepadButton::LeftThumb),
+			gilrs::Button::RightThumb    => Some(GamepadButton::RightThumb),
+			gilrs::Button::DPadUp        => Some(GamepadButton::DPadUp),
+			gilrs::Button::DPadDown      => Some(GamepadButton::DPadDown),
+			gilrs::Button::DPadLeft      => Some(GamepadButton::DPadLeft),
+			gilrs::Button::DPadRight     => Some(GamepadButton::DPadRight),
+			_ => None,
+		}
+	}
+}
+
+/// Keeps track of the axes and buttons of the gamepads, which are all treated as one.
+/// 
+/// Gamepads are only polled if the `gamepad` feature is enabled. Otherwise the sticks are always centred, and no
+/// buttons are pressed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GamepadState {
+	axes: HashMap<GamepadAxis, f32>,
+	pressed: HashSet<GamepadButton>,
+}
+impl GamepadState {
+	/// Constructs a new GamepadState with the sticks centred and all the buttons released.
+	pub fn new() -> GamepadState {
+		GamepadState::default()
+	}
+	
+	/// Gets the raw value of `axis`, without a deadzone applied.
+	pub fn axis(&self, axis: GamepadAxis) -> f32 {
+		self.axes.get(&axis).cloned().unwrap_or(0.0)
+	}
+	
+	/// Gets the position of the left stick, with a radial `deadzone` applied. See `apply_deadzone`.
+	pub fn left_stick(&self, deadzone: f32) -> Vector2<f32> {
+		apply_deadzone(Vector2::new(self.axis(GamepadAxis::LeftStickX), self.axis(GamepadAxis::LeftStickY)), deadzone)
+	}
+	
+	/// Gets the position of the right stick, with a radial `deadzone` applied. See `apply_deadzone`.
+	pub fn right_stick(&self, deadzone: f32) -> Vector2<f32> {
+		apply_deadzone(Vector2::new(self.axis(GamepadAxis::RightStickX), self.axis(GamepadAxis::RightStickY)), deadzone)
+	}
+	
+	/// Returns true if `button` is pressed.
+	pub fn is_pressed(&self, button: GamepadButton) -> bool {
+		self.pressed.contains(&button)
+	}
+	/// Returns true if `button` is released.
+	pub fn is_released(&self, button: GamepadButton) -> bool {
+		!self.is_pressed(button)
+	}
+	
+	/// Processes an axis moving and updates the internal state.
+	pub fn process_axis(&mut self, axis: GamepadAxis, value: f32) {
+		self.axes.insert(axis, value.max(-1.0).min(1.0));
+	}
+	
+	/// Processes a button being pressed or released and updates the internal state.
+	pub fn process_button(&mut self, button: GamepadButton, pressed: bool) {
+		if pressed {
+			self.pressed.insert(button);
+		} else {
+			self.pressed.remove(&button);
+		}
+	}
+	
+	/// Centres the sticks and releases all of the buttons, e.g. when a gamepad is disconnected.
+	pub fn clear(&mut self) {
+		self.axes.clear();
+		self.pressed.clear();
+	}
+}
+
+/// Applies a radial deadzone to the stick position `v`.
+/// 
+/// Positions within `deadzone` of the centre are treated as centred, and the rest of the range is rescaled so that
+/// the stick still reaches a length of 1 at the edge.
+pub fn apply_deadzone(v: Vector2<f32>, deadzone: f32) -> Vector2<f32> {
+	let len = v.norm();
+	if len <= deadzone || deadzone >= 1.0 {
+		Vector2::zero()
+	} else {
+		v / len * ((len - deadzone) / (1.0 - deadzone)).min(1.0)
+	}
+}
+
+/// Polls the connected gamepads with `gilrs`.
+#[cfg(feature = "gamepad")]
+pub struct GamepadPoller {
+	gilrs: Gilrs,
+}
+#[cfg(feature = "gamepad")]
+impl GamepadPoller {
+	/// Starts listening for gamepads.
+	pub fn new() -> Result<GamepadPoller, String> {
+		let gilrs = Gilrs::new().map_err(|e| format!("could not initialize gamepads: {}", e))?;
+		for (_, gamepad) in gilrs.gamepads() {
+			info!("Found gamepad: {}", gamepad.name());
+		}
+		Ok(GamepadPoller { gilrs })
+	}
+	
+	/// Processes all of the gamepad events since the last poll into `state`.
+	pub fn poll(&mut self, state: &mut GamepadState) {
+		while let Some(gilrs::Event { event, .. }) = self.gilrs.next_event() {
+			trace!("Gamepad event recieved: {:?}", event);
+			match event {
+				EventType::AxisChanged(axis, value, _) => {
+					if let Some(axis) = GamepadAxis::from_gilrs(axis) {
+						state.process_axis(axis, value);
+					}
+				},
+				EventType::ButtonPressed(button, _) => {
+					if let Some(button) = GamepadButton::from_gilrs(button) {
+						state.process_button(button, true);
+					}
+				},
+				EventType::ButtonReleased(button, _) => {
+					if let Some(button) = GamepadButton::from_gilrs(button) {
+						state.process_button(button, false);
+					}
+				},
+				EventType::Connected => info!("Gamepad connected"),
+				EventType::Disconnected => {
+					info!("Gamepad disconnected");
+					state.clear();
+				},
+				_ => {},
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	pub fn test_deadzone() {
+		assert_eq!(Vector2::zero(), apply_deadzone(Vector2::new(0.1, 0.0), 0.2));
+		assert_eq!(Vector2::new(0.0, 1.0), apply_deadzone(Vector2::new(0.0, 1.0), 0.2));
+		let v = apply_deadzone(Vector2::new(-0.6, 0.0), 0.2);
+		assert!((v.x + 0.5).abs() < 1e-6 && v.y == 0.0, "{:?}", v);
+	}
+}
@@ -0,0 +1,328 @@
+//! Calculation of the relative gravity between bodies.
+use prelude::*;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// A body that attracts, and is attracted by, other bodies.
+#[derive(Copy, Clone, Debug)]
+pub struct GravityBody {
+	pub pos: Vector3<f32>,
+	pub mass: f32,
+	/// Static bodies don't attract each other.
+	pub is_static: bool,
+}
+
+/// The default softening distance for `Gravity::Relative`, in metres. See `GameState::set_gravity_softening`.
+pub const DEFAULT_SOFTENING: f32 = 0.01;
+
+/// The maximum depth of the Barnes-Hut octree. Bodies that are still together at this depth are stored in the same leaf.
+const MAX_OCTREE_DEPTH: u32 = 32;
+
+/// Calculates the gravitational force on a body of mass `mass` at `pos` from a mass of `other_mass` at `other_pos`.
+/// 
+/// `softening` is added in quadrature to the distance between the masses, which stops the force blowing up when they
+/// get close.
+fn force_between(pos: Vector3<f32>, mass: f32, other_pos: Vector3<f32>, other_mass: f32, g: f32, softening: f32) -> Vector3<f32> {
+	// Get vector from a to b
+	let v = other_pos - pos;
+	let len_sq = v.norm_squared() + softening * softening;
+	v / len_sq.sqrt() * ((g * mass * other_mass) / len_sq)
+}
+
+/// Calls `f` with the index of each body, in parallel if the `parallel` feature is enabled.
+fn map_bodies<F>(bodies: &[GravityBody], f: F) -> Vec<Vector3<f32>> where F: Fn(usize) -> Vector3<f32> + Sync {
+	#[cfg(feature = "parallel")]
+	let it = (0..bodies.len()).into_par_iter();
+	#[cfg(not(feature = "parallel"))]
+	let it = 0..bodies.len();
+	it.map(f).collect()
+}
+
+/// Calculates the net gravitational force on body `i`, summing the forces from the other bodies in order.
+fn net_force_on(bodies: &[GravityBody], i: usize, g: f32, softening: f32) -> Vector3<f32> {
+	let a = &bodies[i];
+	let mut f = Vector3::zero();
+	for (j, b) in bodies.iter().enumerate() {
+		if i == j || (a.is_static && b.is_static) {
+			continue;
+		}
+		f += force_between(a.pos, a.mass, b.pos, b.mass, g, softening);
+	}
+	f
+}
+
+/// Calculates the net gravitational force on each body with a pairwise double loop.
+/// 
+/// `g` is the gravitational constant. `softening` is a distance that is added in quadrature to the distance between
+/// bodies, which stops the force blowing up when bodies get close, and stops coincident bodies producing NaNs.
+pub fn net_forces_serial(bodies: &[GravityBody], g: f32, softening: f32) -> Vec<Vector3<f32>> {
+	let mut forces = vec![Vector3::zero(); bodies.len()];
+	for i in 0..bodies.len() {
+		for j in i + 1..bodies.len() {
+			if bodies[i].is_static && bodies[j].is_static {
+				continue;
+			}
+			let (a, b) = (&bodies[i], &bodies[j]);
+			let f = force_between(a.pos, a.mass, b.pos, b.mass, g, softening);
+			forces[i] += f;
+			forces[j] -= f;
+		}
+	}
+	forces
+}
+
+/// Calculates the net gravitational force on each body, with each body's force calculated independently.
+/// 
+/// With the `parallel` feature the bodies are spread over multiple threads. Each body's forces are always summed in
+/// the same order, so the result is deterministic no matter how the work is split up. This does twice as many force
+/// calculations as `net_forces_serial`, so it is only faster with enough bodies and cores.
+/// 
+/// `g` is the gravitational constant, and `softening` is as in `net_forces_serial`.
+pub fn net_forces_parallel(bodies: &[GravityBody], g: f32, softening: f32) -> Vec<Vector3<f32>> {
+	map_bodies(bodies, |i| net_force_on(bodies, i, g, softening))
+}
+
+/// Calculates the net gravitational force on each body, in parallel if the `parallel` feature is enabled.
+pub fn net_forces(bodies: &[GravityBody], g: f32, softening: f32) -> Vec<Vector3<f32>> {
+	if cfg!(feature = "parallel") {
+		net_forces_parallel(bodies, g, softening)
+	} else {
+		net_forces_serial(bodies, g, softening)
+	}
+}
+
+/// Approximates the net gravitational force on each body with the Barnes-Hut algorithm, in O(n log n) time.
+/// 
+/// The bodies are put into an octree, and distant clusters of bodies are treated as a single body at their center
+/// of mass. A cluster is distant if the ratio of the size of its octree node to its distance is less than `theta`.
+/// A `theta` of `0.0` gives the same result as `net_forces_serial`, and larger values are faster but less accurate.
+/// `0.5` is a good compromise.
+/// 
+/// `softening` is a distance that is added in quadrature to the distance between bodies, which stops the force blowing
+/// up when bodies get close. Static bodies attract the other bodies, but no force is calculated for them.
+/// 
+/// `g` is the gravitational constant.
+pub fn net_forces_barnes_hut(bodies: &[GravityBody], g: f32, theta: f32, softening: f32) -> Vec<Vector3<f32>> {
+	let tree = Octree::new(bodies);
+	map_bodies(bodies, |i| {
+		if bodies[i].is_static {
+			Vector3::zero()
+		} else {
+			tree.net_force_on(bodies, i, g, theta, softening)
+		}
+	})
+}
+
+/// A node of the Barnes-Hut octree.
+struct OctreeNode {
+	/// Center of the node's cube.
+	center: Vector3<f32>,
+	/// Half of the length of the node's cube.
+	half_size: f32,
+	/// Total mass of the bodies in the node.
+	mass: f32,
+	/// Center of mass of the bodies in the node.
+	center_of_mass: Vector3<f32>,
+	/// Indices of the child nodes in the octree.
+	children: Vec<usize>,
+	/// If the node is a leaf, the indices of the bodies in it.
+	bodies: Vec<usize>,
+}
+
+/// An octree of the masses of a set of bodies.
+struct Octree {
+	nodes: Vec<OctreeNode>,
+}
+impl Octree {
+	/// Builds an octree containing `bodies`.
+	fn new(bodies: &[GravityBody]) -> Octree {
+		let mut tree = Octree { nodes: Vec::new() };
+		if bodies.is_empty() {
+			return tree;
+		}
+		
+		// Find the bounding cube of the bodies
+		let mut min = bodies[0].pos;
+		let mut max = bodies[0].pos;
+		for b in bodies.iter() {
+			min = Vector3::new(min.x.min(b.pos.x), min.y.min(b.pos.y), min.z.min(b.pos.z));
+			max = Vector3::new(max.x.max(b.pos.x), max.y.max(b.pos.y), max.z.max(b.pos.z));
+		}
+		let extent = max - min;
+		let half_size = extent.x.max(extent.y).max(extent.z) / 2.0 + ::std::f32::EPSILON;
+		
+		tree.build(bodies, (0..bodies.len()).collect(), (min + max) / 2.0, half_size, 0);
+		tree
+	}
+	
+	/// Adds a node containing the bodies at `indices` to the tree, returning the index of the node.
+	fn build(&mut self, bodies: &[GravityBody], indices: Vec<usize>, center: Vector3<f32>, half_size: f32, depth: u32) -> usize {
+		let mut mass = 0.0;
+		let mut weighted_pos = Vector3::zero();
+		for &i in indices.iter() {
+			mass += bodies[i].mass;
+			weighted_pos += bodies[i].pos * bodies[i].mass;
+		}
+		let center_of_mass = if mass > 0.0 { weighted_pos / mass } else { center };
+		
+		let node = self.nodes.len();
+		self.nodes.push(OctreeNode {
+			center,
+			half_size,
+			mass,
+			center_of_mass,
+			children: Vec::new(),
+			bodies: Vec::new(),
+		});
+		
+		if indices.len() <= 1 || depth >= MAX_OCTREE_DEPTH {
+			self.nodes[node].bodies = indices;
+			return node;
+		}
+		
+		// Split the bodies into octants
+		let mut octants: Vec<Vec<usize>> = vec![Vec::new(); 8];
+		for &i in indices.iter() {
+			let p = bodies[i].pos;
+			let octant = (p.x >= center.x) as usize | ((p.y >= center.y) as usize) << 1 | ((p.z >= center.z) as usize) << 2;
+			octants[octant].push(i);
+		}
+		let quarter_size = half_size / 2.0;
+		for (octant, octant_indices) in octants.into_iter().enumerate() {
+			if octant_indices.is_empty() {
+				continue;
+			}
+			let offset = Vector3::new(
+				if octant & 1 != 0 { quarter_size } else { -quarter_size },
+				if octant & 2 != 0 { quarter_size } else { -quarter_size },
+				if octant & 4 != 0 { quarter_size } else { -quarter_size });
+			let child = self.build(bodies, octant_indices, center + offset, quarter_size, depth + 1);
+			self.nodes[node].children.push(child);
+		}
+		node
+	}
+	
+	/// Approximates the net gravitational force on body `i`.
+	fn net_force_on(&self, bodies: &[GravityBody], i: usize, g: f32, theta: f32, softening: f32) -> Vector3<f32> {
+		let body = &bodies[i];
+		let mut f = Vector3::zero();
+		if self.nodes.is_empty() {
+			return f;
+		}
+		
+		let mut stack = vec![0];
+		while let Some(n) = stack.pop() {
+			let node = &self.nodes[n];
+			if node.children.is_empty() {
+				for &j in node.bodies.iter() {
+					if i != j {
+						f += force_between(body.pos, body.mass, bodies[j].pos, bodies[j].mass, g, softening);
+					}
+				}
+				continue;
+			}
+			
+			// Only approximate nodes that don't contain the body, so that it never attracts itself
+			let d = body.pos - node.center;
+			let contains = d.x.abs() <= node.half_size && d.y.abs() <= node.half_size && d.z.abs() <= node.half_size;
+			let dist = (node.center_of_mass - body.pos).norm();
+			if !contains && node.half_size * 2.0 < theta * dist {
+				f += force_between(body.pos, body.mass, node.center_of_mass, node.mass, g, softening);
+			} else {
+				stack.extend(node.children.iter().cloned());
+			}
+		}
+		f
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	/// Generates a scattered set of bodies, deterministically.
+	fn test_bodies(n: usize) -> Vec<GravityBody> {
+		(0..n).map(|i| {
+			let k = i as f32;
+			GravityBody {
+				pos: Vector3::new((k * 1.7).sin() * 10.0 + k, (k * 2.3).cos() * 10.0, (k * 0.7).sin() * 5.0 - k * 0.5),
+				mass: 1.0 + (k * 3.1).sin().abs() * 10.0,
+				is_static: i % 7 == 3,
+			}
+		}).collect()
+	}
+	
+	#[test]
+	pub fn test_parallel_matches_serial() {
+		let bodies = test_bodies(100);
+		let serial = net_forces_serial(&bodies, 6.674, 0.0);
+		let parallel = net_forces_parallel(&bodies, 6.674, 0.0);
+		assert_eq!(serial.len(), parallel.len());
+		for (s, p) in serial.iter().zip(parallel.iter()) {
+			let tolerance = s.norm().max(1.0) * 1e-4;
+			assert!((s - p).norm() <= tolerance, "serial: {:?}, parallel: {:?}", s, p);
+		}
+	}
+	
+	#[test]
+	pub fn test_parallel_is_deterministic() {
+		let bodies = test_bodies(200);
+		assert_eq!(net_forces_parallel(&bodies, 1.0, 0.0), net_forces_parallel(&bodies, 1.0, 0.0));
+	}
+	
+	/// Returns the total error of `approx` relative to the total magnitude of `exact`.
+	fn relative_error(exact: &[Vector3<f32>], approx: &[Vector3<f32>]) -> f32 {
+		let error: f32 = exact.iter().zip(approx.iter()).map(|(e, a)| (e - a).norm()).sum();
+		let total: f32 = exact.iter().map(|e| e.norm()).sum();
+		error / total
+	}
+	
+	#[test]
+	pub fn test_barnes_hut_exact_with_zero_theta() {
+		let bodies: Vec<_> = test_bodies(50).into_iter().map(|b| GravityBody { is_static: false, ..b }).collect();
+		let exact = net_forces_serial(&bodies, 1.0, 0.0);
+		let approx = net_forces_barnes_hut(&bodies, 1.0, 0.0, 0.0);
+		assert!(relative_error(&exact, &approx) < 1e-4);
+	}
+	
+	#[test]
+	pub fn test_barnes_hut_matches_brute_force() {
+		let bodies: Vec<_> = test_bodies(100).into_iter().map(|b| GravityBody { is_static: false, ..b }).collect();
+		let exact = net_forces_serial(&bodies, 1.0, 0.0);
+		for &theta in [0.3, 0.5, 0.7].iter() {
+			let approx = net_forces_barnes_hut(&bodies, 1.0, theta, 0.0);
+			let error = relative_error(&exact, &approx);
+			assert!(error < 0.02, "theta: {}, relative error: {}", theta, error);
+		}
+	}
+	
+	#[test]
+	pub fn test_barnes_hut_coincident_bodies() {
+		let body = GravityBody { pos: Vector3::new(1.0, 2.0, 3.0), mass: 1.0, is_static: false };
+		let forces = net_forces_barnes_hut(&[body, body, body], 1.0, 0.5, 0.1);
+		for f in forces.iter() {
+			assert_eq!(Vector3::zero(), *f);
+		}
+	}
+	
+	#[test]
+	pub fn test_coincident_bodies() {
+		let body = GravityBody { pos: Vector3::new(1.0, 2.0, 3.0), mass: 1.0, is_static: false };
+		let bodies = [body, body];
+		for f in net_forces_serial(&bodies, 1.0, DEFAULT_SOFTENING).iter().chain(net_forces_parallel(&bodies, 1.0, DEFAULT_SOFTENING).iter()) {
+			assert_eq!(Vector3::zero(), *f);
+		}
+	}
+	
+	#[test]
+	pub fn test_static_bodies_dont_attract() {
+		let bodies = [
+			GravityBody { pos: Vector3::new(0.0, 0.0, 0.0), mass: 1.0, is_static: true },
+			GravityBody { pos: Vector3::new(1.0, 0.0, 0.0), mass: 1.0, is_static: true },
+		];
+		for f in net_forces_serial(&bodies, 1.0, 0.0).iter().chain(net_forces_parallel(&bodies, 1.0, 0.0).iter()) {
+			assert_eq!(Vector3::zero(), *f);
+		}
+	}
+}
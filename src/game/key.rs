@@ -1,19 +1,76 @@
 use std::collections::HashSet;
 
-use glutin::{VirtualKeyCode, ElementState};
+use glutin::{VirtualKeyCode, ElementState, ModifiersState};
+
+/// The state of the modifier keys.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifiers {
+	shift: bool,
+	ctrl: bool,
+	alt: bool,
+	logo: bool,
+}
+impl Modifiers {
+	/// Returns true if a Shift key is pressed.
+	pub fn shift(&self) -> bool {
+		self.shift
+	}
+	/// Returns true if a Control key is pressed.
+	pub fn ctrl(&self) -> bool {
+		self.ctrl
+	}
+	/// Returns true if an Alt key is pressed.
+	pub fn alt(&self) -> bool {
+		self.alt
+	}
+	/// Returns true if a logo key (the Windows key, or the Command key on Mac) is pressed.
+	pub fn logo(&self) -> bool {
+		self.logo
+	}
+}
+impl From<ModifiersState> for Modifiers {
+	fn from(state: ModifiersState) -> Modifiers {
+		Modifiers {
+			shift: state.shift,
+			ctrl: state.ctrl,
+			alt: state.alt,
+			logo: state.logo,
+		}
+	}
+}
+impl From<Modifiers> for ModifiersState {
+	fn from(modifiers: Modifiers) -> ModifiersState {
+		ModifiersState {
+			shift: modifiers.shift,
+			ctrl: modifiers.ctrl,
+			alt: modifiers.alt,
+			logo: modifiers.logo,
+		}
+	}
+}
 
 /// Keeps track of which keys have been pressed.
 pub struct KeyboardState {
 	pressed: HashSet<VirtualKeyCode>,
+	/// The keys that were pressed at the end of the last frame
+	previous: HashSet<VirtualKeyCode>,
+	modifiers: Modifiers,
 }
 impl KeyboardState {
 	/// Constructs a new KeyboardState with all the keys released.
 	pub fn new() -> KeyboardState {
 		KeyboardState {
 			pressed: HashSet::new(),
+			previous: HashSet::new(),
+			modifiers: Modifiers::default(),
 		}
 	}
 	
+	/// Gets the state of the modifier keys, as of the latest keyboard or mouse event.
+	pub fn modifiers(&self) -> Modifiers {
+		self.modifiers
+	}
+	
 	/// Returns true if `key` is pressed.
 	pub fn is_pressed(&self, key: &VirtualKeyCode) -> bool {
 		self.pressed.contains(key)
@@ -23,6 +80,26 @@ impl KeyboardState {
 		!self.is_pressed(key)
 	}
 	
+	/// Returns true if `key` was pressed since the last call to `end_frame`.
+	/// 
+	/// Callers relying on this must make sure that `end_frame` is called exactly once per tick.
+	/// `GameState::tick` does this after calling the tick callback.
+	pub fn was_just_pressed(&self, key: &VirtualKeyCode) -> bool {
+		self.pressed.contains(key) && !self.previous.contains(key)
+	}
+	/// Returns true if `key` was released since the last call to `end_frame`.
+	/// 
+	/// Callers relying on this must make sure that `end_frame` is called exactly once per tick.
+	/// `GameState::tick` does this after calling the tick callback.
+	pub fn was_just_released(&self, key: &VirtualKeyCode) -> bool {
+		!self.pressed.contains(key) && self.previous.contains(key)
+	}
+	
+	/// Remembers which keys are currently pressed, for `was_just_pressed` and `was_just_released`.
+	pub fn end_frame(&mut self) {
+		self.previous.clone_from(&self.pressed);
+	}
+	
 	/// Processes a keyboard event and updated the internal state.
 	pub fn process_event(&mut self, key_state: ElementState, code: VirtualKeyCode) {
 		match key_state {
@@ -34,4 +111,9 @@ impl KeyboardState {
 			}
 		}
 	}
+	
+	/// Updates the state of the modifier keys.
+	pub fn process_modifiers(&mut self, modifiers: ModifiersState) {
+		self.modifiers = modifiers.into();
+	}
 }
@@ -4,10 +4,19 @@ mod state_builder;
 mod game;
 mod entity;
 mod key;
+mod mouse;
+mod gamepad;
+mod stats;
+pub mod gravity;
+pub mod save;
+pub mod replay;
 
-pub use self::state::{GameState, TickCallback, RenderCallback};
+pub use self::state::{GameState, TickCallback, CollisionCallback, RenderCallback};
 pub use self::state_builder::GameStateBuilder;
-pub use self::state::{EntityId, Gravity};
+pub use self::state::{EntityId, SpringId, Gravity};
 pub use self::game::Game;
-pub use self::entity::{Entity, EntityBuilder, Component};
-pub use self::key::KeyboardState;
+pub use self::entity::{Entity, EntityBuilder, Component, Collision, DEFAULT_COLLIDER_MARGIN};
+pub use self::key::{KeyboardState, Modifiers};
+pub use self::mouse::MouseState;
+pub use self::gamepad::{GamepadState, GamepadAxis, GamepadButton, apply_deadzone};
+pub use self::stats::{FrameStats, DEFAULT_FRAME_STATS_LEN};
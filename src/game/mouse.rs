@@ -0,0 +1,50 @@
+use prelude::*;
+use std::collections::HashSet;
+
+use glutin::{MouseButton, ElementState};
+
+/// Keeps track of which mouse buttons have been pressed, and where the cursor is.
+pub struct MouseState {
+	pressed: HashSet<MouseButton>,
+	position: Vector2<f64>,
+}
+impl MouseState {
+	/// Constructs a new MouseState with all the buttons released, and the cursor at the top-left of the window.
+	pub fn new() -> MouseState {
+		MouseState {
+			pressed: HashSet::new(),
+			position: Vector2::zero(),
+		}
+	}
+	
+	/// Returns true if `button` is pressed.
+	pub fn is_pressed(&self, button: MouseButton) -> bool {
+		self.pressed.contains(&button)
+	}
+	/// Returns true if `button` is released.
+	pub fn is_released(&self, button: MouseButton) -> bool {
+		!self.is_pressed(button)
+	}
+	
+	/// Gets the position of the cursor in the window, in logical pixels relative to the top-left of the window.
+	pub fn position(&self) -> Vector2<f64> {
+		self.position
+	}
+	
+	/// Processes a mouse button event and updates the internal state.
+	pub fn process_event(&mut self, button_state: ElementState, button: MouseButton) {
+		match button_state {
+			ElementState::Pressed => {
+				self.pressed.insert(button);
+			},
+			ElementState::Released => {
+				self.pressed.remove(&button);
+			}
+		}
+	}
+	
+	/// Processes a cursor movement and updates the internal state.
+	pub fn process_cursor_moved(&mut self, position: Vector2<f64>) {
+		self.position = position;
+	}
+}
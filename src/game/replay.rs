@@ -0,0 +1,395 @@
+//! Recording and replaying the input of each frame.
+//!
+//! See `Settings::record_input` and `Settings::replay_input`.
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+
+use glutin::{DeviceId, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent, WindowId};
+use glutin::dpi::LogicalPosition;
+use serde::Serialize;
+use serde_json;
+
+use game::{GamepadState, Modifiers};
+use settings::{key_from_name, key_name};
+
+/// The first line of an input recording.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputHeader {
+	/// The seed of the scene RNG when the recording was made
+	pub seed: u64,
+}
+
+/// The input of one frame.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputFrame {
+	/// The number of physics ticks that were simulated
+	pub ticks: u32,
+	/// The number of seconds that the camera was moved for
+	pub dt: f32,
+	/// If the game was paused
+	pub paused: bool,
+	/// How much the mouse moved since the last frame, in screen pixels
+	pub mouse_moved: [f64; 2],
+	/// The state of the gamepads
+	pub gamepad: GamepadState,
+	/// The input events that occured during the frame, in the order they occured
+	pub events: Vec<RecordedEvent>,
+}
+
+/// A mouse button, as recorded.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RecordedMouseButton {
+	Left,
+	Right,
+	Middle,
+	Other(u8),
+}
+
+/// A scroll of the mouse wheel, as recorded.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RecordedScrollDelta {
+	/// Scrolled by lines, e.g. by a mouse wheel
+	Lines(f32, f32),
+	/// Scrolled by pixels, e.g. by a touchpad
+	Pixels(f64, f64),
+}
+
+/// An input event, as recorded.
+/// 
+/// Only the events that are passed on to the `GameState`, and focus changes, are recorded. Keys that can't be named in
+/// config files (see `settings::key_name`) are recorded with only their scancode.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RecordedEvent {
+	CursorMoved { x: f64, y: f64, modifiers: Modifiers },
+	MouseInput { pressed: bool, button: RecordedMouseButton, modifiers: Modifiers },
+	MouseWheel { delta: RecordedScrollDelta, modifiers: Modifiers },
+	KeyboardInput { scancode: u32, pressed: bool, key: Option<String>, modifiers: Modifiers },
+	ReceivedCharacter(char),
+	Focused(bool),
+}
+impl RecordedEvent {
+	/// Converts `event` into a recorded event, or returns `None` if it isn't recorded.
+	pub fn from_event(event: &Event) -> Option<RecordedEvent> {
+		fn pressed(state: ElementState) -> bool {
+			state == ElementState::Pressed
+		}
+		
+		let event = match event {
+			Event::WindowEvent{event, ..} => event,
+			_ => return None,
+		};
+		Some(match event {
+			&WindowEvent::CursorMoved{position: LogicalPosition{x, y}, modifiers, ..} => {
+				RecordedEvent::CursorMoved { x, y, modifiers: modifiers.into() }
+			},
+			&WindowEvent::MouseInput{state, button, modifiers, ..} => {
+				let button = match button {
+					MouseButton::Left => RecordedMouseButton::Left,
+					MouseButton::Right => RecordedMouseButton::Right,
+					MouseButton::Middle => RecordedMouseButton::Middle,
+					MouseButton::Other(b) => RecordedMouseButton::Other(b),
+				};
+				RecordedEvent::MouseInput { pressed: pressed(state), button, modifiers: modifiers.into() }
+			},
+			&WindowEvent::MouseWheel{delta, modifiers, ..} => {
+				let delta = match delta {
+					MouseScrollDelta::LineDelta(x, y) => RecordedScrollDelta::Lines(x, y),
+					MouseScrollDelta::PixelDelta(LogicalPosition{x, y}) => RecordedScrollDelta::Pixels(x, y),
+				};
+				RecordedEvent::MouseWheel { delta, modifiers: modifiers.into() }
+			},
+			&WindowEvent::KeyboardInput{input: KeyboardInput{scancode, state, virtual_keycode, modifiers}, ..} => {
+				RecordedEvent::KeyboardInput {
+					scancode,
+					pressed: pressed(state),
+					key: virtual_keycode.and_then(key_name).map(String::from),
+					modifiers: modifiers.into(),
+				}
+			},
+			&WindowEvent::ReceivedCharacter(c) => RecordedEvent::ReceivedCharacter(c),
+			&WindowEvent::Focused(b) => RecordedEvent::Focused(b),
+			_ => return None,
+		})
+	}
+	
+	/// Converts the recorded event back into an event, as if it occured in the window `window_id`.
+	/// 
+	/// The scroll phase of mouse wheel events isn't recorded, so they always have a phase of `TouchPhase::Moved`.
+	pub fn to_event(&self, window_id: WindowId) -> Event {
+		fn state(pressed: bool) -> ElementState {
+			if pressed { ElementState::Pressed } else { ElementState::Released }
+		}
+		
+		// The device that an event came from isn't recorded
+		let device_id = unsafe { DeviceId::dummy() };
+		let event = match self {
+			&RecordedEvent::CursorMoved { x, y, modifiers } => {
+				WindowEvent::CursorMoved { device_id, position: LogicalPosition::new(x, y), modifiers: modifiers.into() }
+			},
+			&RecordedEvent::MouseInput { pressed, button, modifiers } => {
+				let button = match button {
+					RecordedMouseButton::Left => MouseButton::Left,
+					RecordedMouseButton::Right => MouseButton::Right,
+					RecordedMouseButton::Middle => MouseButton::Middle,
+					RecordedMouseButton::Other(b) => MouseButton::Other(b),
+				};
+				WindowEvent::MouseInput { device_id, state: state(pressed), button, modifiers: modifiers.into() }
+			},
+			&RecordedEvent::MouseWheel { delta, modifiers } => {
+				let delta = match delta {
+					RecordedScrollDelta::Lines(x, y) => MouseScrollDelta::LineDelta(x, y),
+					RecordedScrollDelta::Pixels(x, y) => MouseScrollDelta::PixelDelta(LogicalPosition::new(x, y)),
+				};
+				WindowEvent::MouseWheel { device_id, delta, phase: TouchPhase::Moved, modifiers: modifiers.into() }
+			},
+			&RecordedEvent::KeyboardInput { scancode, pressed, ref key, modifiers } => {
+				WindowEvent::KeyboardInput { device_id, input: KeyboardInput {
+					scancode,
+					state: state(pressed),
+					virtual_keycode: key.as_ref().and_then(|k| key_from_name(k)),
+					modifiers: modifiers.into(),
+				}}
+			},
+			&RecordedEvent::ReceivedCharacter(c) => WindowEvent::ReceivedCharacter(c),
+			&RecordedEvent::Focused(b) => WindowEvent::Focused(b),
+		};
+		Event::WindowEvent { window_id, event }
+	}
+}
+
+/// Records the input of each frame to a file, as JSON with one line per frame.
+/// 
+/// The file starts with an `InputHeader`, followed by an `InputFrame` for each frame.
+pub struct InputRecorder {
+	path: PathBuf,
+	writer: BufWriter<File>,
+}
+impl InputRecorder {
+	/// Creates the recording file at `path`, and writes `header` to it.
+	pub fn create(path: &Path, header: &InputHeader) -> Result<InputRecorder, String> {
+		fn inner_try(path: &Path, header: &InputHeader) -> Result<InputRecorder, String> {
+			let file = File::create(path).map_err(|e| format!("unwritable file: {}", e))?;
+			let mut recorder = InputRecorder { path: path.to_path_buf(), writer: BufWriter::new(file) };
+			recorder.write_line(header)?;
+			Ok(recorder)
+		}
+		inner_try(path, header).map_err(|e| format!("could not record input to '{}': {}", path.display(), e))
+	}
+	
+	/// Writes `frame` to the end of the recording.
+	/// 
+	/// The file is flushed after each frame, so that the recording is complete even if the game crashes.
+	pub fn record(&mut self, frame: &InputFrame) -> Result<(), String> {
+		self.write_line(frame).map_err(|e| format!("could not record input to '{}': {}", self.path.display(), e))
+	}
+	
+	fn write_line<T: Serialize>(&mut self, value: &T) -> Result<(), String> {
+		serde_json::to_writer(&mut self.writer, value).map_err(|e| format!("could not serialize input: {}", e))?;
+		self.writer.write_all(b"\n")
+			.and_then(|_| self.writer.flush())
+			.map_err(|e| format!("write failed: {}", e))
+	}
+}
+
+/// Reads the input of each frame from a file written by `InputRecorder`.
+pub struct InputReplayer {
+	path: PathBuf,
+	lines: Lines<BufReader<File>>,
+	header: InputHeader,
+	/// The number of frames read so far
+	frames: u64,
+}
+impl InputReplayer {
+	/// Opens the recording at `path`, and reads its header.
+	pub fn open(path: &Path) -> Result<InputReplayer, String> {
+		fn inner_try(path: &Path) -> Result<InputReplayer, String> {
+			let file = File::open(path).map_err(|e| format!("unreadable file: {}", e))?;
+			let mut lines = BufReader::new(file).lines();
+			let header = match lines.next() {
+				Some(line) => {
+					let line = line.map_err(|e| format!("read failed: {}", e))?;
+					serde_json::from_str(&line).map_err(|e| format!("invalid header: {}", e))?
+				},
+				None => return Err("missing header".into()),
+			};
+			Ok(InputReplayer { path: path.to_path_buf(), lines, header, frames: 0 })
+		}
+		inner_try(path).map_err(|e| format!("could not replay input from '{}': {}", path.display(), e))
+	}
+	
+	/// Gets the header of the recording.
+	pub fn header(&self) -> &InputHeader {
+		&self.header
+	}
+	
+	/// Reads the next frame of the recording, or returns `None` if there are no frames left.
+	pub fn next_frame(&mut self) -> Result<Option<InputFrame>, String> {
+		let line = match self.lines.next() {
+			Some(Ok(line)) => line,
+			Some(Err(e)) => return Err(format!("could not replay input from '{}': read failed: {}", self.path.display(), e)),
+			None => return Ok(None),
+		};
+		self.frames += 1;
+		serde_json::from_str(&line)
+			.map(Some)
+			.map_err(|e| format!("could not replay input from '{}': invalid frame {}: {}", self.path.display(), self.frames, e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use prelude::*;
+	use std::env;
+	use std::rc::Rc;
+	use std::cell::RefCell;
+	use glutin::{ModifiersState, VirtualKeyCode};
+	use nc::shape::{Ball, Cuboid};
+	use game::{Component, EntityBuilder, EntityId, GameState, GamepadAxis, Gravity};
+	use render::{Camera, EmptyMesh};
+	use settings::Settings;
+	
+	/// Builds a headless scene with a ball above a floor. Holding D pushes the ball along, and typing J makes it jump.
+	fn build_scene() -> GameState {
+		let mut state = GameState::new(Camera::new(Vector3::zero()), Gravity::Constant(Vector3::new(0.0, -9.81, 0.0)));
+		EntityBuilder::new_static(0.3, 0.7)
+			.component(Component::new(Cuboid::new(Vector3::new(20.0, 0.5, 20.0)), Rc::new(EmptyMesh::new())))
+			.pos(Vector3::new(0.0, -0.5, 0.0))
+			.build(&mut state);
+		let ball = EntityBuilder::new(1.0, 0.3, 0.5)
+			.component(Component::new(Ball::new(0.5), Rc::new(EmptyMesh::new())))
+			.pos(Vector3::new(0.0, 2.0, 0.0))
+			.build(&mut state);
+		
+		state.set_tick_callback(Some(Rc::new(RefCell::new(move |state: &mut GameState, _dt: f32, _settings: &Settings, events: &[Event], _mouse_moved: Vector2<f64>| {
+			if state.keyboard_state().is_pressed(&VirtualKeyCode::D) {
+				state.apply_force(ball, Vector3::new(5.0, 0.0, 0.0));
+			}
+			for event in events.iter() {
+				if let &Event::WindowEvent{event: WindowEvent::ReceivedCharacter('j'), ..} = event {
+					state.apply_impulse(ball, Vector3::new(0.0, 4.0, 0.0));
+				}
+			}
+		}))));
+		state
+	}
+	
+	/// Gets the position and velocity of each entity in the scene.
+	fn snapshot(state: &GameState) -> Vec<(EntityId, Vector3<f32>, Vector3<f32>)> {
+		state.entity_ids().into_iter().map(|id| {
+			let body = state.get_entity_rigid_body(id).unwrap();
+			(id, body.position().translation.vector, body.velocity().linear)
+		}).collect()
+	}
+	
+	#[test]
+	pub fn test_event_round_trip() {
+		let window_id = unsafe { WindowId::dummy() };
+		let modifiers = ModifiersState { shift: true, ctrl: false, alt: false, logo: false };
+		let event = RecordedEvent::from_event(&Event::WindowEvent { window_id, event: WindowEvent::KeyboardInput {
+			device_id: unsafe { DeviceId::dummy() },
+			input: KeyboardInput { scancode: 17, state: ElementState::Pressed, virtual_keycode: Some(VirtualKeyCode::W), modifiers },
+		}}).unwrap();
+		match event.to_event(window_id) {
+			Event::WindowEvent { event: WindowEvent::KeyboardInput { input, .. }, .. } => {
+				assert_eq!(17, input.scancode);
+				assert_eq!(ElementState::Pressed, input.state);
+				assert_eq!(Some(VirtualKeyCode::W), input.virtual_keycode);
+				assert!(input.modifiers.shift);
+			},
+			e => panic!("wrong event: {:?}", e),
+		}
+		assert_eq!(None, RecordedEvent::from_event(&Event::WindowEvent { window_id, event: WindowEvent::Refresh }));
+	}
+	
+	#[test]
+	pub fn test_record_then_replay() {
+		let path = env::temp_dir().join("neat_test_input.json");
+		let mut gamepad = GamepadState::new();
+		gamepad.process_axis(GamepadAxis::LeftStickY, 0.5);
+		let frames = vec![
+			InputFrame { ticks: 2, dt: 1.0 / 60.0, paused: false, mouse_moved: [0.1, -3.0], gamepad, events: vec![
+				RecordedEvent::CursorMoved { x: 400.1, y: 300.0, modifiers: Modifiers::default() },
+				RecordedEvent::ReceivedCharacter('w'),
+			]},
+			InputFrame { ticks: 0, dt: 0.0, paused: true, ..InputFrame::default() },
+		];
+		{
+			let mut recorder = InputRecorder::create(&path, &InputHeader { seed: u64::max_value() }).unwrap();
+			for frame in frames.iter() {
+				recorder.record(frame).unwrap();
+			}
+		}
+		let mut replayer = InputReplayer::open(&path).unwrap();
+		assert_eq!(u64::max_value(), replayer.header().seed);
+		for frame in frames.iter() {
+			assert_eq!(Some(frame), replayer.next_frame().unwrap().as_ref());
+		}
+		assert_eq!(None, replayer.next_frame().unwrap());
+	}
+	
+	#[test]
+	pub fn test_replay_reaches_same_state() {
+		let path = env::temp_dir().join("neat_test_replay.json");
+		let window_id = unsafe { WindowId::dummy() };
+		let key = |pressed: bool| Event::WindowEvent { window_id, event: WindowEvent::KeyboardInput {
+			device_id: unsafe { DeviceId::dummy() },
+			input: KeyboardInput {
+				scancode: 32,
+				state: if pressed { ElementState::Pressed } else { ElementState::Released },
+				virtual_keycode: Some(VirtualKeyCode::D),
+				modifiers: ModifiersState { shift: false, ctrl: false, alt: false, logo: false },
+			},
+		}};
+		let jump = || Event::WindowEvent { window_id, event: WindowEvent::ReceivedCharacter('j') };
+		let dt = 1.0 / 120.0;
+		let mut settings = Settings::default();
+		
+		// Play the scene, recording the input of each frame like `Game` does
+		let mut state = build_scene();
+		let mut events = Vec::new();
+		{
+			let mut recorder = InputRecorder::create(&path, &InputHeader { seed: 0 }).unwrap();
+			for i in 0..60 {
+				let frame_events = match i {
+					5 => vec![key(true)],
+					20 => vec![jump(), key(false)],
+					30 => vec![key(true), jump()],
+					45 => vec![key(false)],
+					_ => Vec::new(),
+				};
+				// Some frames take longer than others, and some are too short to simulate any ticks
+				let ticks = i % 3;
+				recorder.record(&InputFrame {
+					ticks,
+					dt: ticks as f32 * dt,
+					paused: false,
+					mouse_moved: [0.0, 0.0],
+					gamepad: GamepadState::new(),
+					events: frame_events.iter().filter_map(RecordedEvent::from_event).collect(),
+				}).unwrap();
+				events.extend(frame_events);
+				state.tick_frame(dt, ticks, &settings, &mut events, Vector2::zero());
+			}
+		}
+		let recorded = snapshot(&state);
+		
+		// Replay the recording headlessly into a fresh scene
+		let mut state = build_scene();
+		let mut events = Vec::new();
+		let mut replayer = InputReplayer::open(&path).unwrap();
+		while let Some(frame) = replayer.next_frame().unwrap() {
+			settings.paused = frame.paused;
+			events.extend(frame.events.iter().map(|e| e.to_event(window_id)));
+			let mouse_moved = Vector2::new(frame.mouse_moved[0], frame.mouse_moved[1]);
+			state.tick_frame(dt, frame.ticks, &settings, &mut events, mouse_moved);
+		}
+		let replayed = snapshot(&state);
+		
+		assert_eq!(recorded, replayed);
+		// Make sure that the input actually did something
+		let ball = recorded[1];
+		assert!(ball.1.x > 0.1, "{:?}", ball);
+	}
+}
@@ -0,0 +1,442 @@
+//! Saving and restoring simulations.
+//!
+//! See `GameState::save` and `GameState::restore`.
+use prelude::*;
+use std::fs::File;
+use std::io::{Read, Write, BufWriter};
+use std::path::Path;
+use std::rc::Rc;
+
+use na::{Quaternion, Unit};
+use nc::shape::{Ball, Cuboid, ShapeHandle};
+use np::object::BodyStatus;
+use serde_json;
+
+use game::{Entity, EntityBuilder, EntityId, Component, Collision, Gravity};
+use game::gravity;
+use render::{Camera, Light, RenderableMesh};
+
+/// A snapshot of a `GameState`, that can be serialized to JSON.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveData {
+	pub entities: Vec<SavedEntity>,
+	pub next_free_id: EntityId,
+	pub camera: SavedCamera,
+	pub gravity: SavedGravity,
+	pub gravity_enabled: bool,
+	#[serde(default = "default_gravity_softening")]
+	pub gravity_softening: f32,
+	pub unit_scale: f32,
+	pub lights: Vec<SavedLight>,
+	pub ambient_light: [f32; 4],
+	#[serde(default = "default_background_color")]
+	pub background_color: [f32; 4],
+}
+impl SaveData {
+	/// Serializes the save data to JSON.
+	pub fn to_json(&self) -> Result<String, String> {
+		serde_json::to_string_pretty(self).map_err(|e| format!("could not serialize save data: {}", e))
+	}
+	
+	/// Deserializes save data from JSON.
+	pub fn from_json(src: &str) -> Result<SaveData, String> {
+		serde_json::from_str(src).map_err(|e| format!("invalid save data: {}", e))
+	}
+	
+	/// Loads save data from a JSON file.
+	pub fn from_file(path: &Path) -> Result<SaveData, String> {
+		fn inner_try(path: &Path) -> Result<SaveData, String> {
+			let mut src = String::new();
+			File::open(path)
+				.and_then(|mut f| f.read_to_string(&mut src))
+				.map_err(|e| format!("unreadable file '{}': {}", path.display(), e))?;
+			SaveData::from_json(&src)
+		}
+		inner_try(path).map_err(|e| format!("could not load save data from '{}': {}", path.display(), e))
+	}
+	
+	/// Saves the save data to a JSON file.
+	pub fn save(&self, path: &Path) -> Result<(), String> {
+		fn inner_try(data: &SaveData, path: &Path) -> Result<(), String> {
+			let src = data.to_json()?;
+			File::create(path)
+				.and_then(|f| BufWriter::new(f).write_all(src.as_bytes()))
+				.map_err(|e| format!("unwritable file '{}': {}", path.display(), e))
+		}
+		inner_try(self, path).map_err(|e| format!("could not save save data to '{}': {}", path.display(), e))
+	}
+}
+
+/// The gravity softening of saves from before the gravity softening was saved.
+fn default_gravity_softening() -> f32 {
+	gravity::DEFAULT_SOFTENING
+}
+
+/// The background color of saves from before the background color was saved.
+fn default_background_color() -> [f32; 4] {
+	[0.0, 0.0, 0.0, 0.0]
+}
+
+/// The saved state of an entity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedEntity {
+	pub id: EntityId,
+	pub components: Vec<SavedComponent>,
+	pub box_collision: bool,
+	pub sensor: bool,
+	pub status: SavedBodyStatus,
+	/// If `None`, the entity has infinite mass.
+	pub density: Option<f32>,
+	pub restitution: f32,
+	pub friction: f32,
+	#[serde(default)]
+	pub linear_damping: f32,
+	#[serde(default)]
+	pub angular_damping: f32,
+	pub pos: [f32; 3],
+	/// The rotation of the entity, as a unit quaternion `[i, j, k, w]`.
+	pub rot: [f32; 4],
+	pub vel: [f32; 3],
+	pub ang_vel: [f32; 3],
+	pub frozen: bool,
+	pub wireframe: bool,
+	pub tag: Option<String>,
+	pub depth_bias: Option<(f32, f32)>,
+}
+impl SavedEntity {
+	/// Saves the entity with the specified id, given its body's position and velocity.
+	pub fn new(id: EntityId, e: &Entity, iso: &Isometry3<f32>, vel: &Velocity3<f32>, status: BodyStatus) -> Result<SavedEntity, String> {
+		let components = e.components().iter()
+			.enumerate()
+			.map(|(i, c)| SavedComponent::new(c).map_err(|e| format!("component {} of entity {}: {}", i, id, e)))
+			.collect::<Result<Vec<_>, String>>()?;
+		
+		// If the entity is frozen, save the state it will be restored to when it is unfrozen
+		let (status, vel) = e.frozen_state().unwrap_or((status, *vel));
+		
+		Ok(SavedEntity {
+			id,
+			components,
+			box_collision: e.collision() == Collision::Box,
+			sensor: e.is_sensor(),
+			status: SavedBodyStatus::new(status),
+			density: e.density(),
+			restitution: e.restitution(),
+			friction: e.friction(),
+			linear_damping: e.linear_damping(),
+			angular_damping: e.angular_damping(),
+			pos: vec3_to_array(iso.translation.vector),
+			rot: quat_to_array(&iso.rotation),
+			vel: vec3_to_array(vel.linear),
+			ang_vel: vec3_to_array(vel.angular),
+			frozen: e.is_frozen(),
+			wireframe: e.wireframe(),
+			tag: e.tag().map(|s| s.to_string()),
+			depth_bias: e.depth_bias(),
+		})
+	}
+	
+	/// Constructs a builder that will build the saved entity.
+	/// 
+	/// `mesh_resolver` is called with the mesh name of each component to get its mesh.
+	pub fn builder<F>(&self, mesh_resolver: &mut F) -> Result<EntityBuilder, String>
+			where F: FnMut(&str) -> Option<Rc<RenderableMesh>> {
+		let mut builder = match self.status {
+			SavedBodyStatus::Dynamic => {
+				let density = self.density.ok_or_else(|| format!("entity {} is dynamic, but has no density", self.id))?;
+				EntityBuilder::new(density, self.restitution, self.friction)
+			},
+			SavedBodyStatus::Static    => EntityBuilder::new_static(self.restitution, self.friction),
+			SavedBodyStatus::Kinematic => EntityBuilder::new_kinematic(self.restitution, self.friction),
+		};
+		builder = builder
+			.pos(array_to_vec3(self.pos))
+			.rot(array_to_quat(self.rot).to_rotation_matrix())
+			.vel(array_to_vec3(self.vel))
+			.ang_vel(array_to_vec3(self.ang_vel))
+			.linear_damping(self.linear_damping)
+			.angular_damping(self.angular_damping)
+			.collision(if self.box_collision { Collision::Box } else { Collision::Compound });
+		if self.sensor {
+			builder = builder.sensor();
+		}
+		if let Some(ref tag) = self.tag {
+			builder = builder.tag(tag.clone());
+		}
+		if let Some((factor, units)) = self.depth_bias {
+			builder = builder.depth_bias(factor, units);
+		}
+		for (i, c) in self.components.iter().enumerate() {
+			let mesh = mesh_resolver(&c.mesh_name)
+				.ok_or_else(|| format!("component {} of entity {}: unknown mesh '{}'", i, self.id, c.mesh_name))?;
+			builder = builder.component(c.component(mesh));
+		}
+		Ok(builder)
+	}
+}
+
+/// The saved state of a component of an entity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedComponent {
+	pub pos: [f32; 3],
+	/// The rotation of the component, as a unit quaternion `[i, j, k, w]`.
+	pub rot: [f32; 4],
+	pub shape: SavedShape,
+	pub mesh_name: String,
+	#[serde(default)]
+	pub wireframe: bool,
+}
+impl SavedComponent {
+	/// Saves a component. The component must have a mesh name.
+	pub fn new(c: &Component) -> Result<SavedComponent, String> {
+		let iso = c.iso();
+		Ok(SavedComponent {
+			pos: vec3_to_array(iso.translation.vector),
+			rot: quat_to_array(&iso.rotation),
+			shape: SavedShape::new(c.shape())?,
+			mesh_name: c.get_mesh_name()
+				.ok_or_else(|| "no mesh name".to_string())?
+				.to_string(),
+			wireframe: c.is_wireframe(),
+		})
+	}
+	
+	/// Constructs the saved component with the specified mesh.
+	pub fn component(&self, mesh: Rc<RenderableMesh>) -> Component {
+		let iso = Isometry3::from_parts(Translation::from_vector(array_to_vec3(self.pos)), array_to_quat(self.rot));
+		Component::with_iso_handle(iso, self.shape.handle(), mesh)
+			.mesh_name(self.mesh_name.clone())
+			.wireframe(self.wireframe)
+	}
+}
+
+/// A saved collision shape.
+/// 
+/// Only balls and cuboids are supported.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SavedShape {
+	Ball { radius: f32 },
+	Cuboid { half_extents: [f32; 3] },
+}
+impl SavedShape {
+	/// Saves a shape. Returns an error if the type of shape is not supported.
+	pub fn new(shape: &ShapeHandle<f32>) -> Result<SavedShape, String> {
+		if let Some(ball) = shape.as_shape::<Ball<f32>>() {
+			Ok(SavedShape::Ball { radius: ball.radius() })
+		} else if let Some(cuboid) = shape.as_shape::<Cuboid<f32>>() {
+			Ok(SavedShape::Cuboid { half_extents: vec3_to_array(*cuboid.half_extents()) })
+		} else {
+			Err("unsupported collision shape".to_string())
+		}
+	}
+	
+	/// Constructs the saved shape.
+	pub fn handle(&self) -> ShapeHandle<f32> {
+		match *self {
+			SavedShape::Ball { radius } => ShapeHandle::new(Ball::new(radius)),
+			SavedShape::Cuboid { half_extents } => ShapeHandle::new(Cuboid::new(array_to_vec3(half_extents))),
+		}
+	}
+}
+
+/// A saved `BodyStatus`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SavedBodyStatus {
+	Dynamic,
+	Static,
+	Kinematic,
+}
+impl SavedBodyStatus {
+	/// Saves a body status. Disabled bodies are saved as static.
+	pub fn new(status: BodyStatus) -> SavedBodyStatus {
+		match status {
+			BodyStatus::Dynamic   => SavedBodyStatus::Dynamic,
+			BodyStatus::Kinematic => SavedBodyStatus::Kinematic,
+			_ => SavedBodyStatus::Static,
+		}
+	}
+}
+
+/// The saved state of the camera.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedCamera {
+	pub pos: [f32; 3],
+	pub forward: [f32; 3],
+	pub orbit_target: Option<[f32; 3]>,
+}
+impl SavedCamera {
+	/// Saves a camera.
+	pub fn new(cam: &Camera) -> SavedCamera {
+		SavedCamera {
+			pos: vec3_to_array(cam.pos()),
+			forward: vec3_to_array(cam.forward()),
+			orbit_target: cam.orbit_target().map(vec3_to_array),
+		}
+	}
+	
+	/// Constructs the saved camera.
+	pub fn camera(&self) -> Camera {
+		let pos = array_to_vec3(self.pos);
+		let mut cam = Camera::new(pos);
+		cam.look_at(pos + array_to_vec3(self.forward));
+		if let Some(target) = self.orbit_target {
+			cam.set_orbit(array_to_vec3(target));
+		}
+		cam
+	}
+}
+
+/// A saved `Gravity`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SavedGravity {
+	Relative(f32),
+	RelativeBarnesHut { g: f32, theta: f32, softening: f32 },
+	Constant([f32; 3]),
+	None,
+}
+impl SavedGravity {
+	/// Saves a gravity type.
+	pub fn new(g: Gravity) -> SavedGravity {
+		match g {
+			Gravity::Relative(g) => SavedGravity::Relative(g),
+			Gravity::RelativeBarnesHut { g, theta, softening } => SavedGravity::RelativeBarnesHut { g, theta, softening },
+			Gravity::Constant(v) => SavedGravity::Constant(vec3_to_array(v)),
+			Gravity::None => SavedGravity::None,
+		}
+	}
+	
+	/// Constructs the saved gravity type.
+	pub fn gravity(&self) -> Gravity {
+		match *self {
+			SavedGravity::Relative(g) => Gravity::Relative(g),
+			SavedGravity::RelativeBarnesHut { g, theta, softening } => Gravity::RelativeBarnesHut { g, theta, softening },
+			SavedGravity::Constant(v) => Gravity::Constant(array_to_vec3(v)),
+			SavedGravity::None => Gravity::None,
+		}
+	}
+}
+
+/// A saved `Light`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedLight {
+	pub pos: [f32; 4],
+	pub diffuse: [f32; 4],
+	pub specular: [f32; 4],
+	pub constant_attenuation: f32,
+	pub linear_attenuation: f32,
+	pub quadratic_attenuation: f32,
+	pub spot_cutoff: f32,
+	pub spot_exponent: f32,
+	pub spot_direction: [f32; 3],
+}
+impl SavedLight {
+	/// Saves a light.
+	pub fn new(l: &Light) -> SavedLight {
+		SavedLight {
+			pos: vec4_to_array(l.pos),
+			diffuse: vec4_to_array(l.diffuse),
+			specular: vec4_to_array(l.specular),
+			constant_attenuation: l.constant_attenuation,
+			linear_attenuation: l.linear_attenuation,
+			quadratic_attenuation: l.quadratic_attenuation,
+			spot_cutoff: l.spot_cutoff,
+			spot_exponent: l.spot_exponent,
+			spot_direction: vec3_to_array(l.spot_direction),
+		}
+	}
+	
+	/// Constructs the saved light.
+	pub fn light(&self) -> Light {
+		Light {
+			pos: array_to_vec4(self.pos),
+			diffuse: array_to_vec4(self.diffuse),
+			specular: array_to_vec4(self.specular),
+			constant_attenuation: self.constant_attenuation,
+			linear_attenuation: self.linear_attenuation,
+			quadratic_attenuation: self.quadratic_attenuation,
+			spot_cutoff: self.spot_cutoff,
+			spot_exponent: self.spot_exponent,
+			spot_direction: array_to_vec3(self.spot_direction),
+		}
+	}
+}
+
+fn vec3_to_array(v: Vector3<f32>) -> [f32; 3] {
+	[v.x, v.y, v.z]
+}
+
+fn array_to_vec3(a: [f32; 3]) -> Vector3<f32> {
+	Vector3::new(a[0], a[1], a[2])
+}
+
+fn vec4_to_array(v: Vector4<f32>) -> [f32; 4] {
+	[v.x, v.y, v.z, v.w]
+}
+
+fn array_to_vec4(a: [f32; 4]) -> Vector4<f32> {
+	Vector4::new(a[0], a[1], a[2], a[3])
+}
+
+fn quat_to_array(q: &UnitQuaternion<f32>) -> [f32; 4] {
+	vec4_to_array(q.quaternion().coords)
+}
+
+fn array_to_quat(a: [f32; 4]) -> UnitQuaternion<f32> {
+	Unit::new_normalize(Quaternion::from_vector(array_to_vec4(a)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	pub fn test_json_round_trip() {
+		let data = SaveData {
+			entities: vec![SavedEntity {
+				id: 3,
+				components: vec![SavedComponent {
+					pos: [0.0, 1.0, 0.0],
+					rot: [0.0, 0.0, 0.0, 1.0],
+					shape: SavedShape::Cuboid { half_extents: [1.0, 2.0, 3.0] },
+					mesh_name: "crate".to_string(),
+					wireframe: true,
+				}],
+				box_collision: false,
+				sensor: false,
+				status: SavedBodyStatus::Dynamic,
+				density: Some(1.5),
+				restitution: 0.5,
+				friction: 0.25,
+				linear_damping: 0.1,
+				angular_damping: 0.0,
+				pos: [1.0, 2.0, 3.0],
+				rot: [0.0, 0.6, 0.0, 0.8],
+				vel: [-1.0, 0.0, 0.5],
+				ang_vel: [0.0, 0.1, 0.0],
+				frozen: true,
+				wireframe: false,
+				tag: Some("box".to_string()),
+				depth_bias: None,
+			}],
+			next_free_id: 4,
+			camera: SavedCamera { pos: [0.0, 5.0, 10.0], forward: [0.0, 0.0, -1.0], orbit_target: None },
+			gravity: SavedGravity::RelativeBarnesHut { g: 1.0, theta: 0.5, softening: 0.1 },
+			gravity_enabled: true,
+			gravity_softening: 0.05,
+			unit_scale: 1.0,
+			lights: vec![SavedLight::new(&Light::off())],
+			ambient_light: [0.05, 0.05, 0.05, 1.0],
+			background_color: [0.0, 0.0, 0.1, 1.0],
+		};
+		
+		let json = data.to_json().unwrap();
+		assert_eq!(SaveData::from_json(&json).unwrap(), data);
+	}
+	
+	#[test]
+	pub fn test_quaternion_round_trip() {
+		let q = UnitQuaternion::from_euler_angles(0.3, -1.2, 2.0);
+		let q2 = array_to_quat(quat_to_array(&q));
+		assert!(q.angle_to(&q2) < 1e-5);
+	}
+}
@@ -1,31 +1,71 @@
 use prelude::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 use std::cell::RefCell;
 
 use glutin::{KeyboardInput, ElementState, Event, WindowEvent};
+use nc::events::ContactEvent;
+use nc::query::{Proximity, Ray};
 use np::world::World;
-use np::object::RigidBody;
+use np::object::{ColliderHandle, RigidBody};
 
-use game::{KeyboardState, Entity, EntityBuilder};
-use render::{Camera, Render, Light};
-use settings::Settings;
+#[cfg(feature = "audio")]
+use audio::MusicPlayer;
+use game::{FrameStats, KeyboardState, MouseState, GamepadState, Entity, EntityBuilder, DEFAULT_COLLIDER_MARGIN};
+use game::gravity::{self, GravityBody};
+use game::save::{SaveData, SavedEntity, SavedCamera, SavedGravity, SavedLight};
+use render::{Camera, Color, Render, RenderableMesh, InstanceBatch, Light, MAX_LIGHTS};
+use collision::Aabb;
+use settings::{Action, Settings};
 
 pub const FONT_SIZE: f32 = 20.0;
+/// The pt size of entity labels.
+const LABEL_FONT_SIZE: f32 = 14.0;
 
 pub type EntityId = u32;
+pub type SpringId = u32;
 
 /// Gravity type of the simulation
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Gravity {
 	/// Each object attracts each other object, scaled by a specified amount.
 	Relative(f32),
+	/// Each object attracts each other object, approximated with the Barnes-Hut algorithm.
+	/// 
+	/// This is O(n log n) in the number of entities rather than O(n²), so it scales to much larger scenes.
+	/// See `gravity::net_forces_barnes_hut` for the meanings of `theta` and `softening`.
+	RelativeBarnesHut { g: f32, theta: f32, softening: f32 },
 	/// Each object is attracted in a constant direction
 	Constant(Vector3<f32>),
 	/// No gravity is applied
 	None,
 }
 
+/// A spring that attaches an entity to a fixed point in the world.
+#[derive(Copy, Clone, Debug)]
+struct WorldSpring {
+	anchor: Vector3<f32>,
+	stiffness: f32,
+	damping: f32,
+}
+
+/// A spring that connects two entities.
+#[derive(Copy, Clone, Debug)]
+struct Spring {
+	a: EntityId,
+	b: EntityId,
+	rest_length: f32,
+	stiffness: f32,
+	damping: f32,
+}
+
+/// A constraint that holds an entity at a fixed height above the surface below it.
+#[derive(Copy, Clone, Debug)]
+struct Hover {
+	height: f32,
+	stiffness: f32,
+}
+
 pub trait TickCallback {
 	fn tick(&mut self, state: &mut GameState, dt: f32, settings: &Settings, events: &[Event], mouse_moved: Vector2<f64>);
 }
@@ -34,6 +74,15 @@ impl<F> TickCallback for F where F: FnMut(&mut GameState, f32, &Settings, &[Even
 		self(state, dt, settings, events, mouse_moved)
 	}
 }
+/// Called when two entities start touching, or when an entity starts overlapping a sensor.
+pub trait CollisionCallback {
+	fn on_collision(&mut self, state: &mut GameState, a: EntityId, b: EntityId);
+}
+impl<F> CollisionCallback for F where F: FnMut(&mut GameState, EntityId, EntityId) {
+	fn on_collision(&mut self, state: &mut GameState, a: EntityId, b: EntityId) {
+		self(state, a, b)
+	}
+}
 pub trait RenderCallback {
 	fn render(&mut self, state: &mut GameState, r: &mut Render, fps: u32);
 }
@@ -47,33 +96,70 @@ impl<F> RenderCallback for F where F: FnMut(&mut GameState, &mut Render, u32) {
 pub struct GameState {
 	pub world: World<f32>,
 	gravity: Gravity,
+	gravity_enabled: bool,
+	gravity_softening: f32,
+	unit_scale: f32,
 	next_free_id: EntityId,
-	pub entities: HashMap<EntityId, Entity>,
+	/// The entities in the world, ordered by ID.
+	pub entities: BTreeMap<EntityId, Entity>,
+	collider_entities: HashMap<ColliderHandle, EntityId>,
+	world_springs: HashMap<EntityId, WorldSpring>,
+	next_free_spring_id: SpringId,
+	springs: BTreeMap<SpringId, Spring>,
+	hovers: HashMap<EntityId, Hover>,
 	keyboard_state: KeyboardState,
+	mouse_state: MouseState,
+	gamepad_state: GamepadState,
+	capture_key: Option<Action>,
 	camera: Camera,
-	light: Light,
+	lights: Vec<Light>,
 	ambient_light: Vector4<f32>,
+	background_color: Color,
 	wireframe_mode: bool,
+	colorblind_mode: bool,
+	show_labels: bool,
+	label_distance: f32,
 	tick_callback: Option<Rc<RefCell<TickCallback>>>,
+	collision_callback: Option<Rc<RefCell<CollisionCallback>>>,
 	render_callback: Option<Rc<RefCell<RenderCallback>>>,
+	#[cfg(feature = "audio")]
+	music: Option<MusicPlayer>,
 }
 impl GameState {
 	/// Constructs a new GameState with the specified initial camera position, and gravity state.
 	/// 
-	/// The main light in the scene is initialized to off. Use `set_light` to specify the light.
+	/// The main light in the scene is initialized to off. Use `set_light` or `set_lights` to specify the lights.
 	pub fn new(cam: Camera, g: Gravity) -> GameState {
 		GameState {
 			world: World::new(),
 			gravity: g,
+			gravity_enabled: true,
+			gravity_softening: gravity::DEFAULT_SOFTENING,
+			unit_scale: 1.0,
 			next_free_id: 0,
-			entities: HashMap::new(),
+			entities: BTreeMap::new(),
+			collider_entities: HashMap::new(),
+			world_springs: HashMap::new(),
+			next_free_spring_id: 0,
+			springs: BTreeMap::new(),
+			hovers: HashMap::new(),
 			keyboard_state: KeyboardState::new(),
+			mouse_state: MouseState::new(),
+			gamepad_state: GamepadState::new(),
+			capture_key: None,
 			camera: cam,
-			light: Light::off(),
+			lights: vec![Light::off()],
 			ambient_light: Vector4::new(0.05, 0.05, 0.05, 1.0),
+			background_color: Color::BLACK.with_alpha(0.0),
 			wireframe_mode: false,
-			tick_callback  : None,
-			render_callback: None,
+			colorblind_mode: false,
+			show_labels: false,
+			label_distance: 50.0,
+			tick_callback     : None,
+			collision_callback: None,
+			render_callback   : None,
+			#[cfg(feature = "audio")]
+			music: None,
 		}
 	}
 	
@@ -81,38 +167,333 @@ impl GameState {
 		self.ambient_light = ambient_light;
 	}
 	
+	/// Gets the color of the background behind the scene.
+	pub fn background_color(&self) -> Color {
+		self.background_color
+	}
+	
+	/// Sets the color of the background behind the scene. (Default = transparent black, the same as
+	/// `Render::set_clear_color`)
+	pub fn set_background_color(&mut self, color: Color) {
+		self.background_color = color;
+	}
+	
+	/// Returns true if the debug colors are remapped to a color-blind-safe palette. (Default = false)
+	pub fn colorblind_mode(&self) -> bool {
+		self.colorblind_mode
+	}
+	
+	/// Sets if the standard `Color::RED`, `Color::GREEN` and `Color::BLUE` colors used for debug visualization
+	/// should be remapped to a color-blind-safe palette when rendering.
+	/// 
+	/// See `Color::colorblind_safe_palette`.
+	pub fn set_colorblind_mode(&mut self, mode: bool) {
+		self.colorblind_mode = mode;
+	}
+	
+	/// Returns true if entities are labelled with their IDs and tags. (Default = false)
+	pub fn show_labels(&self) -> bool {
+		self.show_labels
+	}
+	
+	/// Sets if each entity should be labelled with its ID, and its tag if it has one.
+	/// 
+	/// The labels are drawn above the entities, and fade out as they get further away from the camera.
+	/// Entities further than the label distance are not labelled.
+	pub fn set_show_labels(&mut self, show: bool) {
+		self.show_labels = show;
+	}
+	
+	/// Gets the maximum distance from the camera that entities are labelled at, in world units. (Default = 50.0)
+	pub fn label_distance(&self) -> f32 {
+		self.label_distance
+	}
+	
+	/// Sets the maximum distance from the camera that entities are labelled at, in world units.
+	pub fn set_label_distance(&mut self, distance: f32) {
+		self.label_distance = distance;
+	}
+	
+	/// Gets the gravity mode of the simulation.
+	pub fn gravity(&self) -> Gravity {
+		self.gravity
+	}
+	
+	/// Sets the gravity mode of the simulation. This takes effect from the next tick.
+	pub fn set_gravity(&mut self, g: Gravity) {
+		self.gravity = g;
+	}
+	
+	/// Returns true if gravity is applied to the entities. (Default = true)
+	pub fn gravity_enabled(&self) -> bool {
+		self.gravity_enabled
+	}
+	
+	/// Sets if gravity is applied to the entities, without changing the gravity mode.
+	/// 
+	/// This is useful for measuring the cost of `Gravity::Relative`, which is O(n²) in the number of entities, against
+	/// `Gravity::RelativeBarnesHut`.
+	/// The time taken to apply gravity each tick is logged at the trace level.
+	pub fn set_gravity_enabled(&mut self, enabled: bool) {
+		self.gravity_enabled = enabled;
+	}
+	
+	/// Gets the softening distance of `Gravity::Relative`, in metres. (Default = `gravity::DEFAULT_SOFTENING`)
+	pub fn gravity_softening(&self) -> f32 {
+		self.gravity_softening
+	}
+	
+	/// Sets the softening distance of `Gravity::Relative`, in metres.
+	/// 
+	/// This is added in quadrature to the distance between each pair of entities, which stops the force between them
+	/// blowing up as they get close, and stops entities in the same position producing NaNs.
+	pub fn set_gravity_softening(&mut self, softening: f32) {
+		if softening < 0.0 {
+			warn!("Ignoring invalid gravity softening: {}", softening);
+			return;
+		}
+		self.gravity_softening = softening;
+	}
+	
+	/// Gets the length of one world unit, in metres. (Default = 1.0)
+	pub fn unit_scale(&self) -> f32 {
+		self.unit_scale
+	}
+	
+	/// Sets the length of one world unit, in metres. For example, a scene authored in kilometres would use `1000.0`.
+	/// 
+	/// The gravity constants, collider margins and the camera's speed are all given in metres, and are
+	/// scaled by this so that the scene behaves sensibly at any scale.
+	/// - `Gravity::Constant` accelerations are divided by the scale.
+	/// - `Gravity::Relative` constants are divided by the scale cubed. The softening distances are divided by the scale.
+	/// - Collider margins are divided by the scale. This only affects entities added after the scale is set,
+	///   so it should be set before the scene is built.
+	/// - The camera's speed is divided by the scale.
+	/// 
+	/// The near and far clip planes of the renderer are *not* scaled, as they are given in world units.
+	/// Scenes at extreme scales should set them with `Render::set_clip_planes`, or the `near_plane` and `far_plane`
+	/// settings.
+	pub fn set_unit_scale(&mut self, scale: f32) {
+		if scale <= 0.0 {
+			warn!("Ignoring invalid unit scale: {}", scale);
+			return;
+		}
+		self.unit_scale = scale;
+	}
+	
+	/// Gets the main light of the scene. This is the first light set by `set_lights`.
 	pub fn light(&self) -> &Light {
-		&self.light
+		&self.lights[0]
 	}
 	
+	/// Sets the only light of the scene.
 	pub fn set_light(&mut self, l: Light) {
-		self.light = l;
+		self.lights = vec![l];
 	}
 	
+	/// Gets the lights of the scene.
+	pub fn lights(&self) -> &[Light] {
+		&self.lights
+	}
+	
+	/// Sets the lights of the scene.
+	/// 
+	/// At most `MAX_LIGHTS` lights are supported. Any lights after that are ignored.
+	pub fn set_lights(&mut self, mut lights: Vec<Light>) {
+		if lights.len() > MAX_LIGHTS {
+			warn!("{} lights given, but only {} are supported. Ignoring the extra lights.", lights.len(), MAX_LIGHTS);
+			lights.truncate(MAX_LIGHTS);
+		}
+		if lights.is_empty() {
+			lights.push(Light::off());
+		}
+		self.lights = lights;
+	}
+	
+	/// Gets the state of the keyboard, as of the latest events processed by `tick`.
+	pub fn keyboard_state(&self) -> &KeyboardState {
+		&self.keyboard_state
+	}
+	
+	/// Gets the state of the mouse, as of the latest events processed by `tick`.
+	pub fn mouse_state(&self) -> &MouseState {
+		&self.mouse_state
+	}
+	
+	/// Gets the state of the gamepads, as of the latest frame.
+	pub fn gamepad_state(&self) -> &GamepadState {
+		&self.gamepad_state
+	}
+	
+	/// Sets the state of the gamepads. This is called by `Game` every frame after polling the gamepads.
+	pub fn set_gamepad_state(&mut self, gamepad_state: GamepadState) {
+		self.gamepad_state = gamepad_state;
+	}
+	
+	/// Binds the next key that is pressed to `action`, like `Game::capture_next_key`. Pressing Escape cancels this.
+	/// 
+	/// The key is captured from the next frame, and the key press isn't passed on to the scene.
+	pub fn capture_next_key(&mut self, action: Action) {
+		self.capture_key = Some(action);
+	}
+	
+	/// Takes the action that was passed to `capture_next_key`, if any. This is called by `Game` every frame.
+	pub fn take_capture_key(&mut self) -> Option<Action> {
+		self.capture_key.take()
+	}
+	
+	/// Gets the camera that the scene is rendered from.
 	pub fn camera(&self) -> &Camera {
 		&self.camera
 	}
 	
+	/// Gets a mutable reference to the camera, so that scene callbacks can move it or change its settings.
+	/// 
+	/// Any changes take effect from the next frame that is rendered.
+	pub fn camera_mut(&mut self) -> &mut Camera {
+		&mut self.camera
+	}
+	
 	/// Sets the tick callback. This will be called every physics tick.
 	pub fn set_tick_callback(&mut self, callback: Option<Rc<RefCell<TickCallback>>>) {
 		self.tick_callback = callback;
 	}
 	
+	/// Sets the collision callback. This will be called after each physics tick, once for each pair of entities that
+	/// started touching during the tick. For sensors, it is called when an entity starts overlapping the sensor.
+	/// 
+	/// The callback may remove entities. Any remaining collisions involving a removed entity are then skipped.
+	pub fn set_collision_callback(&mut self, callback: Option<Rc<RefCell<CollisionCallback>>>) {
+		self.collision_callback = callback;
+	}
+	
 	/// Sets the tick callback. This will be called every frame render.
 	pub fn set_render_callback(&mut self, callback: Option<Rc<RefCell<RenderCallback>>>) {
 		self.render_callback = callback;
 	}
 	
+	/// Starts playing the audio file at `path` in the `assets/` folder as background music.
+	/// 
+	/// If `looping` is true, the music will repeat until `stop_music` is called.
+	#[cfg(feature = "audio")]
+	pub fn play_music<P: AsRef<::std::path::Path>>(&mut self, path: P, looping: bool) -> Result<(), String> {
+		if self.music.is_none() {
+			self.music = Some(MusicPlayer::new()?);
+		}
+		self.music.as_mut().unwrap().play(path, looping)
+	}
+	
+	/// Stops the background music, if any is playing.
+	#[cfg(feature = "audio")]
+	pub fn stop_music(&mut self) {
+		if let Some(music) = self.music.as_mut() {
+			music.stop();
+		}
+	}
+	
+	/// Sets the volume of the background music. 1.0 is the volume of the audio file.
+	#[cfg(feature = "audio")]
+	pub fn set_music_volume(&mut self, volume: f32) -> Result<(), String> {
+		if self.music.is_none() {
+			self.music = Some(MusicPlayer::new()?);
+		}
+		self.music.as_mut().unwrap().set_volume(volume);
+		Ok(())
+	}
+	
 	/// Adds an entity to the world
 	pub fn add_entity(&mut self, build: EntityBuilder) -> EntityId {
 		let id = self.next_free_id;
 		self.next_free_id += 1;
-		
-		let e = build.build_world(&mut self.world);
-		self.entities.insert(id, e);
+		self.insert_entity(id, build);
 		id
 	}
 	
+	/// Builds an entity with the specified id, replacing any entity that already had it.
+	fn insert_entity(&mut self, id: EntityId, build: EntityBuilder) {
+		self.remove_entity(&id);
+		let e = build.build_world_with_margin(&mut self.world, DEFAULT_COLLIDER_MARGIN / self.unit_scale);
+		self.collider_entities.insert(e.collider(), id);
+		self.entities.insert(id, e);
+	}
+	
+	/// Takes a snapshot of the simulation, which can be serialized to JSON and restored with `GameState::restore`.
+	/// 
+	/// The entities, camera, gravity and lights are saved. Springs, hovers and callbacks are not.
+	/// 
+	/// Meshes can't be saved, so every component of every entity must have a mesh name instead
+	/// (see `Component::mesh_name`). Only ball and cuboid collision shapes can be saved.
+	pub fn save(&self) -> Result<SaveData, String> {
+		let mut entities = Vec::with_capacity(self.entities.len());
+		for (&id, e) in self.entities.iter() {
+			let rbody = self.world.rigid_body(e.body()).ok_or_else(|| format!("entity {} has no rigid body", id))?;
+			entities.push(SavedEntity::new(id, e, rbody.position(), rbody.velocity(), rbody.status())?);
+		}
+		let a = self.ambient_light;
+		Ok(SaveData {
+			entities,
+			next_free_id: self.next_free_id,
+			camera: SavedCamera::new(&self.camera),
+			gravity: SavedGravity::new(self.gravity),
+			gravity_enabled: self.gravity_enabled,
+			gravity_softening: self.gravity_softening,
+			unit_scale: self.unit_scale,
+			lights: self.lights.iter().map(SavedLight::new).collect(),
+			ambient_light: [a.x, a.y, a.z, a.w],
+			background_color: self.background_color.into_rgba_array(),
+		})
+	}
+	
+	/// Restores a simulation from a snapshot taken by `GameState::save`.
+	/// 
+	/// `mesh_resolver` is called with the mesh name of each saved component, and should return the mesh to render it
+	/// with. An error is returned if it returns `None`.
+	/// The entities keep the IDs they were saved with.
+	pub fn restore<F>(ctx: &Rc<Context>, data: &SaveData, mut mesh_resolver: F) -> Result<GameState, String>
+			where F: FnMut(&Rc<Context>, &str) -> Option<Rc<RenderableMesh>> {
+		let mut state = GameState::new(data.camera.camera(), data.gravity.gravity());
+		state.set_unit_scale(data.unit_scale);
+		state.set_gravity_enabled(data.gravity_enabled);
+		state.set_gravity_softening(data.gravity_softening);
+		state.set_lights(data.lights.iter().map(|l| l.light()).collect());
+		let a = data.ambient_light;
+		state.set_ambient_light(Vector4::new(a[0], a[1], a[2], a[3]));
+		let c = data.background_color;
+		state.set_background_color(Color::new(c[0], c[1], c[2]).with_alpha(c[3]));
+		
+		for saved in data.entities.iter() {
+			if state.entities.contains_key(&saved.id) {
+				return Err(format!("invalid save data: duplicate entity ID {}", saved.id));
+			}
+			let build = saved.builder(&mut |name: &str| mesh_resolver(ctx, name))?;
+			state.insert_entity(saved.id, build);
+			if let Some(e) = state.entities.get_mut(&saved.id) {
+				e.set_wireframe(saved.wireframe);
+			}
+			if saved.frozen {
+				state.set_entity_frozen(saved.id, true);
+			}
+		}
+		let min_free_id = data.entities.iter().map(|e| e.id + 1).max().unwrap_or(0);
+		state.next_free_id = data.next_free_id.max(min_free_id);
+		Ok(state)
+	}
+	
+	/// Iterates over all of the entities in the world, in ascending order of their IDs.
+	pub fn entities(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+		self.entities.iter().map(|(&id, e)| (id, e))
+	}
+	
+	/// Gets the IDs of all of the entities in the world, in ascending order.
+	pub fn entity_ids(&self) -> Vec<EntityId> {
+		self.entities.keys().cloned().collect()
+	}
+	
+	/// Gets the number of entities in the world.
+	pub fn entity_count(&self) -> usize {
+		self.entities.len()
+	}
+	
 	/// Gets a reference to the entity with the specified id
 	pub fn get_entity(&self, id: EntityId) -> Option<&Entity> {
 		self.entities.get(&id)
@@ -138,10 +519,201 @@ impl GameState {
 		}
 	}
 	
+	/// Gets the position of the entity with the specified id, or `None` if it doesn't exist.
+	pub fn entity_position(&self, id: EntityId) -> Option<Vector3<f32>> {
+		self.get_entity_rigid_body(id).map(|rb| rb.position().translation.vector)
+	}
+	
+	/// Gets the ids of all of the entities whose centres are within `radius` of `center`.
+	/// 
+	/// The ids are returned in ascending order.
+	pub fn entities_within(&self, center: Vector3<f32>, radius: f32) -> Vec<EntityId> {
+		let radius_sq = radius * radius;
+		self.entities.iter()
+			.filter_map(|(&id, e)| self.world.rigid_body(e.body()).map(|rb| (id, rb.position().translation.vector)))
+			.filter(|&(_, pos)| (pos - center).norm_squared() <= radius_sq)
+			.map(|(id, _)| id)
+			.collect()
+	}
+	
+	/// Applies an impulse to the centre of mass of the entity with the specified id, instantly changing its velocity
+	/// by `impulse / mass`.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn apply_impulse(&mut self, id: EntityId, impulse: Vector3<f32>) -> bool {
+		if let Some(rbody) = self.get_entity_rigid_body_mut(id) {
+			let mass = rbody.augmented_mass().mass();
+			if mass > 0.0 && mass.is_finite() {
+				let vel = *rbody.velocity();
+				rbody.set_velocity(Velocity3::new(vel.linear + impulse / mass, vel.angular));
+				rbody.activate();
+			}
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Applies a force to the centre of mass of the entity with the specified id, for the next physics step.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn apply_force(&mut self, id: EntityId, force: Vector3<f32>) -> bool {
+		if let Some(rbody) = self.get_entity_rigid_body_mut(id) {
+			rbody.apply_force(&Force3::linear(force));
+			rbody.activate();
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Sets the linear and angular velocity of the entity with the specified id.
+	/// 
+	/// This is how kinematic entities are moved. Call it every tick, e.g. from a `TickCallback`, to script their motion.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn set_velocity(&mut self, id: EntityId, linear: Vector3<f32>, angular: Vector3<f32>) -> bool {
+		if let Some(rbody) = self.get_entity_rigid_body_mut(id) {
+			rbody.set_velocity(Velocity3::new(linear, angular));
+			rbody.activate();
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Sets the linear damping of the entity with the specified id. See `Entity::set_linear_damping`.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn set_linear_damping(&mut self, id: EntityId, damping: f32) -> bool {
+		if let Some(e) = self.entities.get_mut(&id) {
+			e.set_linear_damping(damping);
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Sets the angular damping of the entity with the specified id. See `Entity::set_angular_damping`.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn set_angular_damping(&mut self, id: EntityId, damping: f32) -> bool {
+		if let Some(e) = self.entities.get_mut(&id) {
+			e.set_angular_damping(damping);
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Freezes or unfreezes the entity with the specified id.
+	/// 
+	/// A frozen entity stays where it is regardless of the forces applied to it. This differs from
+	/// sleeping, as the solver can't wake it up. When unfrozen the entity gets back the status and velocity
+	/// it had before it was frozen.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn set_entity_frozen(&mut self, id: EntityId, frozen: bool) -> bool {
+		if let Some(e) = self.entities.get_mut(&id) {
+			e.set_frozen(&mut self.world, frozen);
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Sets if the entity with the specified id is rendered in wireframe mode, while the rest of the scene
+	/// is rendered normally.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn set_entity_wireframe(&mut self, id: EntityId, wireframe: bool) -> bool {
+		if let Some(e) = self.entities.get_mut(&id) {
+			e.set_wireframe(wireframe);
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Softly attaches the entity with the specified id to `anchor` with a damped spring.
+	/// 
+	/// Every tick a force of `-stiffness * (pos - anchor) - damping * vel` is applied to the entity.
+	/// If the entity is already attached to a point, the old spring is replaced.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn add_world_spring(&mut self, id: EntityId, anchor: Vector3<f32>, stiffness: f32, damping: f32) -> bool {
+		if self.entities.contains_key(&id) {
+			self.world_springs.insert(id, WorldSpring { anchor, stiffness, damping });
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Removes the spring attaching the entity with the specified id to the world.
+	/// 
+	/// Returns false if the entity wasn't attached to the world.
+	pub fn remove_world_spring(&mut self, id: EntityId) -> bool {
+		self.world_springs.remove(&id).is_some()
+	}
+	
+	/// Connects the entities `a` and `b` with a damped spring.
+	/// 
+	/// Every tick a force of `stiffness * (length - rest_length) + damping * separation_speed` pulls the entities
+	/// towards each other along the line between their centres, where `length` is the distance between them and
+	/// `separation_speed` is the rate at which that distance is increasing.
+	/// Equal and opposite forces are applied to each entity.
+	/// 
+	/// Returns `None` if either of the entities doesn't exist, or if `a` and `b` are the same entity.
+	pub fn add_spring(&mut self, a: EntityId, b: EntityId, rest_length: f32, stiffness: f32, damping: f32) -> Option<SpringId> {
+		if a == b || !self.entities.contains_key(&a) || !self.entities.contains_key(&b) {
+			return None;
+		}
+		let id = self.next_free_spring_id;
+		self.next_free_spring_id += 1;
+		self.springs.insert(id, Spring { a, b, rest_length, stiffness, damping });
+		Some(id)
+	}
+	
+	/// Removes the spring with the specified id.
+	/// 
+	/// Returns false if the spring doesn't exist.
+	pub fn remove_spring(&mut self, id: SpringId) -> bool {
+		self.springs.remove(&id).is_some()
+	}
+	
+	/// Makes the entity with the specified id hover at `height` above the surface below it, like a hovercraft.
+	/// 
+	/// Every tick a ray is cast straight down from the entity's centre, and if it hits a surface within twice
+	/// `height`, a critically damped spring force of `stiffness * (height - distance)` pushes the entity towards
+	/// `height`. The spring sags under gravity, so a stiffer spring hovers closer to `height`.
+	/// If the entity is already hovering, the old constraint is replaced.
+	/// 
+	/// Returns false if the entity doesn't exist.
+	pub fn set_hover(&mut self, id: EntityId, height: f32, stiffness: f32) -> bool {
+		if self.entities.contains_key(&id) {
+			self.hovers.insert(id, Hover { height, stiffness });
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Stops the entity with the specified id from hovering.
+	/// 
+	/// Returns false if the entity wasn't hovering.
+	pub fn remove_hover(&mut self, id: EntityId) -> bool {
+		self.hovers.remove(&id).is_some()
+	}
+	
 	/// Remove an entity from the simulation.
 	/// If an entity with the ID specified existed, returns that entity.
 	pub fn remove_entity(&mut self, id: &EntityId) -> Option<Entity> {
+		self.world_springs.remove(id);
+		self.springs.retain(|_, s| s.a != *id && s.b != *id);
+		self.hovers.remove(id);
 		if let Some(e) = self.entities.remove(id) {
+			self.collider_entities.remove(&e.collider());
 			e.remove_world(&mut self.world);
 			Some(e)
 		} else {
@@ -149,31 +721,124 @@ impl GameState {
 		}
 	}
 	
+	/// Casts a ray from `origin` in the direction `dir` against the colliders of all of the entities.
+	/// 
+	/// Returns the closest entity hit, and the distance to it along the ray, or `None` if the ray misses everything.
+	pub fn raycast(&self, origin: Vector3<f32>, dir: Vector3<f32>) -> Option<(EntityId, f32)> {
+		self.raycast_excluding(origin, dir, None, false)
+	}
+	
+	/// Casts a ray like `raycast`, ignoring the entity `exclude`, and sensors if `skip_sensors` is true.
+	fn raycast_excluding(&self, origin: Vector3<f32>, dir: Vector3<f32>, exclude: Option<EntityId>, skip_sensors: bool) -> Option<(EntityId, f32)> {
+		let len = dir.norm();
+		if len == 0.0 {
+			return None;
+		}
+		let ray = Ray::new(Point3::from_coordinates(origin), dir / len);
+		
+		let mut closest: Option<(EntityId, f32)> = None;
+		for (&id, e) in self.entities.iter() {
+			if Some(id) == exclude || (skip_sensors && e.is_sensor()) {
+				continue;
+			}
+			let collider = match self.world.collider(e.collider()) {
+				Some(c) => c,
+				None => continue,
+			};
+			let toi = collider.shape().as_ray_cast()
+				.and_then(|rc| rc.toi_with_ray(collider.position(), &ray, true));
+			if let Some(toi) = toi {
+				if closest.map(|(_, d)| toi < d).unwrap_or(true) {
+					closest = Some((id, toi));
+				}
+			}
+		}
+		closest
+	}
+	
+	/// Moves the camera based on the user's input. This should be called once per frame.
+	/// 
+	/// The camera is updated using the real frame time rather than the fixed physics timestep, so that it moves
+	/// smoothly no matter how many physics ticks happen in a frame.
+	/// The entities are interpolated between physics ticks when they are rendered, so they move smoothly too.
+	/// 
+	/// - `dt` is the number of seconds since the last frame.
+	/// - `settings` are the current game settings.
+	/// - `keyboard_state` is the current state of the keyboard.
+	/// - `gamepad_state` is the current state of the gamepads. The left stick moves the camera like the movement keys,
+	///   and the right stick turns it like the mouse.
+	/// - `mouse_moved` is how much the mouse has moved (in screen pixels) since the last frame.
+	/// - `scroll` is how many lines the mouse wheel has scrolled up since the last frame. This zooms the camera in
+	///   orbit mode.
+	pub fn update_camera(&mut self, dt: f32, settings: &Settings, keyboard_state: &KeyboardState, gamepad_state: &GamepadState, mouse_moved: Vector2<f64>, scroll: f32) {
+		let mut speed = settings.move_speed / self.unit_scale * dt;
+		if settings.sprint.map(|k| keyboard_state.is_pressed(&k)).unwrap_or(false) {
+			speed *= settings.sprint_multiplier;
+		}
+		
+		// The direction that each movement action moves the camera in, relative to the camera
+		const MOVEMENT: [(Action, [f32; 3]); 6] = [
+			(Action::Forward,  [ 0.0,  0.0, -1.0]),
+			(Action::Backward, [ 0.0,  0.0,  1.0]),
+			(Action::Left,     [-1.0,  0.0,  0.0]),
+			(Action::Right,    [ 1.0,  0.0,  0.0]),
+			(Action::Up,       [ 0.0,  1.0,  0.0]),
+			(Action::Down,     [ 0.0, -1.0,  0.0]),
+		];
+		
+		// Translate camera based on keyboard state
+		let mut trans = Vector3::new(0.0, 0.0, 0.0);
+		for &(action, dir) in MOVEMENT.iter() {
+			if settings.is_action_pressed(action, keyboard_state) {
+				trans = trans + Vector3::new(dir[0], dir[1], dir[2]) * speed;
+			}
+		}
+		
+		// Translate and turn camera based on gamepad state.
+		// Pushing a stick up gives a positive y, but forwards is -z for the camera, and mouse movement is measured in
+		// screen pixels where +y is down, so the y of both sticks is flipped.
+		let stick = gamepad_state.left_stick(settings.gamepad_deadzone);
+		trans = trans + Vector3::new(stick.x, 0.0, -stick.y) * speed;
+		let look = gamepad_state.right_stick(settings.gamepad_deadzone) * settings.gamepad_look_speed * dt;
+		let mouse_moved = mouse_moved + Vector2::new(look.x as f64, -look.y as f64);
+		
+		self.camera.translate(trans);
+		self.camera.mouse_moved_with(mouse_moved, settings.mouse_sensitivity, settings.invert_mouse_y);
+		if scroll != 0.0 {
+			self.camera.zoom(scroll);
+		}
+		self.camera.update_shake(dt);
+	}
+	
 	/// Processes a tick of the game state.
 	/// 
+	/// The camera is not moved here, see `update_camera`.
+	/// 
 	/// - `dt` is the number of seconds to process.
 	/// - `settings` are the current game settings.
 	/// - `events` is a list of events that occured since last frame.
 	/// - `mouse_moved` is how much the mouse has moved (in screen pixels) since the last update.
+	/// 
+	/// The events are processed before the tick callback is called, so the keyboard and mouse state seen by the
+	/// callback include them.
 	pub fn tick(&mut self, dt: f32, settings: &Settings, events: &mut Vec<Event>, mouse_moved: Vector2<f64>) {
-		// Call callback
-		{
-			let call = self.tick_callback.clone();
-			if let Some(call) = call {
-				let mut call = call.borrow_mut();
-				call.tick(self, dt, settings, &*events, mouse_moved);
+		for e in events.iter() {
+			// Keep track of the modifier keys
+			match *e {
+				Event::WindowEvent{event: WindowEvent::KeyboardInput{input: KeyboardInput{modifiers, ..}, ..}, ..}
+				| Event::WindowEvent{event: WindowEvent::MouseInput{modifiers, ..}, ..}
+				| Event::WindowEvent{event: WindowEvent::MouseWheel{modifiers, ..}, ..}
+				| Event::WindowEvent{event: WindowEvent::CursorMoved{modifiers, ..}, ..} => {
+					self.keyboard_state.process_modifiers(modifiers);
+				},
+				_ => {},
 			}
-		}
-		
-		// m/s
-		let speed = 4.0 * dt;
-		
-		for e in events.drain(..) {
-			match e {
+			
+			match *e {
 				Event::WindowEvent{event: WindowEvent::KeyboardInput{input: KeyboardInput{state:key_state, virtual_keycode: Some(code), ..}, ..}, ..} => {
 					self.keyboard_state.process_event(key_state, code);
 					if key_state == ElementState::Pressed {
-						if Some(code) == settings.wireframe_toggle {
+						if Some(code) == settings.binding(Action::Wireframe) {
 							self.wireframe_mode = !self.wireframe_mode;
 							if self.wireframe_mode {
 								info!("Wireframe mode enabled");
@@ -183,114 +848,318 @@ impl GameState {
 						}
 					}
 				},
+				Event::WindowEvent{event: WindowEvent::MouseInput{state: button_state, button, ..}, ..} => {
+					self.mouse_state.process_event(button_state, button);
+				},
+				Event::WindowEvent{event: WindowEvent::CursorMoved{position, ..}, ..} => {
+					self.mouse_state.process_cursor_moved(Vector2::new(position.x, position.y));
+				},
 				_ => {}
 			}
 		}
 		
-		// Translate camera based on keyboard state
-		let mut trans = Vector3::new(0.0, 0.0, 0.0);
-		if self.keyboard_state.is_pressed(&settings.forward) {
-			trans = trans + Vector3::new(0.0, 0.0, -speed);
+		// Call callback
+		{
+			let call = self.tick_callback.clone();
+			if let Some(call) = call {
+				let mut call = call.borrow_mut();
+				call.tick(self, dt, settings, &*events, mouse_moved);
+			}
 		}
-		if self.keyboard_state.is_pressed(&settings.backward) {
-			trans = trans + Vector3::new(0.0, 0.0,  speed);
+		
+		events.clear();
+		self.keyboard_state.end_frame();
+		
+		if !settings.paused {
+			self.tick_physics(dt);
 		}
-		if self.keyboard_state.is_pressed(&settings.left) {
-			trans = trans + Vector3::new(-speed, 0.0, 0.0);
+	}
+	
+	/// Processes `n` ticks of the game state, like `tick`, for a frame in which `events` occured.
+	/// 
+	/// The events are distributed over the ticks, and removed from `events`. If `n` is 0, they are kept until the next
+	/// frame. The mouse movement is all processed in the first tick.
+	/// 
+	/// This is how `Game` ticks the state every frame. It doesn't need a window, so recorded input can be replayed
+	/// headlessly with it.
+	pub fn tick_frame(&mut self, dt: f32, n: u32, settings: &Settings, events: &mut Vec<Event>, mouse_moved: Vector2<f64>) {
+		if n == 0 {
+			return;
 		}
-		if self.keyboard_state.is_pressed(&settings.right) {
-			trans = trans + Vector3::new( speed, 0.0, 0.0);
+		
+		// The window system doesn't say when each event occured, only their order, so assume that they occured evenly
+		// spread over the frame. Event `i` of `m` then occured at `(i + 0.5) / m` of the way through the frame.
+		let m = events.len();
+		let step_of = |i: usize| ((2 * i + 1) * n as usize) / (2 * m);
+		let mut events = events.drain(..).enumerate().peekable();
+		let mut step_events = Vec::new();
+		for step in 0..n as usize {
+			step_events.clear();
+			while events.peek().map(|&(i, _)| step_of(i) <= step).unwrap_or(false) {
+				step_events.push(events.next().unwrap().1);
+			}
+			let mouse_moved = if step == 0 { mouse_moved } else { Vector2::zero() };
+			self.tick(dt, settings, &mut step_events, mouse_moved);
 		}
-		if self.keyboard_state.is_pressed(&settings.up) {
-			trans = trans + Vector3::new(0.0,  speed, 0.0);
+	}
+	
+	/// Steps the physics simulation by `dt` seconds, without processing any input or calling the tick callback.
+	/// 
+	/// This doesn't need a window, so it can be used to test scenes headlessly.
+	pub fn tick_physics(&mut self, dt: f32) {
+		// info!("=== Entities ===");
+		// for (i, e) in self.entities.iter() {
+		// 	if let Some(body) = self.world.rigid_body(e.body()) {
+		// 		let pos = body.position().translation.vector;
+		// 		let vel = body.velocity().linear;
+		// 		let mass = body.augmented_mass().mass();
+		// 		info!("{}: mass: {:.2}, pos:[{:.2}, {:.2}, {:.2}], vel:[{:.2}, {:.2}, {:.2}]", i, mass, pos.x, pos.y, pos.z, vel.x, vel.y, vel.z);
+		// 	}
+		// }
+		
+		// Apply gravity to all non-static entities.
+		let scale = self.unit_scale;
+		let sw = Stopwatch::start();
+		match self.gravity {
+			_ if !self.gravity_enabled => self.world.set_gravity(Vector3::new(0.0, 0.0, 0.0)),
+			Gravity::Relative(g) => {
+				let (g, softening) = (g / (scale * scale * scale), self.gravity_softening / scale);
+				self.calculate_gravity(|bodies| gravity::net_forces(bodies, g, softening))
+			},
+			Gravity::RelativeBarnesHut { g, theta, softening } => {
+				let (g, softening) = (g / (scale * scale * scale), softening / scale);
+				self.calculate_gravity(|bodies| gravity::net_forces_barnes_hut(bodies, g, theta, softening))
+			},
+			Gravity::Constant(v) => self.world.set_gravity(v / scale),
+			Gravity::None        => self.world.set_gravity(Vector3::new(0.0, 0.0, 0.0)),
 		}
-		if self.keyboard_state.is_pressed(&settings.down) {
-			trans = trans + Vector3::new(0.0, -speed, 0.0);
+		trace!("Applied gravity ({:.3}ms)", sw.elapsed_secs() * 1000.0);
+		
+		self.apply_world_springs();
+		self.apply_springs();
+		self.apply_hovers();
+		for e in self.entities.values_mut() {
+			e.apply_damping(&mut self.world, dt);
+			e.store_pose(&self.world);
 		}
-		self.camera.translate(trans);
-		self.camera.mouse_moved(mouse_moved);
 		
-		if !settings.paused {
-			// info!("=== Entities ===");
-			// for (i, e) in self.entities.iter() {
-			// 	if let Some(body) = self.world.rigid_body(e.body()) {
-			// 		let pos = body.position().translation.vector;
-			// 		let vel = body.velocity().linear;
-			// 		let mass = body.augmented_mass().mass();
-			// 		info!("{}: mass: {:.2}, pos:[{:.2}, {:.2}, {:.2}], vel:[{:.2}, {:.2}, {:.2}]", i, mass, pos.x, pos.y, pos.z, vel.x, vel.y, vel.z);
-			// 	}
-			// }
+		// Tick world
+		self.world.set_timestep(dt);
+		self.world.step();
+		
+		self.fire_collision_callback();
+	}
+	
+	/// Calls the collision callback for each pair of entities that started touching during the last physics step.
+	fn fire_collision_callback(&mut self) {
+		let call = match self.collision_callback.clone() {
+			Some(call) => call,
+			None => return,
+		};
+		
+		let mut pairs = Vec::new();
+		for event in self.world.contact_events().iter() {
+			if let &ContactEvent::Started(h1, h2) = event {
+				pairs.push((h1, h2));
+			}
+		}
+		for event in self.world.proximity_events().iter() {
+			if event.new_status == Proximity::Intersecting && event.prev_status != Proximity::Intersecting {
+				pairs.push((event.collider1, event.collider2));
+			}
+		}
+		let pairs: Vec<(EntityId, EntityId)> = pairs.into_iter()
+			.filter_map(|(h1, h2)| match (self.collider_entities.get(&h1), self.collider_entities.get(&h2)) {
+				(Some(&a), Some(&b)) => Some((a, b)),
+				_ => None,
+			})
+			.collect();
+		
+		let mut call = call.borrow_mut();
+		for (a, b) in pairs {
+			// An earlier call may have removed one of the entities
+			if self.entities.contains_key(&a) && self.entities.contains_key(&b) {
+				call.on_collision(self, a, b);
+			}
+		}
+	}
+	
+	/// Applies the forces of the springs connecting pairs of entities.
+	fn apply_springs(&mut self) {
+		for spring in self.springs.values() {
+			let (body_a, body_b) = match (self.entities.get(&spring.a), self.entities.get(&spring.b)) {
+				(Some(a), Some(b)) => (a.body(), b.body()),
+				_ => continue,
+			};
+			let (pos_a, vel_a) = match self.world.rigid_body(body_a) {
+				Some(rb) => (rb.position().translation.vector, rb.velocity().linear),
+				None => continue,
+			};
+			let (pos_b, vel_b) = match self.world.rigid_body(body_b) {
+				Some(rb) => (rb.position().translation.vector, rb.velocity().linear),
+				None => continue,
+			};
 			
-			// Apply gravity to all non-static entities.
-			match self.gravity {
-				Gravity::Relative(g) => self.calculate_gravity(g),
-				Gravity::Constant(v) => self.world.set_gravity(v),
-				Gravity::None        => self.world.set_gravity(Vector3::new(0.0, 0.0, 0.0)),
+			let d = pos_b - pos_a;
+			let length = d.norm();
+			if length <= 1e-6 {
+				continue;
 			}
+			let dir = d / length;
+			let separation_speed = (vel_b - vel_a).dot(&dir);
+			let f = dir * (spring.stiffness * (length - spring.rest_length) + spring.damping * separation_speed);
 			
-			// Tick world
-			self.world.set_timestep(dt);
-			self.world.step();
+			if let Some(rb) = self.world.rigid_body_mut(body_a) {
+				rb.apply_force(&Force3::linear(f));
+			}
+			if let Some(rb) = self.world.rigid_body_mut(body_b) {
+				rb.apply_force(&Force3::linear(-f));
+			}
 		}
 	}
 	
-	/// Calculates relative gravity for all the entities in the scene.
-	fn calculate_gravity(&mut self, g: f32) {
-		// info!("Calculating gravity");
-		let id_vec: Vec<_> = self.entities.keys().cloned().collect();
-		let mut ids = id_vec.iter();
-		loop {
-			let a_id = match ids.next() {
-				Some(a) => a,
-				None => break,
+	/// Applies the forces of the springs attaching entities to the world.
+	fn apply_world_springs(&mut self) {
+		for (id, spring) in self.world_springs.iter() {
+			let body = match self.entities.get(id) {
+				Some(e) => e.body(),
+				None => continue,
 			};
-			for b_id in ids.clone() {
-				let f = {
-					let a = self.world.rigid_body(self.entities[&a_id].body());
-					let b = self.world.rigid_body(self.entities[&b_id].body());
-					
-					if let (Some(a), Some(b)) = (a, b) {
-						if a.is_static() && b.is_static() {
-							continue;
-						}
-						let a_mass = a.augmented_mass().mass();
-						let b_mass = b.augmented_mass().mass();
-						
-						// Get unit vector from a to b 
-						let mut v = b.position().translation.vector - a.position().translation.vector;
-						let len_sq = v.norm_squared();
-						v = v / len_sq.sqrt();
-						
-						// Calc force.
-						let lin_force = v * ((g * a_mass * b_mass) / len_sq);
-						// info!("Calculate gravity {} <-> {}: {:6.2?}", a_id, b_id, lin_force);
-						Force3::linear(lin_force)
-					} else {
-						continue;
-					}
-				};
-				// Apply force
-				self.world.rigid_body_mut(self.entities[&a_id].body()).unwrap().apply_force(&f);
-				let f = Force3::linear(-f.linear);
-				self.world.rigid_body_mut(self.entities[&b_id].body()).unwrap().apply_force(&f);
+			if let Some(rbody) = self.world.rigid_body_mut(body) {
+				let pos = rbody.position().translation.vector;
+				let vel = rbody.velocity().linear;
+				let f = -(pos - spring.anchor) * spring.stiffness - vel * spring.damping;
+				rbody.apply_force(&Force3::linear(f));
+			}
+		}
+	}
+	
+	/// Applies the forces holding hovering entities above the surfaces below them.
+	fn apply_hovers(&mut self) {
+		let mut forces = Vec::with_capacity(self.hovers.len());
+		for (&id, hover) in self.hovers.iter() {
+			let body = match self.entities.get(&id) {
+				Some(e) => e.body(),
+				None => continue,
+			};
+			let (pos, vel, mass) = match self.world.rigid_body(body) {
+				Some(rbody) => (rbody.position().translation.vector, rbody.velocity().linear, rbody.augmented_mass().mass()),
+				None => continue,
+			};
+			// Sensors don't collide with anything, so entities hover straight through them
+			let distance = match self.raycast_excluding(pos, Vector3::new(0.0, -1.0, 0.0), Some(id), true) {
+				Some((_, distance)) if distance <= hover.height * 2.0 => distance,
+				_ => continue,
+			};
+			let damping = 2.0 * (hover.stiffness * mass).sqrt();
+			let f = hover.stiffness * (hover.height - distance) - damping * vel.y;
+			forces.push((body, Vector3::new(0.0, f, 0.0)));
+		}
+		for (body, f) in forces {
+			if let Some(rbody) = self.world.rigid_body_mut(body) {
+				rbody.apply_force(&Force3::linear(f));
 			}
 		}
 	}
 	
+	/// Calculates relative gravity for all the entities in the scene, using `net_forces` to calculate the force on each.
+	/// 
+	/// The entities are processed in order of their IDs, so that the result is deterministic.
+	fn calculate_gravity<F>(&mut self, net_forces: F) where F: FnOnce(&[GravityBody]) -> Vec<Vector3<f32>> {
+		// Clear any constant gravity left over from before the gravity mode was changed
+		self.world.set_gravity(Vector3::new(0.0, 0.0, 0.0));
+		
+		let ids = self.entity_ids();
+		
+		let mut handles = Vec::with_capacity(ids.len());
+		let mut bodies = Vec::with_capacity(ids.len());
+		for id in ids.iter() {
+			let handle = self.entities[id].body();
+			if let Some(rb) = self.world.rigid_body(handle) {
+				handles.push(handle);
+				bodies.push(GravityBody {
+					pos: rb.position().translation.vector,
+					mass: rb.augmented_mass().mass(),
+					is_static: rb.is_static(),
+				});
+			}
+		}
+		
+		let forces = net_forces(&bodies);
+		for (handle, f) in handles.into_iter().zip(forces.into_iter()) {
+			self.world.rigid_body_mut(handle).unwrap().apply_force(&Force3::linear(f));
+		}
+	}
+	
+	/// Draws the ID and tag of each entity above it.
+	fn render_labels(&self, r: &mut Render) {
+		// Labels start fading out at this fraction of the label distance
+		const FADE_START: f32 = 0.75;
+		let cam_pos = self.camera.pos();
+		for (id, e) in self.entities.iter() {
+			let aabb = match Aabb::from_entity(&self.world, e) {
+				Some(aabb) => aabb,
+				None => continue,
+			};
+			let centre = aabb.center();
+			let pos = Vector3::new(centre.x, aabb.max.y, centre.z);
+			
+			let dist = (pos - cam_pos).norm();
+			if dist > self.label_distance {
+				continue;
+			}
+			let fade = 1.0 - ((dist / self.label_distance - FADE_START) / (1.0 - FADE_START)).max(0.0);
+			
+			let label = match e.tag() {
+				Some(tag) => format!("{} ({})", id, tag),
+				None => format!("{}", id),
+			};
+			r.draw_str_world(&label, pos, LABEL_FONT_SIZE, Color::WHITE.with_alpha(fade));
+		}
+	}
+	
 	/// Renders the GameState using the specified render handler.
 	/// 
 	/// `fps` is the current frames per second.
-	pub fn render(&mut self, r: &mut Render, fps: u32) {
+	/// `alpha` is how far through the current physics tick the frame is, from 0 to 1. The entities are drawn
+	/// interpolated between their state at the previous tick and the latest tick by this amount.
+	/// If `stats` are given, they are drawn below the FPS counter.
+	pub fn render(&mut self, r: &mut Render, fps: u32, alpha: f32, stats: Option<&FrameStats>) {
 		r.set_camera(self.camera);
 		r.set_ambient_light(self.ambient_light);
-		r.set_light(self.light);
+		r.set_clear_color(self.background_color);
+		r.set_lights(self.lights.clone());
 		r.set_wireframe_mode(self.wireframe_mode);
+		r.set_colorblind_mode(self.colorblind_mode);
 		
-		for e in self.entities.values() {
-			e.render(r, &self.world);
+		{
+			let entities = &self.entities;
+			let world = &self.world;
+			r.render_scene(|r| {
+				// Entities that share a mesh are drawn together in one draw call
+				let mut batch = InstanceBatch::new();
+				for e in entities.values() {
+					if Aabb::from_entity_interpolated(world, e, alpha).map(|aabb| r.cull(aabb)).unwrap_or(false) {
+						continue;
+					}
+					e.render_batched(r, world, alpha, &mut batch);
+				}
+				batch.render(r);
+			});
 		}
 		
-		r.draw_str(&format!("{} FPS", fps), 10.0, 10.0, FONT_SIZE);
+		if self.show_labels {
+			self.render_labels(r);
+		}
+		
+		let draw_calls = r.draw_call_count();
+		r.draw_str(&format!("{} FPS, {} draw calls", fps, draw_calls), 10.0, 10.0, FONT_SIZE);
+		if let Some(stats) = stats {
+			// The line below the FPS counter is left free for the scene's render callback
+			r.draw_str(&stats.summary(), 10.0, 30.0 + 2.0 * FONT_SIZE, FONT_SIZE);
+			let culled = r.culled_count();
+			r.draw_str(&format!("{} entities culled", culled), 10.0, 30.0 + 3.0 * FONT_SIZE, FONT_SIZE);
+		}
 		
 		// Call callback
 		{
@@ -304,3 +1173,73 @@ impl GameState {
 		r.swap();
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nc::shape::Ball;
+	use game::Component;
+	use render::EmptyMesh;
+	use glutin::{DeviceId, ModifiersState, VirtualKeyCode, WindowId};
+	
+	#[test]
+	pub fn test_coincident_bodies_relative_gravity() {
+		let mut state = GameState::new(Camera::new(Vector3::zero()), Gravity::Relative(1.0));
+		let ids: Vec<EntityId> = (0..2).map(|_| {
+			EntityBuilder::new(1.0, 0.5, 0.5)
+				.component(Component::new(Ball::new(0.5), Rc::new(EmptyMesh::new())))
+				.pos(Vector3::new(1.0, 2.0, 3.0))
+				.build(&mut state)
+		}).collect();
+		
+		state.tick_physics(1.0 / 60.0);
+		
+		for &id in ids.iter() {
+			let vel = state.get_entity_rigid_body(id).unwrap().velocity().linear;
+			assert!(vel.iter().all(|x| x.is_finite()), "entity {} has velocity {:?}", id, vel);
+		}
+	}
+	
+	#[test]
+	pub fn test_set_gravity() {
+		let mut state = GameState::new(Camera::new(Vector3::zero()), Gravity::Constant(Vector3::new(0.0, -10.0, 0.0)));
+		let id = EntityBuilder::new(1.0, 0.5, 0.5)
+			.component(Component::new(Ball::new(0.5), Rc::new(EmptyMesh::new())))
+			.build(&mut state);
+		
+		state.tick_physics(0.1);
+		let vel = state.get_entity_rigid_body(id).unwrap().velocity().linear;
+		assert!(vel.y < 0.0, "{:?}", vel);
+		
+		// A lone entity isn't attracted to anything, so it keeps moving at the same velocity
+		state.set_gravity(Gravity::Relative(1.0));
+		assert_eq!(Gravity::Relative(1.0), state.gravity());
+		state.tick_physics(0.1);
+		let new_vel = state.get_entity_rigid_body(id).unwrap().velocity().linear;
+		assert!((new_vel - vel).norm() < 1e-5, "{:?} != {:?}", new_vel, vel);
+	}
+	
+	#[test]
+	pub fn test_tick_tracks_modifiers() {
+		let mut state = GameState::new(Camera::new(Vector3::zero()), Gravity::None);
+		let settings = Settings::default();
+		let key_event = |shift: bool| Event::WindowEvent {
+			window_id: unsafe { WindowId::dummy() },
+			event: WindowEvent::KeyboardInput {
+				device_id: unsafe { DeviceId::dummy() },
+				input: KeyboardInput {
+					scancode: 17,
+					state: ElementState::Pressed,
+					virtual_keycode: Some(VirtualKeyCode::W),
+					modifiers: ModifiersState { shift, ctrl: false, alt: false, logo: false },
+				},
+			},
+		};
+		
+		state.tick(1.0 / 60.0, &settings, &mut vec![key_event(true)], Vector2::zero());
+		assert!(state.keyboard_state().modifiers().shift());
+		
+		state.tick(1.0 / 60.0, &settings, &mut vec![key_event(false)], Vector2::zero());
+		assert!(!state.keyboard_state().modifiers().shift());
+	}
+}
@@ -5,18 +5,43 @@ use std::cell::RefCell;
 use glium::Texture2d;
 use glutin::{Event, MouseScrollDelta, WindowEvent};
 use glutin::dpi::LogicalPosition;
-use nc::shape::{ShapeHandle, Ball, Cuboid};
-use rand;
+use nc::shape::{ShapeHandle, Ball, Cuboid, Compound};
+use util;
 
 use super::state::FONT_SIZE;
-use game::{EntityBuilder, GameState, Gravity, Component, TickCallback, RenderCallback};
-use render::{Render, Camera, SimpleMesh, ColoredMesh, Material, LitMesh, Light, Color};
+use game::{EntityBuilder, EntityId, GameState, Gravity, Component, TickCallback, RenderCallback};
+use render::{Render, RenderableMesh, Camera, SimpleMesh, ColoredMesh, LodMesh, Material, LitMesh, Light, Color};
 use settings::Settings;
 use vfs;
 
 pub struct GameStateBuilder {}
 /// Util functions for creating a GameState
 impl GameStateBuilder {
+	/// Builds a grid of entities in `state`.
+	/// 
+	/// `counts` is the number of cells along the x, y and z axes, `spacing` is the distance between each cell,
+	/// and `origin` is the position of the first cell.
+	/// 
+	/// `f` is called for each cell with the index of the cell and its position, and should return the entity to build
+	/// for that cell. The position is not set automatically. Cells are visited in x, y, z order, with z changing fastest.
+	/// 
+	/// Returns the IDs of the entities built, in the order they were built.
+	pub fn grid<F>(state: &mut GameState, counts: (u32, u32, u32), spacing: Vector3<f32>, origin: Vector3<f32>, mut f: F) -> Vec<EntityId>
+			where F: FnMut(usize, Vector3<f32>) -> EntityBuilder {
+		let (cx, cy, cz) = counts;
+		let mut ids = Vec::with_capacity((cx * cy * cz) as usize);
+		for x in 0..cx {
+			for y in 0..cy {
+				for z in 0..cz {
+					let offset = Vector3::new(x as f32, y as f32, z as f32).component_mul(&spacing);
+					let i = ids.len();
+					ids.push(f(i, origin + offset).build(state));
+				}
+			}
+		}
+		ids
+	}
+	
 	/// Builds the default GameState. Used in the default main.rs for easy prototyping.
 	pub fn build_default(ctx: &Rc<Context>) -> GameState {
 		GameStateBuilder::build_tables(ctx)
@@ -30,49 +55,66 @@ impl GameStateBuilder {
 	/// - One is green which represents earth. This has a medium weight.
 	/// 
 	/// The yellow ball should oscillate around the centre of the scene.
-	#[allow(non_snake_case)]
 	pub fn build_solar(ctx: &Rc<Context>) -> GameState {
-		let sphere = Rc::new(SimpleMesh::sphere(ctx, 4));
-		
+		// The planets are much smaller than the sun, so they don't need as detailed spheres
+		GameStateBuilder::build_solar_with_meshes(|color, radius| {
+			let sphere = Rc::new(SimpleMesh::sphere_for_radius(ctx, radius));
+			Rc::new(ColoredMesh::with_scale(sphere, color, radius))
+		})
+	}
+	
+	/// Builds the `solar` scene, using `mesh` to create the mesh of a sphere with the specified color and radius.
+	/// 
+	/// This doesn't need a window, so the scene can be simulated headlessly with `GameState::tick_physics`.
+	#[allow(non_snake_case)]
+	pub fn build_solar_with_meshes<F>(mut mesh: F) -> GameState where F: FnMut(Color, f32) -> Rc<RenderableMesh> {
 		const PI: f32 = ::std::f32::consts::PI;
 		
 		const SUN_POS: f32 = 0.0;
 		const SUN_MASS: f32 = 100.0;
 		const SUN_RADIUS: f32 = 1.0;
 		let SUN_VOLUME: f32 = (4.0 * PI * SUN_RADIUS * SUN_RADIUS * SUN_RADIUS) / 3.0;
-		let DENSITY: f32 = SUN_MASS / SUN_VOLUME;
 		
+		// The scales are the volumes of the planets. The planets are as dense as the sun, so their masses are the
+		// fraction of the sun's volume that they take up.
 		const EARTH_POS: f32 = 18.0;
 		const EARTH_VEL: f32 = 22.0;
 		const EARTH_SCALE: f32 = 0.05;
+		let EARTH_MASS: f32 = SUN_MASS * EARTH_SCALE / SUN_VOLUME;
 		let EARTH_RADIUS: f32 = ((3.0 * EARTH_SCALE) / (4.0 * PI)).cbrt();
 		
 		const MERCURY_POS: f32 = 10.0;
 		const MERCURY_VEL: f32 = 30.0;
 		const MERCURY_SCALE: f32 = 0.0005;
+		let MERCURY_MASS: f32 = SUN_MASS * MERCURY_SCALE / SUN_VOLUME;
 		let MERCURY_RADIUS: f32 = ((3.0 * MERCURY_SCALE) / (4.0 * PI)).cbrt();
 		
 		// Equalize forces
 		const SUN_VEL: f32 = 0.38;
 		
-		let yellow = Rc::new(ColoredMesh::with_scale(sphere.clone(), Color::YELLOW, SUN_RADIUS));
-		let green  = Rc::new(ColoredMesh::with_scale(sphere.clone(), Color::GREEN , EARTH_RADIUS));
-		let red    = Rc::new(ColoredMesh::with_scale(sphere.clone(), Color::RED   , MERCURY_RADIUS));
+		let yellow = mesh(Color::YELLOW, SUN_RADIUS);
+		let green  = mesh(Color::GREEN , EARTH_RADIUS);
+		let red    = mesh(Color::RED   , MERCURY_RADIUS);
 		
 		let mut state = GameState::new(Camera::new(Vector3::new(0.0, 0.0, 20.0)), Gravity::Relative(1.0));
-		let sun     = EntityBuilder::new(DENSITY, 1.0, 0.0)
+		state.set_background_color(Color::new(0.0, 0.01, 0.05));
+		
+		let sun     = EntityBuilder::new(1.0, 1.0, 0.0)
+			.mass(SUN_MASS)
 			.component(Component::new(Ball::new(SUN_RADIUS), yellow))
 			.pos(Vector3::new(SUN_POS, 0.0, 0.0))
 			.vel(Vector3::new(0.0, 0.0, SUN_VEL))
 			.build(&mut state);
 		
-		let earth   = EntityBuilder::new(DENSITY, 1.0, 0.0)
+		let earth   = EntityBuilder::new(1.0, 1.0, 0.0)
+			.mass(EARTH_MASS)
 			.component(Component::new(Ball::new(EARTH_RADIUS), green))
 			.pos(Vector3::new(EARTH_POS, 0.0, 0.0))
 			.vel(Vector3::new(0.0, 0.0, -EARTH_VEL))
 			.build(&mut state);
 		
-		let mercury = EntityBuilder::new(DENSITY, 1.0, 0.0)
+		let mercury = EntityBuilder::new(1.0, 1.0, 0.0)
+			.mass(MERCURY_MASS)
 			.component(Component::new(Ball::new(MERCURY_RADIUS), red))
 			.pos(Vector3::new(MERCURY_POS, 0.0, 0.0))
 			.vel(Vector3::new(0.0, 0.0, -MERCURY_VEL))
@@ -205,6 +247,7 @@ impl GameStateBuilder {
 	/// 
 	/// This scene consists of 4 planes that are rotated inwards to form a simple cone.
 	/// There are also balls that are generated at the top of the screen that fall down into the cone.
+	/// Each ball is removed as soon as it rolls down to the floor at the bottom of the cone, through a collision callback.
 	/// This shows the physics collision aspect of the system.
 	pub fn build_balls(ctx: &Rc<Context>) -> GameState {
 		// Gen planes
@@ -229,54 +272,70 @@ impl GameStateBuilder {
 		// Plane -X
 		EntityBuilder::new_static(0.1, 0.5)
 			.component(Component::new(Cuboid::new(he), green.clone()))
-			.rot(Rotation3::from_euler_angles(0.0, 0.0, ANG)).
-			build(&mut state);
+			.rot(Rotation3::from_euler_angles(0.0, 0.0, ANG))
+			.build(&mut state);
 		// Plane -Y
 		EntityBuilder::new_static(0.1, 0.5)
 			.component(Component::new(Cuboid::new(he), blue .clone()))
-			.rot(Rotation3::from_euler_angles(ANG, 0.0, 0.0)).
-			build(&mut state);
+			.rot(Rotation3::from_euler_angles(ANG, 0.0, 0.0))
+			.build(&mut state);
+		
+		// The planes make a funnel, with a floor at the bottom that sticks up just above where the planes meet
+		let he = Vector3::new(1.5, 0.5, 1.5);
+		let floor = EntityBuilder::new_static(0.1, 0.5)
+			.component(Component::new(Cuboid::new(he), Rc::new(ColoredMesh::new(Rc::new(SimpleMesh::cuboid(ctx, he)), Color::RED))))
+			.pos(Vector3::new(0.0, 0.8, 0.0))
+			.build(&mut state);
+		
+		// Remove the balls as soon as they roll down the funnel and hit the floor
+		state.set_collision_callback(Some(Rc::new(RefCell::new(move |state: &mut GameState, a: EntityId, b: EntityId| {
+			if a == floor {
+				state.remove_entity(&b);
+			} else if b == floor {
+				state.remove_entity(&a);
+			}
+		}))));
 		
 		// Gen balls at top
 		const SCALE: f32 = 0.4;
 		let ball = ShapeHandle::new(Ball::new(SCALE));
-		let ball_mesh = Rc::new(SimpleMesh::sphere(ctx, 4));
-		
-		let r = move || { rand::thread_rng().gen::<f32>() };
-		
-		const N: i32 = 10;
-		for x in 0..N {
-			let x = (x - N/2) as f32 * 2.0;
-			for z in 0..N {
-				let z = (z - N/2) as f32 * 2.0;
-				let col = Color::new(r(), r(), r());
-				let ball_mesh = Rc::new(ColoredMesh::with_scale(ball_mesh.clone(), col, SCALE));
-				
-				EntityBuilder::new(1.0, 0.3, 0.5)
-					.component(Component::with_handle(ball.clone(), ball_mesh))
-					.pos(Vector3::new(x, 20.0, z))
-					.build(&mut state);
-			}
-		}
+		// Use less detailed spheres for the balls that are further away
+		let ball_lods: Vec<(f32, Rc<SimpleMesh>)> = vec![
+			(15.0, Rc::new(SimpleMesh::sphere(ctx, 4))),
+			(40.0, Rc::new(SimpleMesh::sphere(ctx, 2))),
+			(::std::f32::INFINITY, Rc::new(SimpleMesh::sphere(ctx, 1))),
+		];
+		
+		const N: u32 = 10;
+		let origin = Vector3::new(-((N / 2) as f32) * 2.0, 20.0, -((N / 2) as f32) * 2.0);
+		GameStateBuilder::grid(&mut state, (N, 1, N), Vector3::new(2.0, 0.0, 2.0), origin, |i, pos| {
+			// Spread the hues evenly over the balls
+			let col = Color::from_hsv(i as f32 / (N * N) as f32 * 360.0, 0.8, 0.9);
+			let ball_mesh = Rc::new(LodMesh::new(ball_lods.iter()
+				.map(|&(max_dist, ref mesh)| (max_dist, Rc::new(ColoredMesh::with_scale(mesh.clone(), col, SCALE)) as Rc<RenderableMesh>))
+				.collect()));
+			
+			EntityBuilder::new(1.0, 0.3, 0.5)
+				.component(Component::with_handle(ball.clone(), ball_mesh))
+				.pos(pos)
+		});
 		
 		state
 	}
 	
 	/// Builds the `phong` scene.
 	/// 
-	/// This is basically a lighting test. There are some textured cubes, a textured plane, and a sphere.
+	/// This is basically a lighting test. There are some textured cubes, one of which is loaded from `cube.obj`, a
+	/// textured plane, and a sphere.
 	pub fn build_phong(ctx: &Rc<Context>) -> GameState {
 		let mut state = GameState::new(Camera::new(Vector3::new(2.0, 2.0, 10.0)), Gravity::None);
 		
 		let he = Vector3::new(0.5, 0.5, 0.5);
 		
-		let texture = Rc::new(vfs::load_texture(ctx, "test.png"));
+		let texture = vfs::load_texture_cached(ctx, "test.png");
 		
-		let material = Material::new(
-			Vector4::new(0.9, 0.9, 0.9, 1.0),
-			Vector4::new(0.9, 0.9, 0.9, 1.0),
-			Vector4::new(0.5, 0.5, 0.5, 1.0),
-			1.0);
+		// The broad highlights suit the lighting of this scene better than the preset's shininess
+		let material = Material::plastic(Color::uniform(0.9)).with_shininess(1.0);
 		
 		EntityBuilder::new(1.0, 0.9, 0.1)
 			.component(Component::new(Cuboid::new(he),
@@ -299,12 +358,19 @@ impl GameStateBuilder {
 				.ang_vel(Vector3::new(0.0, 2.0, 1.0))
 				.build(&mut state);
 		
+		EntityBuilder::new(1.0, 0.9, 0.1)
+			.component(Component::new(Cuboid::new(he),
+				Rc::new(vfs::load_obj(ctx, "cube.obj", texture.clone(), material.with_scale_rgba(Vector4::new(1.0, 1.0, 0.0, 1.0))))))
+				.pos(Vector3::new(-5.0, 0.0, 0.0))
+				.ang_vel(Vector3::new(1.0, 0.0, 2.0))
+				.build(&mut state);
+		
 		let red = Rc::new(ColoredMesh::with_scale(Rc::new(SimpleMesh::sphere(ctx, 4)), Color::RED, 0.1));
 		EntityBuilder::new(1.0, 0.9, 0.1)
 			.component(Component::new(Ball::new(0.1), red))
 			.build(&mut state);
 		
-		let sphere_mesh = Rc::new(LitMesh::sphere(ctx, 4, Rc::new(vfs::load_texture(ctx, "white.png")), material));
+		let sphere_mesh = Rc::new(LitMesh::sphere(ctx, 4, vfs::load_texture_cached(ctx, "white.png"), material));
 		
 		EntityBuilder::new(1.0, 0.9, 0.1)
 			.component(Component::new(Ball::new(1.0), sphere_mesh))
@@ -337,58 +403,16 @@ impl GameStateBuilder {
 	/// 
 	/// This is basically an entity test scene, testing how entities interact with themselves and other objects.
 	pub fn build_tables(ctx: &Rc<Context>) -> GameState {
-		fn build_table(ctx: &Rc<Context>, state: &mut GameState, top_tex: Rc<Texture2d>, leg_tex: Rc<Texture2d>, pos: Vector3<f32>, material: Material) {
-			let r = move || { rand::thread_rng().gen::<f32>() };
-			//let r_neg = move || { rand::thread_rng().gen::<f32>() * 2.0 - 1.0 };
-			
-			let col = Vector4::new(r(), r(), r(), 1.0);
-			let material = material.with_scale_rgba(col);
-			
-			let table_size = 2.0;
-			let top_h = 0.5;
-			let leg_h = 1.2;
-			let leg_w = 0.4;
-			
-			let table_size2 = table_size / 2.0;
-			let top_h2 = top_h / 2.0;
-			let leg_h2 = leg_h / 2.0;
-			let leg_w2 = leg_w / 2.0;
-			
-			let leg_he = Vector3::new(leg_w2, leg_h2, leg_w2);
-			let leg_mesh = Rc::new(LitMesh::cuboid(ctx, leg_he, leg_tex, material));
-			let leg = Component::new(Cuboid::new(leg_he), leg_mesh);
-			
-			let top_he = Vector3::new(table_size2, top_h2, table_size2);
-			let top_mesh = Rc::new(LitMesh::cuboid(ctx, top_he, top_tex, material));
-			let top = Component::new(Cuboid::new(top_he), top_mesh);
-			
-			let off = table_size2 - leg_w2;
-			EntityBuilder::new(1.0, 0.3, 0.6)
-				// Add legs
-				.component(leg.clone().pos(Vector3::new( off, -top_h2-leg_h2,  off)))
-				.component(leg.clone().pos(Vector3::new(-off, -top_h2-leg_h2,  off)))
-				.component(leg.clone().pos(Vector3::new( off, -top_h2-leg_h2, -off)))
-				.component(leg.clone().pos(Vector3::new(-off, -top_h2-leg_h2, -off)))
-				// Add table top
-				.component(top)
-				.pos(pos)
-				.build(state);
-		}
-		
 		let mut state = GameState::new(
 			Camera::new(Vector3::new(2.0, 2.0, 10.0)),
 			Gravity::Constant(Vector3::new(0.0, -9.81, 0.0)));
 		
 		let light_pos = Vector3::new(3.0, 3.0, 0.0);
 		
-		let material = Material::new(
-			Vector4::new(0.9, 0.9, 0.9, 1.0),
-			Vector4::new(0.9, 0.9, 0.9, 1.0),
-			Vector4::new(0.5, 0.5, 0.5, 1.0),
-			1.0);
+		let material = Material::plastic(Color::uniform(0.9)).with_shininess(1.0);
 		
-		let top_tex = Rc::new(vfs::load_texture(ctx, "test.png"));
-		let leg_tex = Rc::new(vfs::load_texture(ctx, "white.png"));
+		let top_tex = vfs::load_texture_cached(ctx, "test.png");
+		let leg_tex = vfs::load_texture_cached(ctx, "white.png");
 		
 		// X- Plane
 		let he = Vector3::new(1.0, 20.0, 20.0);
@@ -418,17 +442,14 @@ impl GameStateBuilder {
 			.build(&mut state);
 		
 		// Tables
-		build_table(ctx, &mut state, top_tex.clone(), leg_tex.clone(), Vector3::new(0.0, 1.0, 0.0)  , material);
-		build_table(ctx, &mut state, top_tex.clone(), leg_tex.clone(), Vector3::new(0.3, 3.0, 0.1)  , material);
-		build_table(ctx, &mut state, top_tex.clone(), leg_tex.clone(), Vector3::new(-0.5, 6.0, -0.4), material);
-		build_table(ctx, &mut state, top_tex.clone(), leg_tex.clone(), Vector3::new(0.5, 9.0, 0.4)  , material);
-		
-		let f = move |n: usize| { n as f32 * 2.0 - 5.0 };
-		for x in 0..10 {
-			for z in 0..10 {
-				build_table(ctx, &mut state, top_tex.clone(), leg_tex.clone(), Vector3::new(f(x), 12.0, f(z)), material);
-			}
-		}
+		build_table(ctx, top_tex.clone(), leg_tex.clone(), Vector3::new(0.0, 1.0, 0.0)  , material).build(&mut state);
+		build_table(ctx, top_tex.clone(), leg_tex.clone(), Vector3::new(0.3, 3.0, 0.1)  , material).build(&mut state);
+		build_table(ctx, top_tex.clone(), leg_tex.clone(), Vector3::new(-0.5, 6.0, -0.4), material).build(&mut state);
+		build_table(ctx, top_tex.clone(), leg_tex.clone(), Vector3::new(0.5, 9.0, 0.4)  , material).build(&mut state);
+		
+		GameStateBuilder::grid(&mut state, (10, 1, 10), Vector3::new(2.0, 0.0, 2.0), Vector3::new(-5.0, 12.0, -5.0), |_, pos| {
+			build_table(ctx, top_tex.clone(), leg_tex.clone(), pos, material)
+		});
 		
 		// Light indicator
 		let red = Rc::new(ColoredMesh::with_scale(Rc::new(SimpleMesh::sphere(ctx, 4)), Color::RED, 0.1));
@@ -447,6 +468,277 @@ impl GameStateBuilder {
 		
 		state
 	}
+	
+	/// Builds the `springs` scene.
+	/// 
+	/// This scene consists of two balls, red and blue, connected by a spring.
+	/// They start stretched apart and moving in opposite directions, so they tumble around each other while the
+	/// spring oscillates.
+	pub fn build_springs(ctx: &Rc<Context>) -> GameState {
+		let sphere = Rc::new(SimpleMesh::sphere(ctx, 4));
+		
+		let red  = Rc::new(ColoredMesh::new(sphere.clone(), Color::RED));
+		let blue = Rc::new(ColoredMesh::new(sphere.clone(), Color::BLUE));
+		
+		let mut state = GameState::new(Camera::new(Vector3::new(0.0, 2.0, 12.0)), Gravity::None);
+		let a = EntityBuilder::new(1.0, 0.9, 0.1)
+			.component(Component::new(Ball::new(0.5), red))
+			.pos(Vector3::new(-3.0, 0.0, 0.0))
+			.vel(Vector3::new(0.0,  1.0, 0.0))
+			.build(&mut state);
+		
+		let b = EntityBuilder::new(1.0, 0.9, 0.1)
+			.component(Component::new(Ball::new(0.5), blue))
+			.pos(Vector3::new( 3.0, 0.0, 0.0))
+			.vel(Vector3::new( 0.0, -1.0, 0.0))
+			.build(&mut state);
+		
+		state.add_spring(a, b, 3.0, 2.0, 0.05);
+		
+		state
+	}
+	
+	/// Builds the `platform` scene.
+	/// 
+	/// This scene consists of a kinematic platform that moves back and forth, with balls dropped on top of it.
+	/// The balls are carried along by the platform through friction.
+	pub fn build_platform(ctx: &Rc<Context>) -> GameState {
+		const SPEED: f32 = 2.0;
+		const EXTENT: f32 = 5.0;
+		
+		let mut state = GameState::new(Camera::new(Vector3::new(0.0, 6.0, 15.0)), Gravity::Constant(Vector3::new(0.0, -9.81, 0.0)));
+		
+		let he = Vector3::new(4.0, 0.25, 4.0);
+		let platform_mesh = Rc::new(ColoredMesh::new(Rc::new(SimpleMesh::cuboid(ctx, he)), Color::GREEN));
+		let platform = EntityBuilder::new_kinematic(0.1, 1.0)
+			.component(Component::new(Cuboid::new(he), platform_mesh))
+			.vel(Vector3::new(SPEED, 0.0, 0.0))
+			.build(&mut state);
+		
+		const SCALE: f32 = 0.4;
+		let ball = ShapeHandle::new(Ball::new(SCALE));
+		let ball_mesh = Rc::new(ColoredMesh::with_scale(Rc::new(SimpleMesh::sphere(ctx, 4)), Color::RED, SCALE));
+		GameStateBuilder::grid(&mut state, (3, 1, 3), Vector3::new(2.0, 0.0, 2.0), Vector3::new(-2.0, 3.0, -2.0), |_, pos| {
+			EntityBuilder::new(1.0, 0.1, 1.0)
+				.component(Component::with_handle(ball.clone(), ball_mesh.clone()))
+				.pos(pos)
+		});
+		
+		// Turn the platform around when it reaches either end
+		state.set_tick_callback(Some(Rc::new(RefCell::new(move |state: &mut GameState, _dt: f32, _settings: &Settings, _events: &[Event], _mouse_moved: Vector2<f64>| {
+			let x = match state.entity_position(platform) {
+				Some(pos) => pos.x,
+				None => return,
+			};
+			let vel_x = match state.get_entity_rigid_body(platform) {
+				Some(rb) => rb.velocity().linear.x,
+				None => return,
+			};
+			let vel_x = if x >= EXTENT {
+				-SPEED
+			} else if x <= -EXTENT {
+				SPEED
+			} else {
+				vel_x
+			};
+			state.set_velocity(platform, Vector3::new(vel_x, 0.0, 0.0), Vector3::zero());
+		}))));
+		
+		state
+	}
+	
+	/// Builds the `spotlight` scene.
+	/// 
+	/// This scene consists of a table on a plane, lit up by a spotlight from above. The edge of the cone of light
+	/// fades out smoothly across the table and the floor.
+	pub fn build_spotlight(ctx: &Rc<Context>) -> GameState {
+		let mut state = GameState::new(
+			Camera::new(Vector3::new(0.0, 2.0, 8.0)),
+			Gravity::Constant(Vector3::new(0.0, -9.81, 0.0)));
+		
+		let light_pos = Vector3::new(1.0, 4.0, 1.0);
+		
+		let material = Material::matte(Color::uniform(0.9));
+		
+		let top_tex = vfs::load_texture_cached(ctx, "test.png");
+		let leg_tex = vfs::load_texture_cached(ctx, "white.png");
+		
+		// Floor
+		let mesh = Rc::new(LitMesh::plane(ctx, 40.0, 40.0, 64, top_tex.clone(), material));
+		EntityBuilder::new_static(0.3, 0.7)
+			.component(Component::new(floor_collider(20.0, 20.0), mesh))
+			.pos(Vector3::new(0.0, -2.0, 0.0))
+			.build(&mut state);
+		
+		// Table
+		build_table(ctx, top_tex.clone(), leg_tex.clone(), Vector3::new(0.0, -0.5, 0.0), Material::plastic(Color::uniform(0.9)))
+			.build(&mut state);
+		
+		// Light indicator
+		let red = Rc::new(ColoredMesh::with_scale(Rc::new(SimpleMesh::sphere(ctx, 4)), Color::RED, 0.1));
+		EntityBuilder::new_static(0.3, 0.7)
+			.component(Component::new(Ball::new(0.1), red))
+			.pos(light_pos)
+			.build(&mut state);
+		
+		state.set_ambient_light(Vector4::new(0.05, 0.05, 0.05, 1.0));
+		
+		// Point at the centre of the table, from slightly off to the side
+		state.set_light(Light::new_spotlight(
+			light_pos,
+			Vector3::new(0.0, -0.5, 0.0) - light_pos,
+			Vector4::new(1.0, 1.0, 0.9, 1.0),
+			Vector4::new(1.0, 1.0, 0.9, 1.0),
+			1.0, 0.05, 0.01,
+			util::to_rad(25.0), 2.0));
+		
+		state
+	}
+	
+	/// Builds the `gravity` scene.
+	/// 
+	/// This scene consists of a grid of balls above a floor. Pressing G switches the gravity between relative, where the
+	/// balls attract each other and clump together, and constant, where they fall onto the floor.
+	pub fn build_gravity(ctx: &Rc<Context>) -> GameState {
+		let relative = Gravity::Relative(2.0);
+		let constant = Gravity::Constant(Vector3::new(0.0, -9.81, 0.0));
+		
+		let mut state = GameState::new(Camera::new(Vector3::new(0.0, 4.0, 16.0)), relative);
+		
+		let he = Vector3::new(10.0, 0.5, 10.0);
+		let floor_mesh = Rc::new(ColoredMesh::new(Rc::new(SimpleMesh::cuboid(ctx, he)), Color::BLUE));
+		EntityBuilder::new_static(0.3, 0.7)
+			.component(Component::new(Cuboid::new(he), floor_mesh))
+			.pos(Vector3::new(0.0, -4.0, 0.0))
+			.build(&mut state);
+		
+		const SCALE: f32 = 0.4;
+		let ball = ShapeHandle::new(Ball::new(SCALE));
+		let sphere = Rc::new(SimpleMesh::sphere_for_radius(ctx, SCALE));
+		GameStateBuilder::grid(&mut state, (3, 3, 3), Vector3::new(2.0, 2.0, 2.0), Vector3::new(-2.0, 0.0, -2.0), |i, pos| {
+			let col = Color::from_hsv(i as f32 / 27.0 * 360.0, 0.8, 0.9);
+			EntityBuilder::new(1.0, 0.3, 0.5)
+				.component(Component::with_handle(ball.clone(), Rc::new(ColoredMesh::with_scale(sphere.clone(), col, SCALE))))
+				.pos(pos)
+		});
+		
+		state.set_tick_callback(Some(Rc::new(RefCell::new(move |state: &mut GameState, _dt: f32, _settings: &Settings, events: &[Event], _mouse_moved: Vector2<f64>| {
+			for event in events.iter() {
+				if let &Event::WindowEvent{event: WindowEvent::ReceivedCharacter('g'), ..} = event {
+					let g = if state.gravity() == relative { constant } else { relative };
+					info!("Changing gravity to {:?}", g);
+					state.set_gravity(g);
+				}
+			}
+		}))));
+		state.set_render_callback(Some(Rc::new(RefCell::new(move |state: &mut GameState, r: &mut Render, _fps: u32| {
+			let s = if state.gravity() == relative { "relative gravity" } else { "constant gravity" };
+			r.draw_str(s, 10.0, 20.0 + FONT_SIZE, FONT_SIZE);
+		}))));
+		
+		state
+	}
+	
+	/// Builds the `hover` scene.
+	/// 
+	/// This scene consists of a row of hovering boxes gliding across a floor, through a sensor gate and up a ramp.
+	/// The boxes follow the height of the surface below them, and ignore the gate as it is a sensor.
+	pub fn build_hover(ctx: &Rc<Context>) -> GameState {
+		let mut state = GameState::new(
+			Camera::new(Vector3::new(8.0, 6.0, 10.0)),
+			Gravity::Constant(Vector3::new(0.0, -9.81, 0.0)));
+		
+		let he = Vector3::new(15.0, 0.5, 15.0);
+		let floor_mesh = Rc::new(ColoredMesh::new(Rc::new(SimpleMesh::cuboid(ctx, he)), Color::BLUE));
+		EntityBuilder::new_static(0.3, 0.7)
+			.component(Component::new(Cuboid::new(he), floor_mesh))
+			.pos(Vector3::new(0.0, -0.5, 0.0))
+			.build(&mut state);
+		
+		let he = Vector3::new(4.0, 0.5, 4.0);
+		let ramp_mesh = Rc::new(ColoredMesh::new(Rc::new(SimpleMesh::cuboid(ctx, he)), Color::CYAN));
+		EntityBuilder::new_static(0.3, 0.7)
+			.component(Component::new(Cuboid::new(he), ramp_mesh))
+			.pos(Vector3::new(0.0, 0.5, -8.0))
+			.rot(Rotation3::from_euler_angles(0.25, 0.0, 0.0))
+			.build(&mut state);
+		
+		// The boxes hover straight through the gate, as raycasts for hovering skip sensors
+		let he = Vector3::new(3.5, 2.0, 0.25);
+		let gate_mesh = Rc::new(ColoredMesh::new(Rc::new(SimpleMesh::cuboid(ctx, he)), Color::GREEN));
+		let gate = EntityBuilder::new_static(0.3, 0.7)
+			.component(Component::new(Cuboid::new(he), gate_mesh).wireframe(true))
+			.pos(Vector3::new(0.0, 2.0, -1.0))
+			.sensor()
+			.build(&mut state);
+		
+		let he = Vector3::new(0.5, 0.25, 0.5);
+		let box_mesh = Rc::new(SimpleMesh::cuboid(ctx, he));
+		for i in 0..3 {
+			let col = Color::from_hsv(i as f32 * 120.0, 0.8, 0.9);
+			let id = EntityBuilder::new(1.0, 0.3, 0.5)
+				.component(Component::new(Cuboid::new(he), Rc::new(ColoredMesh::new(box_mesh.clone(), col))))
+				.pos(Vector3::new((i as f32 - 1.0) * 2.5, 1.5, 8.0))
+				.vel(Vector3::new(0.0, 0.0, -2.0 - i as f32 * 0.5))
+				.angular_damping(0.9)
+				.build(&mut state);
+			state.set_hover(id, 1.5, 40.0);
+		}
+		
+		state.set_collision_callback(Some(Rc::new(RefCell::new(move |_state: &mut GameState, a: EntityId, b: EntityId| {
+			if a == gate || b == gate {
+				info!("Entity {} passed through the gate", if a == gate { b } else { a });
+			}
+		}))));
+		
+		state
+	}
+}
+
+/// Builds the collider for a floor made with `LitMesh::plane`: a cuboid 1 unit thick, with its top level with the
+/// plane.
+fn floor_collider(half_width: f32, half_depth: f32) -> Compound<f32> {
+	let iso = Isometry3::new(Vector3::new(0.0, -0.5, 0.0), Vector3::zero());
+	Compound::new(vec![(iso, ShapeHandle::new(Cuboid::new(Vector3::new(half_width, 0.5, half_depth))))])
+}
+
+/// Builds a table with a randomly tinted `material`, with the centre of its top at `pos`.
+fn build_table(ctx: &Rc<Context>, top_tex: Rc<Texture2d>, leg_tex: Rc<Texture2d>, pos: Vector3<f32>, material: Material) -> EntityBuilder {
+	let r = || util::with_scene_rng(|rng| rng.gen::<f32>());
+	//let r_neg = || util::with_scene_rng(|rng| rng.gen::<f32>() * 2.0 - 1.0);
+	
+	let col = Vector4::new(r(), r(), r(), 1.0);
+	let material = material.with_scale_rgba(col);
+	
+	let table_size = 2.0;
+	let top_h = 0.5;
+	let leg_h = 1.2;
+	let leg_w = 0.4;
+	
+	let table_size2 = table_size / 2.0;
+	let top_h2 = top_h / 2.0;
+	let leg_h2 = leg_h / 2.0;
+	let leg_w2 = leg_w / 2.0;
+	
+	let leg_he = Vector3::new(leg_w2, leg_h2, leg_w2);
+	let leg_mesh = Rc::new(LitMesh::cuboid(ctx, leg_he, leg_tex, material));
+	let leg = Component::new(Cuboid::new(leg_he), leg_mesh);
+	
+	let top_he = Vector3::new(table_size2, top_h2, table_size2);
+	let top_mesh = Rc::new(LitMesh::cuboid(ctx, top_he, top_tex, material));
+	let top = Component::new(Cuboid::new(top_he), top_mesh);
+	
+	let off = table_size2 - leg_w2;
+	EntityBuilder::new(1.0, 0.3, 0.6)
+		// Add legs
+		.component(leg.clone().pos(Vector3::new( off, -top_h2-leg_h2,  off)))
+		.component(leg.clone().pos(Vector3::new(-off, -top_h2-leg_h2,  off)))
+		.component(leg.clone().pos(Vector3::new( off, -top_h2-leg_h2, -off)))
+		.component(leg.clone().pos(Vector3::new(-off, -top_h2-leg_h2, -off)))
+		// Add table top
+		.component(top)
+		.pos(pos)
+		.tag("table")
 }
 
 #[derive(Debug)]
@@ -520,3 +812,32 @@ impl RenderCallback for LightHandler {
 		r.draw_str(s, 10.0, 20.0 + FONT_SIZE, FONT_SIZE);
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	/// A mesh that doesn't render anything, so that scenes can be built without a window.
+	struct NullMesh;
+	impl RenderableMesh for NullMesh {
+		fn render(&self, _r: &mut Render, _model: Matrix4<f32>, _wireframe: bool) {}
+	}
+	
+	#[test]
+	pub fn test_solar_sun_stays_bounded() {
+		let mut state = GameStateBuilder::build_solar_with_meshes(|_, _| Rc::new(NullMesh));
+		let sun = state.entity_ids()[0];
+		
+		const DT: f32 = 1.0 / 120.0;
+		for _ in 0..10_000 {
+			state.tick_physics(DT);
+		}
+		
+		// The sun starts at the origin moving at 0.38 units per second, so after ~83 seconds it should still be
+		// close to the centre of the scene, and in the plane of the orbits.
+		let pos = state.entity_position(sun).unwrap();
+		assert!(pos.x.is_finite() && pos.y.is_finite() && pos.z.is_finite(), "sun position is not finite: {:?}", pos);
+		assert!(pos.norm() < 50.0, "sun left the bounding region: {:?}", pos);
+		assert!(pos.y.abs() < 1e-3, "sun left the plane of the orbits: {:?}", pos);
+	}
+}
@@ -0,0 +1,128 @@
+//! Frame time statistics.
+use prelude::*;
+use std::collections::VecDeque;
+
+/// The default number of frames that statistics are kept for.
+pub const DEFAULT_FRAME_STATS_LEN: usize = 240;
+
+/// A rolling buffer of the durations of the latest frames.
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+	frames: VecDeque<Duration>,
+	capacity: usize,
+}
+impl FrameStats {
+	/// Constructs empty stats that keep the durations of the latest `capacity` frames.
+	pub fn new(capacity: usize) -> FrameStats {
+		let capacity = capacity.max(1);
+		FrameStats {
+			frames: VecDeque::with_capacity(capacity),
+			capacity,
+		}
+	}
+	
+	/// Adds the duration of a frame, forgetting the oldest frame if the buffer is full.
+	pub fn push(&mut self, frame: Duration) {
+		if self.frames.len() == self.capacity {
+			self.frames.pop_front();
+		}
+		self.frames.push_back(frame);
+	}
+	
+	/// Forgets all of the frames.
+	pub fn clear(&mut self) {
+		self.frames.clear();
+	}
+	
+	/// Gets the number of frames that statistics are currently calculated over.
+	pub fn len(&self) -> usize {
+		self.frames.len()
+	}
+	
+	/// Returns true if no frames have been added.
+	pub fn is_empty(&self) -> bool {
+		self.frames.is_empty()
+	}
+	
+	/// Gets the duration of the shortest frame, in milliseconds.
+	pub fn min_ms(&self) -> f64 {
+		self.frames_ms().fold(None, |min: Option<f64>, t| Some(min.map_or(t, |m| m.min(t)))).unwrap_or(0.0)
+	}
+	
+	/// Gets the mean duration of the frames, in milliseconds.
+	pub fn avg_ms(&self) -> f64 {
+		if self.frames.is_empty() {
+			0.0
+		} else {
+			self.frames_ms().sum::<f64>() / self.frames.len() as f64
+		}
+	}
+	
+	/// Gets the duration of the longest frame, in milliseconds.
+	pub fn max_ms(&self) -> f64 {
+		self.frames_ms().fold(0.0, f64::max)
+	}
+	
+	/// Gets the `p`th percentile of the frame durations, in milliseconds, where `p` is from 0 to 100.
+	/// 
+	/// This is the shortest duration that at least `p`% of the frames are no longer than.
+	pub fn percentile_ms(&self, p: f64) -> f64 {
+		if self.frames.is_empty() {
+			return 0.0;
+		}
+		let mut sorted: Vec<f64> = self.frames_ms().collect();
+		sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+		let rank = (p.max(0.0).min(100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+		sorted[rank.max(1) - 1]
+	}
+	
+	/// Formats the statistics as a line of text, for drawing on screen.
+	pub fn summary(&self) -> String {
+		format!("Frame time: min {:.2}ms, avg {:.2}ms, max {:.2}ms, 99% {:.2}ms",
+			self.min_ms(), self.avg_ms(), self.max_ms(), self.percentile_ms(99.0))
+	}
+	
+	fn frames_ms<'a>(&'a self) -> impl Iterator<Item = f64> + 'a {
+		self.frames.iter().map(|d| d.as_secs_partial() * 1000.0)
+	}
+}
+impl Default for FrameStats {
+	fn default() -> FrameStats {
+		FrameStats::new(DEFAULT_FRAME_STATS_LEN)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn assert_close(expected: f64, actual: f64) {
+		assert!((expected - actual).abs() < 1e-6, "expected {}, got {}", expected, actual);
+	}
+	
+	#[test]
+	pub fn test_stats() {
+		let mut stats = FrameStats::new(100);
+		assert_close(0.0, stats.avg_ms());
+		for ms in 1..101 {
+			stats.push(Duration::from_millis(ms));
+		}
+		assert_close(1.0, stats.min_ms());
+		assert_close(100.0, stats.max_ms());
+		assert_close(50.5, stats.avg_ms());
+		assert_close(99.0, stats.percentile_ms(99.0));
+		assert_close(50.0, stats.percentile_ms(50.0));
+		assert_close(100.0, stats.percentile_ms(100.0));
+		assert_close(1.0, stats.percentile_ms(0.0));
+	}
+	
+	#[test]
+	pub fn test_oldest_frames_are_forgotten() {
+		let mut stats = FrameStats::new(3);
+		for &ms in [50, 1, 2, 3].iter() {
+			stats.push(Duration::from_millis(ms));
+		}
+		assert_eq!(3, stats.len());
+		assert_close(3.0, stats.max_ms());
+	}
+}
@@ -14,11 +14,22 @@ extern crate num;
 extern crate log;
 extern crate simplelog;
 extern crate unicode_normalization;
+extern crate toml;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 #[macro_use]
 extern crate cfg_if;
 
 #[cfg(windows)]
 extern crate user32;
+#[cfg(feature = "audio")]
+extern crate rodio;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "gamepad")]
+extern crate gilrs;
 
 use prelude::*;
 use std::io::{self, Write, BufWriter};
@@ -35,6 +46,9 @@ pub mod game;
 pub mod util;
 pub mod settings;
 pub mod vfs;
+pub mod collision;
+#[cfg(feature = "audio")]
+pub mod audio;
 
 use game::{Game, GameState};
 use settings::Settings;
@@ -1,9 +1,43 @@
 use prelude::*;
 
 use na;
+use rand;
 
 use util;
 
+/// The default minimum distance of an orbiting camera from its target.
+const DEFAULT_MIN_ORBIT_DISTANCE: f32 = 0.5;
+/// The default maximum distance of an orbiting camera from its target.
+const DEFAULT_MAX_ORBIT_DISTANCE: f32 = 1000.0;
+/// How much the orbit distance is multiplied by per line scrolled.
+const ORBIT_ZOOM_FACTOR: f32 = 0.9;
+/// How far the camera rotates per pixel moved by the mouse, in radians, at a sensitivity of 1.
+const MOUSE_RADIANS_PER_PIXEL: f32 = 0.008;
+/// The maximum number of camera shakes that can be active at once.
+const MAX_SHAKES: usize = 8;
+/// The maximum rotation of a camera shake, in radians per unit of intensity.
+const SHAKE_ROTATION: f32 = 0.02;
+
+/// A camera shake that decays over time.
+#[derive(Copy, Clone, Debug)]
+struct Shake {
+	intensity: f32,
+	duration: f32,
+	remaining: f32,
+}
+impl Shake {
+	const NONE: Shake = Shake { intensity: 0.0, duration: 0.0, remaining: 0.0 };
+	
+	/// Gets the current strength of the shake, which decays linearly to zero over its duration.
+	fn strength(&self) -> f32 {
+		if self.remaining <= 0.0 || self.duration <= 0.0 {
+			0.0
+		} else {
+			self.intensity * self.remaining / self.duration
+		}
+	}
+}
+
 /// Structure holding the position and rotation of a camera
 #[derive(Copy, Clone, Debug)]
 pub struct Camera {
@@ -11,6 +45,15 @@ pub struct Camera {
 	yrot: f32,
 	xrot: f32,
 	view_mat: Option<Matrix4<f32>>,
+	/// The target and distance of the camera when in orbit mode
+	orbit: Option<(Vector3<f32>, f32)>,
+	min_orbit_distance: f32,
+	max_orbit_distance: f32,
+	shakes: [Shake; MAX_SHAKES],
+	/// The current offset of the camera's position due to shaking
+	shake_offset: Vector3<f32>,
+	/// The current offset of the camera's rotation due to shaking
+	shake_rot: Vector2<f32>,
 }
 impl Camera {
 	/// Constructs a new camera at the specified path.
@@ -20,6 +63,12 @@ impl Camera {
 			yrot: 0.0,
 			xrot: 0.0,
 			view_mat: None,
+			orbit: None,
+			min_orbit_distance: DEFAULT_MIN_ORBIT_DISTANCE,
+			max_orbit_distance: DEFAULT_MAX_ORBIT_DISTANCE,
+			shakes: [Shake::NONE; MAX_SHAKES],
+			shake_offset: Vector3::zero(),
+			shake_rot: Vector2::zero(),
 		}
 	}
 	
@@ -27,36 +76,235 @@ impl Camera {
 		self.pos
 	}
 	
+	/// Gets the rotation of the camera from camera space to world space.
+	fn rotation(&self) -> Rotation3<f32> {
+		let rot_y = Rotation3::from_euler_angles(-self.yrot, 0.0, 0.0);
+		let rot_x = Rotation3::from_euler_angles(0.0, -self.xrot, 0.0);
+		(rot_y * rot_x).inverse()
+	}
+	
+	/// Gets the unit vector in world space of the direction that the camera is facing.
+	pub fn forward(&self) -> Vector3<f32> {
+		self.rotation() * Vector3::new(0.0, 0.0, -1.0)
+	}
+	
+	/// Gets the unit vector in world space pointing to the right of the camera.
+	pub fn right(&self) -> Vector3<f32> {
+		self.rotation() * Vector3::new(1.0, 0.0, 0.0)
+	}
+	
+	/// Gets the unit vector in world space pointing up from the camera.
+	pub fn up(&self) -> Vector3<f32> {
+		self.rotation() * Vector3::new(0.0, 1.0, 0.0)
+	}
+	
+	/// Puts the camera in orbit mode around `target`.
+	/// 
+	/// The camera keeps looking at the target as it is rotated, and scrolling zooms in and out. The distance from
+	/// the target starts at the camera's current distance, clamped to the orbit distance limits.
+	pub fn set_orbit(&mut self, target: Vector3<f32>) {
+		let distance = (self.pos - target).norm();
+		self.orbit = Some((target, distance));
+		self.update_orbit();
+	}
+	
+	/// Takes the camera out of orbit mode. The camera stays where it is.
+	pub fn clear_orbit(&mut self) {
+		self.orbit = None;
+	}
+	
+	/// Gets the target of the camera, if it is in orbit mode.
+	pub fn orbit_target(&self) -> Option<Vector3<f32>> {
+		self.orbit.map(|(target, _)| target)
+	}
+	
+	/// Gets the distance of the camera from its target, if it is in orbit mode.
+	pub fn orbit_distance(&self) -> Option<f32> {
+		self.orbit.map(|(_, distance)| distance)
+	}
+	
+	/// Sets the minimum and maximum distance of the camera from its target in orbit mode.
+	/// (Default = 0.5 to 1000.0)
+	pub fn set_orbit_distance_limits(&mut self, min: f32, max: f32) {
+		if min <= 0.0 || max < min {
+			warn!("Ignoring invalid orbit distance limits: {} to {}", min, max);
+			return;
+		}
+		self.min_orbit_distance = min;
+		self.max_orbit_distance = max;
+		self.update_orbit();
+	}
+	
+	/// Zooms the camera in by `lines` scrolled, if it is in orbit mode. Negative values zoom out.
+	pub fn zoom(&mut self, lines: f32) {
+		if let Some((target, distance)) = self.orbit {
+			self.orbit = Some((target, distance * ORBIT_ZOOM_FACTOR.powf(lines)));
+			self.update_orbit();
+		}
+	}
+	
+	/// Clamps the orbit distance and moves the camera so that it is looking at its target.
+	fn update_orbit(&mut self) {
+		if let Some((target, distance)) = self.orbit {
+			let distance = na::clamp(distance, self.min_orbit_distance, self.max_orbit_distance);
+			self.orbit = Some((target, distance));
+			self.pos = target - self.forward() * distance;
+			self.view_mat = None;
+		}
+	}
+	
+	/// Starts shaking the camera, for example when there is a big collision.
+	/// 
+	/// `intensity` is the maximum distance that the camera is moved, in world units, and the shake decays to nothing
+	/// over `duration` seconds. Shakes are layered on top of the normal movement of the camera, and multiple shakes
+	/// add together. If too many shakes are active, the weakest is replaced.
+	pub fn add_shake(&mut self, intensity: f32, duration: f32) {
+		if intensity <= 0.0 || duration <= 0.0 {
+			return;
+		}
+		let shake = Shake { intensity, duration, remaining: duration };
+		let weakest = self.shakes.iter_mut()
+			.min_by(|a, b| a.strength().partial_cmp(&b.strength()).unwrap_or(::std::cmp::Ordering::Equal));
+		if let Some(weakest) = weakest {
+			if weakest.strength() < shake.strength() {
+				*weakest = shake;
+			}
+		}
+	}
+	
+	/// Returns true if the camera is currently shaking.
+	pub fn is_shaking(&self) -> bool {
+		self.shakes.iter().any(|s| s.strength() > 0.0)
+	}
+	
+	/// Decays the active camera shakes by `dt` seconds, and picks a new random offset for the camera.
+	/// This should be called once per frame.
+	pub fn update_shake(&mut self, dt: f32) {
+		let was_shaking = self.is_shaking();
+		for shake in self.shakes.iter_mut() {
+			shake.remaining -= dt;
+		}
+		let strength: f32 = self.shakes.iter().map(|s| s.strength()).sum();
+		
+		if strength > 0.0 {
+			let mut rng = rand::thread_rng();
+			let mut r = || rng.gen_range(-1.0, 1.0);
+			self.shake_offset = Vector3::new(r(), r(), r()) * strength;
+			self.shake_rot = Vector2::new(r(), r()) * (strength * SHAKE_ROTATION);
+			self.view_mat = None;
+		} else if was_shaking {
+			self.shake_offset = Vector3::zero();
+			self.shake_rot = Vector2::zero();
+			self.view_mat = None;
+		}
+	}
+	
 	/// Get the view matrix of the camera.
 	pub fn view_matrix(&mut self) -> Matrix4<f32> {
-		let mat = if let Some(view_mat) = self.view_mat {
-			view_mat
-		} else {
-			let pos = util::mat4_translation(-self.pos);
-			let rot_y = Rotation3::from_euler_angles(-self.yrot, 0.0, 0.0).to_homogeneous();
-			let rot_x = Rotation3::from_euler_angles(0.0, -self.xrot, 0.0).to_homogeneous();
-			rot_y * rot_x * pos
-		};
+		let mat = self.view_mat.unwrap_or_else(|| self.calc_view_matrix());
 		self.view_mat = Some(mat);
 		mat
 	}
 	
+	/// Calculates the view matrix of the camera, without using the cached matrix.
+	fn calc_view_matrix(&self) -> Matrix4<f32> {
+		let pos = util::mat4_translation(-(self.pos + self.shake_offset));
+		let rot_y = Rotation3::from_euler_angles(-(self.yrot + self.shake_rot.y), 0.0, 0.0).to_homogeneous();
+		let rot_x = Rotation3::from_euler_angles(0.0, -(self.xrot + self.shake_rot.x), 0.0).to_homogeneous();
+		rot_y * rot_x * pos
+	}
+	
+	/// Gets a ray going from the camera through a point on the screen, as `(origin, direction)`.
+	/// 
+	/// The origin is on the near clip plane.
+	/// 
+	/// - `projection` is the projection matrix used to render the scene. See `Render::projection`.
+	/// - `screen_pos` is the point on the screen in pixels, relative to the top-left of the screen.
+	/// - `screen_size` is the size of the screen in pixels.
+	/// 
+	/// The direction is normalized.
+	pub fn ray_from_screen(&self, projection: &Matrix4<f32>, screen_pos: Vector2<f32>, screen_size: Vector2<f32>) -> (Vector3<f32>, Vector3<f32>) {
+		// Convert to normalized device coordinates
+		let x = screen_pos.x / screen_size.x * 2.0 - 1.0;
+		let y = 1.0 - screen_pos.y / screen_size.y * 2.0;
+		
+		let inv = (projection * self.view_mat.unwrap_or_else(|| self.calc_view_matrix()))
+			.try_inverse()
+			.unwrap_or(Matrix4::one());
+		let unproject = |z: f32| {
+			let p = inv * Vector4::new(x, y, z, 1.0);
+			Vector3::new(p.x, p.y, p.z) / p.w
+		};
+		let near = unproject(-1.0);
+		let far = unproject(1.0);
+		let dir = far - near;
+		let len = dir.norm();
+		if len == 0.0 {
+			(self.pos, self.forward())
+		} else {
+			(near, dir / len)
+		}
+	}
+	
 	/// Translate the camera by a specified amount, taking into account the rotation.
+	/// 
+	/// In orbit mode, this moves the target instead.
 	pub fn translate(&mut self, v: Vector3<f32>) {
 		let rot = UnitQuaternion::new(Vector3::new(0.0, self.xrot, 0.0));
-		self.pos = self.pos + rot * v;
-		self.view_mat = None;
+		if let Some((target, distance)) = self.orbit {
+			self.orbit = Some((target + rot * v, distance));
+			self.update_orbit();
+		} else {
+			self.pos = self.pos + rot * v;
+			self.view_mat = None;
+		}
 	}
 	
-	/// Handle a mouse move on the screen by rotating the camera.
+	/// Handle a mouse move on the screen by rotating the camera, with the default sensitivity.
 	pub fn mouse_moved(&mut self, moved: Vector2<f64>) {
-		let rot = Vector2::new(moved.x as f32, moved.y as f32) * -0.008;
+		self.mouse_moved_with(moved, 1.0, false);
+	}
+	
+	/// Handle a mouse move on the screen by rotating the camera.
+	/// 
+	/// `sensitivity` scales how far the camera rotates, and if `invert_y` is true moving the mouse up looks down.
+	pub fn mouse_moved_with(&mut self, moved: Vector2<f64>, sensitivity: f32, invert_y: bool) {
+		let y_sign = if invert_y { -1.0 } else { 1.0 };
+		let rot = Vector2::new(moved.x as f32, moved.y as f32 * y_sign) * (-MOUSE_RADIANS_PER_PIXEL * sensitivity);
 		if moved.x != 0.0 && moved.y != 0.0 {
 			trace!("mouse moved: {:3},{:3} look change: {:1},{:1}", rot.x, rot.y, -moved.x, -moved.y);
 		}
 		self.look(rot);
 	}
 	
+	/// Rotates the camera so that it is looking at `target`.
+	/// 
+	/// The pitch is limited to straight up or down, like with `look`. In orbit mode, `target` becomes the orbit
+	/// target, and the camera stays where it is.
+	pub fn look_at(&mut self, target: Vector3<f32>) {
+		const PI: f32 = ::std::f32::consts::PI;
+		let dir = target - self.pos;
+		let len = dir.norm();
+		if len == 0.0 {
+			return;
+		}
+		let dir = dir / len;
+		
+		// Inverse of `forward`
+		self.yrot = na::clamp(dir.y, -1.0, 1.0).asin();
+		self.xrot = (-dir.x).atan2(-dir.z);
+		if self.xrot < 0.0 {
+			self.xrot += PI * 2.;
+		}
+		self.yrot = na::clamp(self.yrot, PI / -2., PI / 2.);
+		
+		self.view_mat = None;
+		if self.orbit.is_some() {
+			self.orbit = Some((target, len));
+			self.update_orbit();
+		}
+	}
+	
 	/// Apply a rotation in the x and y direction (in radians)
 	pub fn look(&mut self, rot: Vector2<f32>) {
 		const PI: f32 = ::std::f32::consts::PI;
@@ -71,5 +319,6 @@ impl Camera {
 		self.yrot = na::clamp(self.yrot, PI / -2., PI / 2.);
 		
 		self.view_mat = None;
+		self.update_orbit();
 	}
 }
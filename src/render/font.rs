@@ -1,12 +1,11 @@
 use prelude::*;
 use std::rc::Rc;
 use std::borrow::Cow;
-use std::process::exit;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use glium::{Blend, BlendingFunction, LinearBlendingFactor, Texture2d, Program, Surface, VertexBuffer, IndexBuffer, DrawParameters, BackfaceCullingMode};
 use glium::Rect as GlRect;
-use glium::index::PrimitiveType;
+use glium::index::{NoIndices, PrimitiveType};
 use glium::texture::{RawImage2d, ClientFormat, MipmapsOption};
 use rusttype::{Font, PositionedGlyph, GlyphId, IntoGlyphId, Rect, Scale, VMetrics, point};
 use rusttype::gpu_cache::{Cache, CacheWriteErr};
@@ -55,6 +54,12 @@ struct FormatState {
 	
 	pub x: f32,
 	pub y: f32,
+	/// The furthest right that any glyph has been laid out to
+	pub max_x: f32,
+	/// The index of the current line
+	line: usize,
+	/// The widths of the lines that have been finished by a newline
+	line_widths: Vec<f32>,
 	scale: Scale,
 	
 	v_metrics: VMetrics,
@@ -69,6 +74,9 @@ impl FormatState {
 			
 			x: x,
 			y: y,
+			max_x: x,
+			line: 0,
+			line_widths: Vec::new(),
 			scale: scale,
 			
 			v_metrics: v_metrics,
@@ -77,6 +85,8 @@ impl FormatState {
 	
 	/// Processes a newline
 	pub fn newline(&mut self) {
+		self.line_widths.push(self.x - self.init_x);
+		self.line += 1;
 		self.x = self.init_x;
 		self.y = self.y + self.v_metrics.descent + self.v_metrics.ascent + self.v_metrics.line_gap;
 	}
@@ -85,13 +95,105 @@ impl FormatState {
 	/// 
 	/// Handles newlines (`'\n'`, `'\r'`, `'\r\n'`) properly. Doesn't perform wrapping
 	pub fn layout_text<'a, 'f>(&'a mut self, ignorable_db: &'a IgnorableDatabase, font: &Font<'f>, text: &str, glyphs: &mut Vec<(char, PositionedGlyph<'f>)>) {
+		self.layout_text_aligned(ignorable_db, font, text, TextAlign::Left, glyphs);
+	}
+	
+	/// Lays out a string like `layout_text`, aligning each line relative to the initial x position.
+	pub fn layout_text_aligned<'a, 'f>(&'a mut self, ignorable_db: &'a IgnorableDatabase, font: &Font<'f>, text: &str, align: TextAlign, glyphs: &mut Vec<(char, PositionedGlyph<'f>)>) {
+		let start = glyphs.len();
+		let mut glyph_lines = Vec::new();
 		let mut cprev = None;
 		for c in normalize_line_endings(text.chars().nfc()) {
 			if let Some(glyph) = self.layout_char(ignorable_db, font, cprev, c) {
 				glyphs.push((c, glyph));
+				glyph_lines.push(self.line);
 				cprev = Some(c);
 			}
 		}
+		if align == TextAlign::Left {
+			return;
+		}
+		
+		let mut line_widths = self.line_widths.clone();
+		line_widths.push(self.x - self.init_x);
+		for (&mut (_, ref mut glyph), line) in glyphs[start..].iter_mut().zip(glyph_lines.into_iter()) {
+			let w = line_widths[line];
+			let offset = match align {
+				TextAlign::Left   => 0.0,
+				TextAlign::Center => -w / 2.0,
+				TextAlign::Right  => -w,
+			};
+			let pos = glyph.position();
+			glyph.set_position(point(pos.x + offset, pos.y));
+		}
+	}
+	
+	/// Lays out a string like `layout_text`, but wraps lines so that they are no wider than `max_width`.
+	/// 
+	/// Lines are broken on whitespace where possible, and words that are too long to fit on a line on their own are
+	/// broken at the last character that fits.
+	pub fn layout_text_wrapped<'a, 'f>(&'a mut self, ignorable_db: &'a IgnorableDatabase, font: &Font<'f>, text: &str, max_width: f32, glyphs: &mut Vec<(char, PositionedGlyph<'f>)>) {
+		let chars: Vec<char> = normalize_line_endings(text.chars().nfc()).collect();
+		let mut cprev = None;
+		let mut i = 0;
+		while i < chars.len() {
+			let c = chars[i];
+			if c.is_whitespace() {
+				if c != '\n' && c != '\r' && self.x - self.init_x + self.char_advance(ignorable_db, font, cprev, c) > max_width {
+					// Break the line here, instead of laying out the whitespace
+					self.newline();
+					cprev = None;
+				} else if let Some(glyph) = self.layout_char(ignorable_db, font, cprev, c) {
+					glyphs.push((c, glyph));
+					cprev = Some(c);
+				}
+				i += 1;
+				continue;
+			}
+			
+			let end = chars[i..].iter().position(|c| c.is_whitespace()).map_or(chars.len(), |n| i + n);
+			let word = &chars[i..end];
+			if self.x > self.init_x && self.x - self.init_x + self.word_advance(ignorable_db, font, cprev, word) > max_width {
+				self.newline();
+				cprev = None;
+			}
+			for &c in word {
+				// Hard-break words that are too long to fit on a line
+				if self.x > self.init_x && self.x - self.init_x + self.char_advance(ignorable_db, font, cprev, c) > max_width {
+					self.newline();
+					cprev = None;
+				}
+				if let Some(glyph) = self.layout_char(ignorable_db, font, cprev, c) {
+					glyphs.push((c, glyph));
+					cprev = Some(c);
+				}
+			}
+			i = end;
+		}
+	}
+	
+	/// Gets how far the current position would move if `c` was laid out after `cprev`, including kerning.
+	fn char_advance<'f>(&self, ignorable_db: &IgnorableDatabase, font: &Font<'f>, cprev: Option<char>, c: char) -> f32 {
+		if ignorable_db.is_char_default_ignorable(c) {
+			return 0.0;
+		}
+		let advance = font.glyph(c.into_glyph_id(font)).scaled(self.scale).h_metrics().advance_width;
+		match cprev {
+			Some(cprev) => advance + font.pair_kerning(self.scale, cprev, c),
+			None => advance,
+		}
+	}
+	
+	/// Gets how far the current position would move if `word` was laid out after `cprev`.
+	fn word_advance<'f>(&self, ignorable_db: &IgnorableDatabase, font: &Font<'f>, mut cprev: Option<char>, word: &[char]) -> f32 {
+		let mut advance = 0.0;
+		for &c in word {
+			advance += self.char_advance(ignorable_db, font, cprev, c);
+			if !ignorable_db.is_char_default_ignorable(c) {
+				cprev = Some(c);
+			}
+		}
+		advance
 	}
 	
 	/// Lays out a char at the current posiion, and updates the current position.
@@ -159,6 +261,7 @@ impl FormatState {
 		
 		let positioned = scaled.positioned(point(self.x, self.y));
 		self.x += advance;
+		self.max_x = self.max_x.max(self.x);
 		Ok(Some(positioned))
 	}
 }
@@ -255,17 +358,87 @@ impl IgnorableDatabase {
 	}
 }
 
+/// Where the origin of the screen coordinates given to the text drawing functions is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextOrigin {
+	/// `(0, 0)` is the top-left of the screen, and y increases downwards.
+	TopLeft,
+	/// `(0, 0)` is the bottom-left of the screen, and y increases upwards. This is OpenGL's convention.
+	BottomLeft,
+}
+
+/// How each line of text is aligned horizontally relative to the x coordinate it is drawn at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextAlign {
+	/// The lines start at x.
+	Left,
+	/// The lines are centred on x.
+	Center,
+	/// The lines end at x.
+	Right,
+}
+
+/// Identifies a font that has been loaded by a `FontRender`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+/// The font that is loaded when a `FontRender` is constructed.
+pub const DEFAULT_FONT: FontId = FontId(0);
+
+/// The name of the file in the `fonts/` folder that `DEFAULT_FONT` is loaded from.
+const DEFAULT_FONT_NAME: &str = "consolas.ttf";
+
+/// A font, and the glyph cache and texture that its glyphs are rendered to.
+/// 
+/// Each font needs its own cache, as the cache only identifies glyphs within a single font.
+struct LoadedFont {
+	font: Font<'static>,
+	cache: Cache<'static>,
+	tex: Texture2d,
+}
+impl LoadedFont {
+	/// Loads the font `name` from the filesystem.
+	fn try_load(ctx: &Rc<Context>, name: &str) -> Result<LoadedFont, String> {
+		let font = vfs::try_load_font(name, 0)?;
+		
+		let img = RawImage2d {
+			data  : Cow::Borrowed(&EMPTY_TEXTURE_DATA as &[u8]),
+			width : SIZE,
+			height: SIZE,
+			format: ClientFormat::U8,
+		};
+		
+		let tex = Texture2d::with_mipmaps(ctx, img, MipmapsOption::NoMipmap)
+			.map_err(|e| format!("Could not create texture: {:?}", e))?;
+		
+		let cache = Cache::builder()
+			.dimensions(SIZE, SIZE)
+			.pad_glyphs(true)
+			.multithread(false)
+			.build();
+		
+		Ok(LoadedFont {
+			font,
+			cache,
+			tex,
+		})
+	}
+}
+
 /// Font rendering handler.
 pub struct FontRender {
 	ctx: Rc<Context>,
-	cache: Cache<'static>,
 	
-	font: Font<'static>,
+	fonts: Vec<LoadedFont>,
+	font_ids: HashMap<String, FontId>,
 	
 	ignorable_db: IgnorableDatabase,
 	
-	font_tex: Texture2d,
 	shader: Program,
+	panel_shader: Program,
+	origin: TextOrigin,
+	/// The number of draw calls issued since the last call to `take_draw_calls`
+	draw_calls: u32,
 }
 impl FontRender {
 	/// Constructs a new font renderer with an OpenGL context.
@@ -275,52 +448,167 @@ impl FontRender {
 		let ignorable_db = IgnorableDatabase::load();
 		
 		let shader = vfs::load_shader(&ctx, "font");
+		let panel_shader = vfs::load_shader(&ctx, "panel");
 		
-		let font = vfs::load_font("consolas.ttf", 0);
-		
-		let img = RawImage2d {
-			data  : Cow::Borrowed(&EMPTY_TEXTURE_DATA as &[u8]),
-			width : SIZE,
-			height: SIZE,
-			format: ClientFormat::U8,
-		};
-		
-		let font_tex = match Texture2d::with_mipmaps(&ctx, img, MipmapsOption::NoMipmap) {
-			Ok(t) => t,
-			Err(e) => {
-				error!("Could not create texture: {:?}", e);
-				exit(1);
-			},
-		};
-		
-		let cache = Cache::builder()
-			.dimensions(SIZE, SIZE)
-			.pad_glyphs(true)
-			.multithread(false)
-			.build();
+		let default_font = util::exit_on_err(LoadedFont::try_load(&ctx, DEFAULT_FONT_NAME));
+		let fonts = vec![default_font];
+		let mut font_ids = HashMap::new();
+		font_ids.insert(DEFAULT_FONT_NAME.into(), DEFAULT_FONT);
 		
 		FontRender {
 			ctx,
-			cache,
 			
-			font,
+			fonts,
+			font_ids,
 			
 			ignorable_db,
 			
-			font_tex,
 			shader,
+			panel_shader,
+			origin: TextOrigin::TopLeft,
+			draw_calls: 0,
+		}
+	}
+	
+	/// Loads the font `name` from the `fonts/` folder, returning the id to draw text with it.
+	/// 
+	/// If the font has already been loaded, the id of the loaded font is returned.
+	/// 
+	/// Exits if the font could not be loaded.
+	pub fn load_font(&mut self, name: &str) -> FontId {
+		util::exit_on_err(self.try_load_font(name))
+	}
+	
+	/// Like `load_font`, but returns an `Err` if the font could not be loaded.
+	pub fn try_load_font(&mut self, name: &str) -> Result<FontId, String> {
+		if let Some(&id) = self.font_ids.get(name) {
+			return Ok(id);
+		}
+		let id = FontId(self.fonts.len());
+		self.fonts.push(LoadedFont::try_load(&self.ctx, name)?);
+		self.font_ids.insert(name.into(), id);
+		Ok(id)
+	}
+	
+	/// Gets the origin of the coordinates passed to `draw_str`.
+	pub fn origin(&self) -> TextOrigin {
+		self.origin
+	}
+	
+	/// Sets the origin of the coordinates passed to `draw_str`. (Default = `TextOrigin::TopLeft`).
+	pub fn set_origin(&mut self, origin: TextOrigin) {
+		self.origin = origin;
+	}
+	
+	/// Returns the number of draw calls issued since this was last called, and resets the count.
+	pub fn take_draw_calls(&mut self) -> u32 {
+		let n = self.draw_calls;
+		self.draw_calls = 0;
+		n
+	}
+	
+	/// Measures the size of a string drawn with `draw_str` in `font` at pt size `scale`, as `(width, height)` in pixels.
+	pub fn measure_str(&self, font: FontId, s: &str, scale: f32) -> (f32, f32) {
+		let font = &self.fonts[font.0].font;
+		let mut state = FormatState::new(0.0, 0.0, scale, font);
+		let mut glyphs = Vec::new();
+		state.layout_text(&self.ignorable_db, font, s, &mut glyphs);
+		(state.max_x, state.y - state.v_metrics.descent)
+	}
+	
+	/// Draws a string like `draw_str`, on top of a filled rectangle in `bg_color`.
+	/// 
+	/// The rectangle is the size of the text, with `padding` pixels added on each side. A translucent `bg_color` is
+	/// alpha blended over what has already been drawn.
+	pub fn draw_str_panel<S: Surface>(&mut self, surface: &mut S, font: FontId, s: &str, x: f32, y: f32, screen_w: f32, screen_h: f32, scale: f32, text_color: Color, bg_color: Color, padding: f32) {
+		let (w, h) = self.measure_str(font, s, scale);
+		let top = match self.origin {
+			TextOrigin::TopLeft    => y,
+			TextOrigin::BottomLeft => -y,
+		};
+		let (x0, y0) = (x - padding, top - padding);
+		let (x1, y1) = (x + w + padding, top + h + padding);
+		let vs = [
+			FontVertex::new([x0, y0], [0.0, 0.0]),
+			FontVertex::new([x1, y0], [0.0, 0.0]),
+			FontVertex::new([x0, y1], [0.0, 0.0]),
+			FontVertex::new([x1, y1], [0.0, 0.0]),
+		];
+		match VertexBuffer::immutable(&self.ctx, &vs) {
+			Ok(vs) => {
+				let mat = screen_matrix((screen_w, screen_h), self.origin);
+				self.draw_calls += 1;
+				surface.draw(
+					&vs,
+					NoIndices(PrimitiveType::TriangleStrip),
+					&self.panel_shader,
+					&uniform!{
+						color: bg_color.into_rgba_array(),
+						mat  : *mat.as_ref(),
+					},
+					&DrawParameters {
+						blend: Blend {
+							color: BlendingFunction::Addition {
+								source:      LinearBlendingFactor::SourceAlpha,
+								destination: LinearBlendingFactor::OneMinusSourceAlpha,
+							},
+							alpha: BlendingFunction::Addition {
+								source:      LinearBlendingFactor::SourceAlpha,
+								destination: LinearBlendingFactor::OneMinusSourceAlpha,
+							},
+							..Default::default()
+						},
+						..Default::default()
+					}
+				).map_err(|e| error!("Draw failed: {:?}", e)).ok();
+			},
+			Err(e) => error!("Could not create vertex buffer: {:?}", e),
 		}
+		
+		self.draw_str(surface, font, s, x, y, screen_w, screen_h, scale, text_color);
+	}
+	
+	/// Draw a string in `font` at x, y on the screen scaled by scale.
+	/// 
+	/// `x` and `y` are relative to the current origin, and specify the top-left corner of the text.
+	pub fn draw_str<S: Surface>(&mut self, surface: &mut S, font: FontId, s: &str, x: f32, y: f32, screen_w: f32, screen_h: f32, scale: f32, color: Color) {
+		self.draw_str_aligned(surface, font, s, x, y, screen_w, screen_h, scale, color, TextAlign::Left);
 	}
 	
-	/// Draw a string at x, y on the screen scaled by scale.
-	pub fn draw_str<S: Surface>(&mut self, surface: &mut S, s: &str, x: f32, y: f32, screen_w: f32, screen_h: f32, scale: f32, color: Color) {
+	/// Draw a string in `font` at x, y on the screen scaled by scale, with each line aligned horizontally by `align`.
+	/// 
+	/// `x` and `y` are relative to the current origin. `y` specifies the top of the text.
+	pub fn draw_str_aligned<S: Surface>(&mut self, surface: &mut S, font: FontId, s: &str, x: f32, y: f32, screen_w: f32, screen_h: f32, scale: f32, color: Color, align: TextAlign) {
 		//println!("Rendering string: {}", s);
-		let mut state = FormatState::new(x, y, scale, &self.font);
+		// When the origin is at the bottom, lay the text out going down from -y, and let the matrix move it up.
+		let y = match self.origin {
+			TextOrigin::TopLeft    => y,
+			TextOrigin::BottomLeft => -y,
+		};
+		let font = &mut self.fonts[font.0];
+		let mut state = FormatState::new(x, y, scale, &font.font);
+		let mut glyphs = Vec::new();
+		state.layout_text_aligned(&self.ignorable_db, &font.font, s, align, &mut glyphs);
+		
+		let size = (screen_w, screen_h);
+		draw_glyphs(&self.ctx, surface, &self.shader, &mut font.tex, &mut font.cache, &mut self.draw_calls, size, self.origin, &glyphs, color);
+	}
+	
+	/// Draw a string in `font` at x, y on the screen scaled by scale, wrapping lines so that they are no wider than `max_width`.
+	/// 
+	/// `x` and `y` are relative to the current origin, and specify the top-left corner of the text.
+	pub fn draw_str_wrapped<S: Surface>(&mut self, surface: &mut S, font: FontId, s: &str, x: f32, y: f32, max_width: f32, screen_w: f32, screen_h: f32, scale: f32, color: Color) {
+		let y = match self.origin {
+			TextOrigin::TopLeft    => y,
+			TextOrigin::BottomLeft => -y,
+		};
+		let font = &mut self.fonts[font.0];
+		let mut state = FormatState::new(x, y, scale, &font.font);
 		let mut glyphs = Vec::new();
-		state.layout_text(&self.ignorable_db, &self.font, s, &mut glyphs);
+		state.layout_text_wrapped(&self.ignorable_db, &font.font, s, max_width, &mut glyphs);
 		
 		let size = (screen_w, screen_h);
-		draw_glyphs(&self.ctx, surface, &self.shader, &mut self.font_tex, &mut self.cache, size, &glyphs, color);
+		draw_glyphs(&self.ctx, surface, &self.shader, &mut font.tex, &mut font.cache, &mut self.draw_calls, size, self.origin, &glyphs, color);
 	}
 }
 
@@ -386,21 +674,37 @@ fn draw_glyph<'a>(cache: &mut Cache<'a>, glyph: &PositionedGlyph<'a>, vs: &mut V
 	}
 }
 
-/// Draws the glyphs at a specified point on `surface`.
+/// Calculates the matrix that transforms laid out text coordinates into normalized device coordinates.
 /// 
-/// Properly calculates matrix.
-fn draw_glyphs<'a, S: Surface>(ctx: &Rc<Context>, surface: &mut S, shader: &Program, font_tex: &mut Texture2d, cache: &mut Cache<'a>, size: (f32, f32), glyphs: &[(char, PositionedGlyph<'a>)], color: Color) {
-	// Calculate matrix
+/// The coordinates have y increasing downwards, and are relative to the bottom of the screen if `origin` is
+/// `TextOrigin::BottomLeft`.
+fn screen_matrix(size: (f32, f32), origin: TextOrigin) -> Matrix4<f32> {
 	let (w, h) = size;
 	let mut mat = Matrix4::one();
 	mat = mat * util::mat4_scale(Vector3::new(1.0, -1.0, 1.0));
 	mat = mat * util::mat4_translation(Vector3::new(-1.0, -1.0, 0.0));
 	mat = mat * util::mat4_scale(Vector3::new(2.0 / w, 2.0 / h, 1.0));
-	draw_glyphs_mat(ctx, surface, shader, font_tex, cache, mat, glyphs, color)
+	if origin == TextOrigin::BottomLeft {
+		mat = mat * util::mat4_translation(Vector3::new(0.0, h, 0.0));
+	}
+	mat
+}
+
+/// Draws the glyphs at a specified point on `surface`.
+/// 
+/// Properly calculates matrix. The glyphs are always laid out with y increasing downwards, so if `origin` is
+/// `TextOrigin::BottomLeft` they are expected to be laid out relative to the bottom of the screen.
+/// 
+/// `draw_calls` is incremented for every draw call issued.
+fn draw_glyphs<'a, S: Surface>(ctx: &Rc<Context>, surface: &mut S, shader: &Program, font_tex: &mut Texture2d, cache: &mut Cache<'a>, draw_calls: &mut u32, size: (f32, f32), origin: TextOrigin, glyphs: &[(char, PositionedGlyph<'a>)], color: Color) {
+	let mat = screen_matrix(size, origin);
+	draw_glyphs_mat(ctx, surface, shader, font_tex, cache, draw_calls, mat, glyphs, color)
 }
 
 /// Transforms the glyphs by `mat` and then draws the glyphs on `surface`.
-fn draw_glyphs_mat<'a, S: Surface>(ctx: &Rc<Context>, surface: &mut S, shader: &Program, font_tex: &mut Texture2d, cache: &mut Cache<'a>, mat: Matrix4<f32>, glyphs: &[(char, PositionedGlyph<'a>)], color: Color) {
+/// 
+/// `draw_calls` is incremented for every draw call issued.
+fn draw_glyphs_mat<'a, S: Surface>(ctx: &Rc<Context>, surface: &mut S, shader: &Program, font_tex: &mut Texture2d, cache: &mut Cache<'a>, draw_calls: &mut u32, mat: Matrix4<f32>, glyphs: &[(char, PositionedGlyph<'a>)], color: Color) {
 	match cache_glyphs(font_tex, cache, glyphs) {
 		Ok(()) => {
 			let mut vs = Vec::new();
@@ -426,13 +730,14 @@ fn draw_glyphs_mat<'a, S: Surface>(ctx: &Rc<Context>, surface: &mut S, shader: &
 				},
 			};
 			// Draw buffer
+			*draw_calls += 1;
 			surface.draw(
 				&vs,
 				&is,
 				&shader,
 				&uniform!{
 					tex  : &*font_tex,
-					color: color.into_array(),
+					color: color.into_rgba_array(),
 					mat  : *mat.as_ref(),
 				},
 				&DrawParameters {
@@ -441,7 +746,10 @@ fn draw_glyphs_mat<'a, S: Surface>(ctx: &Rc<Context>, surface: &mut S, shader: &
 							source:      LinearBlendingFactor::SourceAlpha,
 							destination: LinearBlendingFactor::OneMinusSourceAlpha,
 						},
-						alpha: BlendingFunction::Max,
+						alpha: BlendingFunction::Addition {
+							source:      LinearBlendingFactor::SourceAlpha,
+							destination: LinearBlendingFactor::OneMinusSourceAlpha,
+						},
 						..Default::default()
 					},
 					backface_culling: BackfaceCullingMode::CullClockwise,
@@ -460,8 +768,8 @@ fn draw_glyphs_mat<'a, S: Surface>(ctx: &Rc<Context>, surface: &mut S, shader: &
 				warn!("Cannot render all glyphs in array (len {}): {:?}, splitting at {}", glyphs.len(), e, glyphs.len() / 2);
 				// Split glyphs up into two halves, and draw them seperately.
 				let (a, b) = glyphs.split_at(glyphs.len() / 2);
-				draw_glyphs_mat(ctx, surface, shader, font_tex, cache, mat, a, color);
-				draw_glyphs_mat(ctx, surface, shader, font_tex, cache, mat, b, color);
+				draw_glyphs_mat(ctx, surface, shader, font_tex, cache, draw_calls, mat, a, color);
+				draw_glyphs_mat(ctx, surface, shader, font_tex, cache, draw_calls, mat, b, color);
 			}
 		}
 	}
@@ -1,12 +1,12 @@
 use prelude::*;
 use std::rc::Rc;
-use std::process::exit;
-use std::mem;
 
 use glium::index;
 use glium::{Texture2d, IndexBuffer, VertexBuffer};
+use glium::uniforms::SamplerWrapFunction;
 
 use render::{RenderableMesh, Material, Render};
+use super::obj::parse_obj;
 use util;
 
 #[derive(Copy, Clone, Debug)]
@@ -20,11 +20,26 @@ implement_vertex!(LitVertex, pos, normal, uv);
 impl LitVertex {
 	pub fn new(pos: Vector3<f32>, normal: Vector3<f32>, uv: Vector2<f32>) -> LitVertex {
 		LitVertex {
-			pos   : unsafe { mem::transmute(pos) },
-			normal: unsafe { mem::transmute(normal) },
-			uv    : unsafe { mem::transmute(uv) },
+			pos   : [pos.x, pos.y, pos.z],
+			normal: [normal.x, normal.y, normal.z],
+			uv    : [uv.x, uv.y],
 		}
 	}
+	
+	/// Gets the position of the vertex.
+	pub fn pos(&self) -> Vector3<f32> {
+		Vector3::new(self.pos[0], self.pos[1], self.pos[2])
+	}
+	
+	/// Gets the normal of the vertex.
+	pub fn normal(&self) -> Vector3<f32> {
+		Vector3::new(self.normal[0], self.normal[1], self.normal[2])
+	}
+	
+	/// Sets the normal of the vertex.
+	pub fn set_normal(&mut self, normal: Vector3<f32>) {
+		self.normal = [normal.x, normal.y, normal.z];
+	}
 }
 
 /// A LitMesh is a textured mesh that is affected by lighting.
@@ -35,24 +50,34 @@ pub struct LitMesh {
 	index_buffer : IndexBuffer<u16>,
 	/// The texture that will be used to texture the object.
 	texture      : Rc<Texture2d>,
+	/// How the texture is sampled outside of the 0 to 1 range of uvs.
+	wrap         : SamplerWrapFunction,
 	/// The material that the object has.
 	material     : Material,
 }
 impl RenderableMesh for LitMesh {
-	fn render(&self, r: &mut Render, model: Matrix4<f32>) {
-		r.render_lit(&self.vertex_buffer, &self.index_buffer, model, &*self.texture, &self.material);
+	fn render(&self, r: &mut Render, model: Matrix4<f32>, wireframe: bool) {
+		r.render_lit(&self.vertex_buffer, &self.index_buffer, model, &*self.texture, self.wrap, &self.material, wireframe);
 	}
 }
 impl LitMesh {
 	/// Generates a new sphere with a specified detail, texture and material.
 	/// 
-	/// At the moment the uvs of the mesh outputted are all set to 0.0,0.0.
+	/// The sphere has a radius of 1.0. The uvs are a spherical mapping: `u` goes from 0 to 1 around the sphere
+	/// anti-clockwise (looking down), starting and ending at -x, and `v` goes from 0 at the top to 1 at the bottom.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
 	pub fn sphere(ctx: &Rc<Context>, detail: u32, texture: Rc<Texture2d>, material: Material) -> LitMesh {
+		util::exit_on_err(LitMesh::try_sphere(ctx, detail, texture, material))
+	}
+	
+	/// Like `sphere`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_sphere(ctx: &Rc<Context>, detail: u32, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
 		let mut vs: Vec<LitVertex> = Vec::new();
 		let mut is: Vec<u16> = Vec::new();
 		
 		LitMesh::gen_sphere(&mut vs, &mut is, detail);
-		LitMesh::from_vecs(ctx, vs, is, texture, material)
+		LitMesh::from_data(ctx, &vs, &is, texture, material)
 	}
 	
 	/// Generates a cuboid with the specified half extents, texture and material.
@@ -66,44 +91,311 @@ impl LitMesh {
 	///   | L | D | R | // Left, Down, Right
 	/// 1 +---+---+---+
 	/// ```
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
 	pub fn cuboid(ctx: &Rc<Context>, half_extents: Vector3<f32>, texture: Rc<Texture2d>, material: Material) -> LitMesh {
+		util::exit_on_err(LitMesh::try_cuboid(ctx, half_extents, texture, material))
+	}
+	
+	/// Like `cuboid`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_cuboid(ctx: &Rc<Context>, half_extents: Vector3<f32>, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
 		let mut vs: Vec<LitVertex> = Vec::new();
 		let mut is: Vec<u16> = Vec::new();
 		
 		LitMesh::gen_cuboid(&mut vs, &mut is, half_extents);
-		LitMesh::from_vecs(ctx, vs, is, texture, material)
-	}
-	
-	fn from_vecs(ctx: &Rc<Context>, vs: Vec<LitVertex>, is: Vec<u16>, texture: Rc<Texture2d>, material: Material) -> LitMesh {
-		let vs = match VertexBuffer::immutable(ctx, &vs) {
-			Ok(vs) => vs,
-			Err(e) => {
-				error!("Could not create vertex buffer: {:?}", e);
-				exit(1);
-			},
-		};
-		let is = match IndexBuffer ::immutable(ctx, index::PrimitiveType::TrianglesList, &is) {
-			Ok(is) => is,
-			Err(e) => {
-				error!("Could not create index buffer: {:?}", e);
-				exit(1);
-			},
-		};
-		
-		LitMesh {
+		LitMesh::from_data(ctx, &vs, &is, texture, material)
+	}
+	
+	/// Generates a capped cylinder, centred on the origin and aligned to the Y axis, with a texture and material.
+	/// 
+	/// `segments` is the number of radial subdivisions, and must be at least 3.
+	/// This matches the ncollide shape `Cylinder::new(height / 2.0, radius)`.
+	/// 
+	/// The side is wrapped with the whole texture, with `u` going around the cylinder and `v` going from the top to
+	/// the bottom. Each cap is mapped to the circle inscribed in the texture.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
+	pub fn cylinder(ctx: &Rc<Context>, radius: f32, height: f32, segments: u32, texture: Rc<Texture2d>, material: Material) -> LitMesh {
+		util::exit_on_err(LitMesh::try_cylinder(ctx, radius, height, segments, texture, material))
+	}
+	
+	/// Like `cylinder`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_cylinder(ctx: &Rc<Context>, radius: f32, height: f32, segments: u32, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
+		let mut vs: Vec<LitVertex> = Vec::new();
+		let mut is: Vec<u16> = Vec::new();
+		
+		LitMesh::gen_cylinder(&mut vs, &mut is, radius, height, segments);
+		LitMesh::from_data(ctx, &vs, &is, texture, material)
+	}
+	
+	/// Generates a capsule, centred on the origin and aligned to the Y axis, with a texture and material.
+	/// 
+	/// The capsule is two hemispheres of radius `radius` joined by a cylinder of height `cylinder_height`.
+	/// `segments` is the number of radial subdivisions, and `rings` is the number of subdivisions from the pole to
+	/// the equator of each hemisphere. The normals are continuous where the hemispheres meet the cylinder.
+	/// 
+	/// This matches the ncollide shape `Capsule::new(cylinder_height / 2.0, radius)`, which can be passed to
+	/// `Component::new` so that the collision matches the mesh.
+	/// 
+	/// The texture is wrapped around the capsule, with `u` going around it and `v` going from the top to the bottom.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
+	pub fn capsule(ctx: &Rc<Context>, radius: f32, cylinder_height: f32, segments: u32, rings: u32, texture: Rc<Texture2d>, material: Material) -> LitMesh {
+		util::exit_on_err(LitMesh::try_capsule(ctx, radius, cylinder_height, segments, rings, texture, material))
+	}
+	
+	/// Like `capsule`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_capsule(ctx: &Rc<Context>, radius: f32, cylinder_height: f32, segments: u32, rings: u32, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
+		let mut vs: Vec<LitVertex> = Vec::new();
+		let mut is: Vec<u16> = Vec::new();
+		
+		LitMesh::gen_capsule(&mut vs, &mut is, radius, cylinder_height, segments, rings);
+		LitMesh::from_data(ctx, &vs, &is, texture, material)
+	}
+	
+	/// Generates a torus, centred on the origin and lying in the XZ plane, with a texture and material.
+	/// 
+	/// `major_radius` is the distance from the centre of the torus to the centre of the tube, and `minor_radius` is
+	/// the radius of the tube. `major_segments` is the number of subdivisions around the Y axis, and
+	/// `minor_segments` is the number of subdivisions around the tube. Both must be at least 3.
+	/// 
+	/// The texture is wrapped around the torus, with `u` going around the Y axis and `v` going around the tube.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
+	pub fn torus(ctx: &Rc<Context>, major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32, texture: Rc<Texture2d>, material: Material) -> LitMesh {
+		util::exit_on_err(LitMesh::try_torus(ctx, major_radius, minor_radius, major_segments, minor_segments, texture, material))
+	}
+	
+	/// Like `torus`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_torus(ctx: &Rc<Context>, major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
+		let mut vs: Vec<LitVertex> = Vec::new();
+		let mut is: Vec<u16> = Vec::new();
+		
+		LitMesh::gen_torus(&mut vs, &mut is, major_radius, minor_radius, major_segments, minor_segments);
+		LitMesh::from_data(ctx, &vs, &is, texture, material)
+	}
+	
+	/// Generates a flat plane, centred on the origin and lying in the XZ plane, with a texture and material.
+	/// 
+	/// The plane is `width` long along the X axis and `depth` long along the Z axis, and faces up, with all of its
+	/// normals being +Y. It is divided into a grid of `subdivisions` by `subdivisions` quads, so that lights that
+	/// are calculated per vertex, like the falloff of point lights, are smooth across large planes.
+	/// `subdivisions` is clamped to between 1 and 255, so that the vertices can be indexed by a `u16`.
+	/// 
+	/// The texture is tiled once per world unit, starting from the -X -Z corner.
+	/// 
+	/// Planes have no thickness, so for a floor pair the plane with a thin cuboid collider whose top is level with it.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
+	pub fn plane(ctx: &Rc<Context>, width: f32, depth: f32, subdivisions: u32, texture: Rc<Texture2d>, material: Material) -> LitMesh {
+		util::exit_on_err(LitMesh::try_plane(ctx, width, depth, subdivisions, texture, material))
+	}
+	
+	/// Like `plane`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_plane(ctx: &Rc<Context>, width: f32, depth: f32, subdivisions: u32, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
+		let mut vs: Vec<LitVertex> = Vec::new();
+		let mut is: Vec<u16> = Vec::new();
+		
+		LitMesh::gen_plane(&mut vs, &mut is, width, depth, subdivisions);
+		// The uvs go past 1 so that the texture tiles
+		LitMesh::from_data(ctx, &vs, &is, texture, material).map(|m| m.with_wrap(SamplerWrapFunction::Repeat))
+	}
+	
+	/// Constructs a mesh from a list of vertices, and a list of triangles as indices into the vertices, with a
+	/// texture and material.
+	/// 
+	/// The triangles should be in counter-clockwise order when looking at their front faces.
+	/// 
+	/// The texture is clamped at the edges. Use `with_wrap` for meshes whose uvs go outside of the 0 to 1 range.
+	pub fn from_data(ctx: &Rc<Context>, vertices: &[LitVertex], indices: &[u16], texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
+		let vs = VertexBuffer::immutable(ctx, vertices)
+			.map_err(|e| format!("Could not create vertex buffer: {:?}", e))?;
+		let is = IndexBuffer::immutable(ctx, index::PrimitiveType::TrianglesList, indices)
+			.map_err(|e| format!("Could not create index buffer: {:?}", e))?;
+		
+		Ok(LitMesh {
 			vertex_buffer: vs,
 			index_buffer : is,
 			texture      : texture,
+			wrap         : SamplerWrapFunction::Clamp,
 			material     : material,
-		}
+		})
+	}
+	
+	/// Returns the mesh, but with its texture sampled with `wrap` outside of the 0 to 1 range of uvs.
+	pub fn with_wrap(mut self, wrap: SamplerWrapFunction) -> LitMesh {
+		self.wrap = wrap;
+		self
+	}
+	
+	/// Constructs a mesh from the source of a Wavefront OBJ file, with a texture and material.
+	/// 
+	/// Polygons are triangulated. Vertices without normals are given smooth normals, and vertices without texture
+	/// coordinates are given `(0, 0)`. Materials and groups are ignored.
+	pub fn from_obj(ctx: &Rc<Context>, src: &str, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
+		let (vs, is) = parse_obj(src)?;
+		LitMesh::from_data(ctx, &vs, &is, texture, material)
 	}
 	
 	fn gen_sphere(vs: &mut Vec<LitVertex>, is: &mut Vec<u16>, detail: u32) {
+		const PI: f32 = ::std::f32::consts::PI;
 		let start = vs.len();
 		LitMesh::gen_dodec(vs, is, detail);
-		for i in start..vs.len() {
-			Vector3::<f32>::from(vs[i].pos).normalize_mut();
-			vs[i].normal = vs[i].pos;
+		
+		// Each face of the dodecahedron has its own vertices, so the uvs of the vertices on the seam and the poles
+		// can be picked per face.
+		let verts_per_face = (vs.len() - start) / 8;
+		for face in vs[start..].chunks_mut(verts_per_face) {
+			// Normalize the vertices
+			let mut centre = Vector3::zero();
+			for v in face.iter_mut() {
+				let pos = Vector3::new(v.pos[0], v.pos[1], v.pos[2]).normalize();
+				*v = LitVertex::new(pos, pos, Vector2::zero());
+				centre += pos;
+			}
+			let face_u = 0.5 + centre.z.atan2(centre.x) / (2.0 * PI);
+			
+			for v in face.iter_mut() {
+				let (x, y, z) = (v.pos[0], v.pos[1], v.pos[2]);
+				let u = if x * x + z * z < 1e-6 {
+					// The longitude is undefined at the poles, so use the longitude of the face
+					face_u
+				} else {
+					let u = 0.5 + z.atan2(x) / (2.0 * PI);
+					// Keep vertices on the seam on the same side as the rest of the face
+					if u - face_u > 0.5 {
+						u - 1.0
+					} else if face_u - u > 0.5 {
+						u + 1.0
+					} else {
+						u
+					}
+				};
+				let v_coord = 0.5 - y.max(-1.0).min(1.0).asin() / PI;
+				v.uv = [u, v_coord];
+			}
+		}
+	}
+	
+	fn gen_cylinder(vs: &mut Vec<LitVertex>, is: &mut Vec<u16>, radius: f32, height: f32, segments: u32) {
+		const PI: f32 = ::std::f32::consts::PI;
+		let segments = segments.max(3) as u16;
+		let half = height / 2.0;
+		
+		// Side. The first column is repeated at the end so that the texture doesn't wrap back around.
+		let side = vs.len() as u16;
+		for seg in 0..segments + 1 {
+			let k = seg as f32 / segments as f32;
+			let angle = k * 2.0 * PI;
+			let normal = Vector3::new(angle.cos(), 0.0, angle.sin());
+			vs.push(LitVertex::new(Vector3::new(normal.x * radius,  half, normal.z * radius), normal, Vector2::new(k, 0.0)));
+			vs.push(LitVertex::new(Vector3::new(normal.x * radius, -half, normal.z * radius), normal, Vector2::new(k, 1.0)));
+		}
+		for seg in 0..segments {
+			let (t0, b0) = (side + seg * 2, side + seg * 2 + 1);
+			let (t1, b1) = (t0 + 2, b0 + 2);
+			is.extend(&[t0, t1, b0]);
+			is.extend(&[b0, t1, b1]);
+		}
+		
+		// Caps
+		for &(y, normal_y) in &[(half, 1.0), (-half, -1.0)] {
+			let normal = Vector3::new(0.0, normal_y, 0.0);
+			let centre = vs.len() as u16;
+			vs.push(LitVertex::new(Vector3::new(0.0, y, 0.0), normal, Vector2::new(0.5, 0.5)));
+			for seg in 0..segments {
+				let angle = seg as f32 / segments as f32 * 2.0 * PI;
+				let (c, s) = (angle.cos(), angle.sin());
+				vs.push(LitVertex::new(Vector3::new(c * radius, y, s * radius), normal, Vector2::new(0.5 + c * 0.5, 0.5 + s * 0.5)));
+			}
+			for seg in 0..segments {
+				let v0 = centre + 1 + seg;
+				let v1 = centre + 1 + (seg + 1) % segments;
+				if normal_y > 0.0 {
+					is.extend(&[centre, v1, v0]);
+				} else {
+					is.extend(&[centre, v0, v1]);
+				}
+			}
+		}
+	}
+	
+	fn gen_capsule(vs: &mut Vec<LitVertex>, is: &mut Vec<u16>, radius: f32, cylinder_height: f32, segments: u32, rings: u32) {
+		const PI: f32 = ::std::f32::consts::PI;
+		let segments = segments.max(3) as u16;
+		let rings = rings.max(1) as u16;
+		let half = cylinder_height / 2.0;
+		let total_height = cylinder_height + radius * 2.0;
+		let start = vs.len() as u16;
+		
+		// Rows go from the top pole to the equator of the top hemisphere, then from the equator of the bottom
+		// hemisphere to the bottom pole. The two equator rows have the same normals, and form the cylinder.
+		let rows = (rings + 1) * 2;
+		for row in 0..rows {
+			let (lat, centre_y) = if row <= rings {
+				(PI / 2.0 * (1.0 - row as f32 / rings as f32), half)
+			} else {
+				(-PI / 2.0 * ((row - rings - 1) as f32 / rings as f32), -half)
+			};
+			let (ring_radius, ring_y) = (lat.cos(), lat.sin());
+			
+			// The first column is repeated at the end so that the texture doesn't wrap back around.
+			for col in 0..segments + 1 {
+				let k = col as f32 / segments as f32;
+				let angle = k * 2.0 * PI;
+				let normal = Vector3::new(angle.cos() * ring_radius, ring_y, angle.sin() * ring_radius);
+				let pos = normal * radius + Vector3::new(0.0, centre_y, 0.0);
+				let v = (half + radius - pos.y) / total_height;
+				vs.push(LitVertex::new(pos, normal, Vector2::new(k, v)));
+			}
+		}
+		
+		let cols = segments + 1;
+		for row in 0..rows - 1 {
+			for col in 0..segments {
+				let t0 = start + row * cols + col;
+				let t1 = t0 + 1;
+				let (b0, b1) = (t0 + cols, t1 + cols);
+				is.extend(&[t0, t1, b0]);
+				is.extend(&[b0, t1, b1]);
+			}
+		}
+	}
+	
+	fn gen_torus(vs: &mut Vec<LitVertex>, is: &mut Vec<u16>, major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) {
+		const PI: f32 = ::std::f32::consts::PI;
+		let major_segments = major_segments.max(3) as u16;
+		let minor_segments = minor_segments.max(3) as u16;
+		let start = vs.len() as u16;
+		
+		// The first row and column are repeated at the end so that the texture doesn't wrap back around. The repeated
+		// vertices have exactly the same positions and normals, so the mesh is closed and there is no lighting seam.
+		for row in 0..minor_segments + 1 {
+			let v = row as f32 / minor_segments as f32;
+			// Goes down the outside of the tube first, so that the triangles face outwards
+			let minor_angle = if row == minor_segments { 0.0 } else { v * 2.0 * PI };
+			let (minor_c, minor_s) = (minor_angle.cos(), -minor_angle.sin());
+			for col in 0..major_segments + 1 {
+				let u = col as f32 / major_segments as f32;
+				let major_angle = if col == major_segments { 0.0 } else { u * 2.0 * PI };
+				let (major_c, major_s) = (major_angle.cos(), major_angle.sin());
+				
+				// The normal points away from the centre of the tube
+				let tube_centre = Vector3::new(major_c * major_radius, 0.0, major_s * major_radius);
+				let normal = Vector3::new(major_c * minor_c, minor_s, major_s * minor_c);
+				vs.push(LitVertex::new(tube_centre + normal * minor_radius, normal, Vector2::new(u, v)));
+			}
+		}
+		
+		let cols = major_segments + 1;
+		for row in 0..minor_segments {
+			for col in 0..major_segments {
+				let t0 = start + row * cols + col;
+				let t1 = t0 + 1;
+				let (b0, b1) = (t0 + cols, t1 + cols);
+				is.extend(&[t0, t1, b0]);
+				is.extend(&[b0, t1, b1]);
+			}
 		}
 	}
 	
@@ -182,6 +474,34 @@ impl LitMesh {
 		}
 	}
 	
+	fn gen_plane(vs: &mut Vec<LitVertex>, is: &mut Vec<u16>, width: f32, depth: f32, subdivisions: u32) {
+		let n = subdivisions.max(1).min(255) as u16;
+		let normal = Vector3::new(0.0, 1.0, 0.0);
+		let start = vs.len() as u16;
+		
+		// Rows go along the Z axis, and columns along the X axis
+		for row in 0..n + 1 {
+			let z = (row as f32 / n as f32 - 0.5) * depth;
+			for col in 0..n + 1 {
+				let x = (col as f32 / n as f32 - 0.5) * width;
+				let uv = Vector2::new(x + width / 2.0, z + depth / 2.0);
+				vs.push(LitVertex::new(Vector3::new(x, 0.0, z), normal, uv));
+			}
+		}
+		
+		// Counter-clockwise when looking down
+		let stride = n + 1;
+		for row in 0..n {
+			for col in 0..n {
+				let v0 = start + row * stride + col;
+				let (v1, v2) = (v0 + 1, v0 + stride);
+				let v3 = v2 + 1;
+				is.extend(&[v0, v2, v1]);
+				is.extend(&[v2, v3, v1]);
+			}
+		}
+	}
+	
 	fn gen_cuboid(vs: &mut Vec<LitVertex>, is: &mut Vec<u16>, half_extents: Vector3<f32>) {
 		// v0 --- v1 
 		// |          <- Looking forward, normal out of the screen.
@@ -246,3 +566,168 @@ impl LitMesh {
 			Vector2::new(ux*2.0, 1.0));
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	pub fn test_cylinder_vertex_count() {
+		for &segments in &[3, 8, 32] {
+			let mut vs = Vec::new();
+			let mut is = Vec::new();
+			LitMesh::gen_cylinder(&mut vs, &mut is, 1.0, 2.0, segments);
+			// The side has a duplicated seam column, and the caps have their own rings with ±Y normals
+			let side = 2 * (segments as usize + 1);
+			let caps = 2 * segments as usize + 2;
+			assert_eq!(side + caps, vs.len());
+			for v in vs.iter() {
+				let n = Vector3::new(v.normal[0], v.normal[1], v.normal[2]);
+				assert!((n.norm() - 1.0).abs() < 1e-5);
+			}
+		}
+	}
+	
+	#[test]
+	pub fn test_capsule_normals_continuous() {
+		let segments = 8;
+		let rings = 4;
+		let mut vs = Vec::new();
+		let mut is = Vec::new();
+		LitMesh::gen_capsule(&mut vs, &mut is, 0.5, 2.0, segments, rings);
+		
+		let cols = segments as usize + 1;
+		assert_eq!((rings as usize + 1) * 2 * cols, vs.len());
+		// The equator rows of each hemisphere have the same normals
+		let top_equator = rings as usize * cols;
+		let bottom_equator = top_equator + cols;
+		for col in 0..cols {
+			let a = vs[top_equator + col];
+			let b = vs[bottom_equator + col];
+			assert_eq!(a.normal, b.normal);
+			assert!(a.normal[1].abs() < 1e-6);
+			assert!((a.pos[1] - 1.0).abs() < 1e-6 && (b.pos[1] + 1.0).abs() < 1e-6);
+		}
+	}
+	
+	#[test]
+	pub fn test_torus_topology() {
+		for &(major_segments, minor_segments) in &[(3, 3), (16, 8), (32, 12)] {
+			let mut vs = Vec::new();
+			let mut is = Vec::new();
+			LitMesh::gen_torus(&mut vs, &mut is, 2.0, 0.5, major_segments, minor_segments);
+			assert_eq!((major_segments * minor_segments * 6) as usize, is.len());
+			
+			// The last row and column are in exactly the same place as the first
+			let cols = major_segments as usize + 1;
+			let rows = minor_segments as usize + 1;
+			for row in 0..rows {
+				assert_eq!(vs[row * cols].pos, vs[row * cols + cols - 1].pos);
+				assert_eq!(vs[row * cols].normal, vs[row * cols + cols - 1].normal);
+			}
+			for col in 0..cols {
+				assert_eq!(vs[col].pos, vs[(rows - 1) * cols + col].pos);
+				assert_eq!(vs[col].normal, vs[(rows - 1) * cols + col].normal);
+			}
+		}
+	}
+	
+	#[test]
+	pub fn test_torus_faces_outwards() {
+		let mut vs = Vec::new();
+		let mut is = Vec::new();
+		LitMesh::gen_torus(&mut vs, &mut is, 2.0, 0.5, 16, 8);
+		for tri in is.chunks(3) {
+			let p: Vec<Vector3<f32>> = tri.iter().map(|&i| Vector3::new(vs[i as usize].pos[0], vs[i as usize].pos[1], vs[i as usize].pos[2])).collect();
+			let n = vs[tri[0] as usize].normal;
+			let face_normal = (p[1] - p[0]).cross(&(p[2] - p[0]));
+			assert!(face_normal.dot(&Vector3::new(n[0], n[1], n[2])) > 0.0, "triangle faces inwards: {:?}", tri);
+		}
+	}
+	
+	#[test]
+	pub fn test_sphere_uvs_in_range() {
+		let mut vs = Vec::new();
+		let mut is = Vec::new();
+		LitMesh::gen_sphere(&mut vs, &mut is, 3);
+		for v in vs.iter() {
+			assert!(v.uv[0] >= 0.0 && v.uv[0] <= 1.0, "u out of range: {:?}", v);
+			assert!(v.uv[1] >= 0.0 && v.uv[1] <= 1.0, "v out of range: {:?}", v);
+		}
+	}
+	
+	#[test]
+	pub fn test_sphere_uvs_poles() {
+		let mut vs = Vec::new();
+		let mut is = Vec::new();
+		LitMesh::gen_sphere(&mut vs, &mut is, 2);
+		let top: Vec<_> = vs.iter().filter(|v| v.pos[1] > 0.9999).collect();
+		let bottom: Vec<_> = vs.iter().filter(|v| v.pos[1] < -0.9999).collect();
+		assert_eq!(4, top.len());
+		assert_eq!(4, bottom.len());
+		for v in top {
+			assert!(v.uv[1].abs() < 1e-6, "top pole not at v=0: {:?}", v);
+		}
+		for v in bottom {
+			assert!((v.uv[1] - 1.0).abs() < 1e-6, "bottom pole not at v=1: {:?}", v);
+		}
+	}
+	
+	#[test]
+	pub fn test_sphere_is_normalized() {
+		let mut vs = Vec::new();
+		let mut is = Vec::new();
+		LitMesh::gen_sphere(&mut vs, &mut is, 2);
+		for v in vs.iter() {
+			let len = Vector3::new(v.pos[0], v.pos[1], v.pos[2]).norm();
+			assert!((len - 1.0).abs() < 1e-5, "vertex not on the unit sphere: {:?}", v);
+			assert_eq!(v.pos, v.normal);
+		}
+	}
+	
+	#[test]
+	pub fn test_sphere_no_triangle_spans_seam() {
+		let mut vs = Vec::new();
+		let mut is = Vec::new();
+		LitMesh::gen_sphere(&mut vs, &mut is, 3);
+		for tri in is.chunks(3) {
+			let us: Vec<f32> = tri.iter().map(|&i| vs[i as usize].uv[0]).collect();
+			let min = us.iter().cloned().fold(1.0, f32::min);
+			let max = us.iter().cloned().fold(0.0, f32::max);
+			assert!(max - min < 0.5, "triangle spans the seam: {:?}", us);
+		}
+	}
+	
+	#[test]
+	pub fn test_plane_faces_up() {
+		for &subdivisions in &[1, 4, 1000] {
+			let mut vs = Vec::new();
+			let mut is = Vec::new();
+			LitMesh::gen_plane(&mut vs, &mut is, 4.0, 2.0, subdivisions);
+			let n = subdivisions.min(255) as usize;
+			assert_eq!((n + 1) * (n + 1), vs.len());
+			assert_eq!(n * n * 6, is.len());
+			for tri in is.chunks(3) {
+				let p = |i: u16| Vector3::new(vs[i as usize].pos[0], vs[i as usize].pos[1], vs[i as usize].pos[2]);
+				let normal = (p(tri[1]) - p(tri[0])).cross(&(p(tri[2]) - p(tri[0])));
+				assert!(normal.y > 0.0, "triangle {:?} faces {:?}", tri, normal);
+			}
+		}
+		
+		// The corners are at the edges of the plane, with the texture tiled once per unit
+		let mut vs = Vec::new();
+		LitMesh::gen_plane(&mut vs, &mut Vec::new(), 4.0, 2.0, 2);
+		assert_eq!([-2.0, 0.0, -1.0], vs[0].pos);
+		assert_eq!([0.0, 0.0], vs[0].uv);
+		assert_eq!([2.0, 0.0, 1.0], vs[vs.len() - 1].pos);
+		assert_eq!([4.0, 2.0], vs[vs.len() - 1].uv);
+	}
+	
+	#[test]
+	pub fn test_vertex_new() {
+		let v = LitVertex::new(Vector3::new(1.0, -2.0, 3.5), Vector3::new(0.0, 1.0, 0.0), Vector2::new(0.25, 0.75));
+		assert_eq!([1.0, -2.0, 3.5], v.pos);
+		assert_eq!([0.0, 1.0, 0.0], v.normal);
+		assert_eq!([0.25, 0.75], v.uv);
+	}
+}
@@ -1,18 +1,72 @@
 use prelude::*;
 use std::rc::Rc;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 
 use super::{Color, Render};
 use util;
 
-pub use self::simple::{SimpleVertex, SimpleMesh};
+pub use self::simple::{SimpleVertex, SimpleMesh, sphere_detail_for_radius, MAX_SPHERE_DETAIL};
 pub use self::lit::{LitVertex, LitMesh};
 
 mod simple;
 mod lit;
+mod obj;
 
 /// Represents a mesh that can be rendered.
 pub trait RenderableMesh {
-	fn render(&self, r: &mut Render, model: Matrix4<f32>);
+	/// Renders the mesh transformed by `model`. If `wireframe` is true, only the edges of the triangles should be drawn.
+	fn render(&self, r: &mut Render, model: Matrix4<f32>, wireframe: bool);
+	
+	/// Gets the `SimpleMesh`, model matrix and color that the mesh would be rendered with when transformed by `model`,
+	/// so that it can be drawn along with other copies of the same `SimpleMesh` in an `InstanceBatch`.
+	/// 
+	/// Returns `None` if the mesh can't be drawn like this, which is the default.
+	fn instance(&self, _r: &Render, _model: Matrix4<f32>) -> Option<(Rc<SimpleMesh>, Matrix4<f32>, Color)> {
+		None
+	}
+}
+
+/// Collects copies of `SimpleMesh`es, so that all of the copies of each mesh can be drawn in one draw call with
+/// `Render::render_simple_instanced`.
+pub struct InstanceBatch {
+	/// The index of each mesh in `groups`, by the address of the mesh.
+	indices: HashMap<*const SimpleMesh, usize>,
+	/// The meshes, with the model matrix and color of each copy, in the order that they were first added.
+	groups: Vec<(Rc<SimpleMesh>, Vec<(Matrix4<f32>, Color)>)>,
+}
+impl InstanceBatch {
+	/// Constructs a new, empty InstanceBatch.
+	pub fn new() -> InstanceBatch {
+		InstanceBatch {
+			indices: HashMap::new(),
+			groups: Vec::new(),
+		}
+	}
+	
+	/// Adds a copy of `mesh` transformed by `model` in `color`.
+	pub fn push(&mut self, mesh: Rc<SimpleMesh>, model: Matrix4<f32>, color: Color) {
+		let groups = &mut self.groups;
+		let i = *self.indices.entry(&*mesh as *const SimpleMesh).or_insert_with(|| {
+			groups.push((mesh.clone(), Vec::new()));
+			groups.len() - 1
+		});
+		groups[i].1.push((model, color));
+	}
+	
+	/// Draws all of the copies of the meshes that have been added.
+	/// 
+	/// Meshes with only one copy are drawn with `SimpleMesh::render`, as that is faster than instancing them.
+	pub fn render(self, r: &mut Render) {
+		for (mesh, instances) in self.groups {
+			if instances.len() == 1 {
+				let (model, color) = instances[0];
+				mesh.render(r, model, color, false);
+			} else {
+				r.render_simple_instanced(&mesh, &instances);
+			}
+		}
+	}
 }
 
 /// Holds a SimpleMesh and gives it a color and scale so that it can be rendered to the screen.
@@ -40,9 +94,60 @@ impl ColoredMesh {
 	}
 }
 impl RenderableMesh for ColoredMesh {
-	fn render(&self, r: &mut Render, model: Matrix4<f32>) {
+	fn render(&self, r: &mut Render, model: Matrix4<f32>, wireframe: bool) {
+		let scale = util::mat4_scale(Vector3::new(self.scale, self.scale, self.scale));
+		self.mesh.render(r, model * scale, self.color, wireframe);
+	}
+	
+	fn instance(&self, _r: &Render, model: Matrix4<f32>) -> Option<(Rc<SimpleMesh>, Matrix4<f32>, Color)> {
+		// Transparent instances would stop all of the other instances from writing to the depth buffer
+		if self.color.is_transparent() {
+			return None;
+		}
 		let scale = util::mat4_scale(Vector3::new(self.scale, self.scale, self.scale));
-		self.mesh.render(r, model * scale, self.color);
+		Some((self.mesh.clone(), model * scale, self.color))
+	}
+}
+
+/// Renders a different mesh depending on how far away from the camera it is, so that distant objects can be drawn
+/// with less detail.
+pub struct LodMesh {
+	/// The meshes, with the maximum distance from the camera that they are used at, ordered by distance.
+	levels: Vec<(f32, Rc<RenderableMesh>)>,
+}
+impl LodMesh {
+	/// Constructs a new LodMesh from a list of `(max_dist, mesh)` levels, in any order.
+	/// 
+	/// Each mesh is used when the origin of the mesh is up to `max_dist` away from the camera, and further away than
+	/// the previous level. When it is further away than every `max_dist`, the mesh with the largest `max_dist` is used.
+	pub fn new(mut levels: Vec<(f32, Rc<RenderableMesh>)>) -> LodMesh {
+		levels.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+		LodMesh {
+			levels,
+		}
+	}
+	
+	/// Gets the mesh used when the mesh is `dist` away from the camera, or `None` if there are no levels.
+	pub fn level(&self, dist: f32) -> Option<&Rc<RenderableMesh>> {
+		self.levels.iter()
+			.find(|&&(max_dist, _)| dist <= max_dist)
+			.or_else(|| self.levels.last())
+			.map(|&(_, ref mesh)| mesh)
+	}
+}
+impl RenderableMesh for LodMesh {
+	fn render(&self, r: &mut Render, model: Matrix4<f32>, wireframe: bool) {
+		let origin = Vector3::new(model[(0, 3)], model[(1, 3)], model[(2, 3)]);
+		let dist = (origin - r.camera().pos()).norm();
+		if let Some(mesh) = self.level(dist) {
+			mesh.render(r, model, wireframe);
+		}
+	}
+	
+	fn instance(&self, r: &Render, model: Matrix4<f32>) -> Option<(Rc<SimpleMesh>, Matrix4<f32>, Color)> {
+		let origin = Vector3::new(model[(0, 3)], model[(1, 3)], model[(2, 3)]);
+		let dist = (origin - r.camera().pos()).norm();
+		self.level(dist)?.instance(r, model)
 	}
 }
 
@@ -58,7 +163,24 @@ impl EmptyMesh {
 	}
 }
 impl RenderableMesh for EmptyMesh {
-	fn render(&self, _r: &mut Render, _model: Matrix4<f32>) {
+	fn render(&self, _r: &mut Render, _model: Matrix4<f32>, _wireframe: bool) {
 		// No-op.
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	pub fn test_lod_level() {
+		let near: Rc<RenderableMesh> = Rc::new(EmptyMesh::new());
+		let far: Rc<RenderableMesh> = Rc::new(EmptyMesh::new());
+		let lod = LodMesh::new(vec![(50.0, far.clone()), (10.0, near.clone())]);
+		assert!(Rc::ptr_eq(&near, lod.level(0.0).unwrap()));
+		assert!(Rc::ptr_eq(&near, lod.level(10.0).unwrap()));
+		assert!(Rc::ptr_eq(&far, lod.level(10.5).unwrap()));
+		assert!(Rc::ptr_eq(&far, lod.level(1000.0).unwrap()));
+		assert!(LodMesh::new(Vec::new()).level(0.0).is_none());
+	}
+}
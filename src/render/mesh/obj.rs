@@ -0,0 +1,214 @@
+//! Parsing of Wavefront OBJ meshes.
+use prelude::*;
+use std::collections::HashMap;
+
+use super::LitVertex;
+use util;
+
+/// Parses an index in a face of an OBJ file, which is 1-based, or relative to the end of the list if negative.
+fn parse_index(s: &str, len: usize, line_num: usize) -> Result<usize, String> {
+	let i: i64 = s.parse().map_err(|e| format!("line {}: invalid index '{}': {}", line_num, s, e))?;
+	let i = if i < 0 { len as i64 + i } else { i - 1 };
+	if i < 0 || i >= len as i64 {
+		Err(format!("line {}: index out of range: {}", line_num, s))
+	} else {
+		Ok(i as usize)
+	}
+}
+
+/// Parses the floats after the keyword of a line.
+fn parse_floats<'a, I: Iterator<Item=&'a str>>(it: I, n: usize, line_num: usize) -> Result<Vec<f32>, String> {
+	let vs = it.take(n)
+		.map(|s| s.parse::<f32>().map_err(|e| format!("line {}: invalid number '{}': {}", line_num, s, e)))
+		.collect::<Result<Vec<f32>, String>>()?;
+	if vs.len() < n {
+		Err(format!("line {}: expected {} numbers", line_num, n))
+	} else {
+		Ok(vs)
+	}
+}
+
+/// Parses the source of an OBJ file into a list of vertices and triangles.
+/// 
+/// Polygons are triangulated as fans. Vertices without normals are given smooth normals, averaged from the faces
+/// that share them, and vertices without texture coordinates are given `(0, 0)`. Texture coordinates are flipped vertically to match the textures loaded
+/// by the `vfs`. Materials, groups and smoothing groups are ignored.
+pub fn parse_obj(src: &str) -> Result<(Vec<LitVertex>, Vec<u16>), String> {
+	let mut positions: Vec<Vector3<f32>> = Vec::new();
+	let mut normals: Vec<Vector3<f32>> = Vec::new();
+	let mut uvs: Vec<Vector2<f32>> = Vec::new();
+	
+	let mut vs: Vec<LitVertex> = Vec::new();
+	let mut is: Vec<u16> = Vec::new();
+	// Vertices that have already been added, indexed by their position, uv and normal indices
+	let mut added: HashMap<(usize, Option<usize>, Option<usize>), u16> = HashMap::new();
+	// Vertices that need their normals computed
+	let mut smooth: Vec<u16> = Vec::new();
+	
+	for (line_num, line) in src.lines().enumerate() {
+		let line_num = line_num + 1;
+		let line = match line.find('#') {
+			Some(i) => &line[..i],
+			None => line,
+		};
+		let mut words = line.split_whitespace();
+		match words.next() {
+			Some("v") => {
+				let v = parse_floats(words, 3, line_num)?;
+				positions.push(Vector3::new(v[0], v[1], v[2]));
+			},
+			Some("vn") => {
+				let v = parse_floats(words, 3, line_num)?;
+				normals.push(Vector3::new(v[0], v[1], v[2]));
+			},
+			Some("vt") => {
+				// The v coordinate is optional
+				let u = parse_floats(words.by_ref(), 1, line_num)?[0];
+				let v = match words.next() {
+					Some(s) => parse_floats(Some(s).into_iter(), 1, line_num)?[0],
+					None => 0.0,
+				};
+				uvs.push(Vector2::new(u, 1.0 - v));
+			},
+			Some("f") => {
+				// (position, uv, normal)
+				let mut face: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+				for word in words {
+					let mut parts = word.split('/');
+					let pos = parse_index(parts.next().unwrap_or(""), positions.len(), line_num)?;
+					let uv = match parts.next() {
+						Some(s) if !s.is_empty() => Some(parse_index(s, uvs.len(), line_num)?),
+						_ => None,
+					};
+					let normal = match parts.next() {
+						Some(s) if !s.is_empty() => Some(parse_index(s, normals.len(), line_num)?),
+						_ => None,
+					};
+					face.push((pos, uv, normal));
+				}
+				if face.len() < 3 {
+					return Err(format!("line {}: face has less than 3 vertices", line_num));
+				}
+				
+				let mut face_is = Vec::with_capacity(face.len());
+				for &(pos, uv, normal) in face.iter() {
+					let i = match added.get(&(pos, uv, normal)).cloned() {
+						Some(i) => i,
+						None => {
+							if vs.len() > u16::max_value() as usize {
+								return Err(format!("line {}: too many vertices", line_num));
+							}
+							let i = vs.len() as u16;
+							vs.push(LitVertex::new(
+								positions[pos],
+								normal.map(|n| normals[n]).unwrap_or(Vector3::zero()),
+								uv.map(|uv| uvs[uv]).unwrap_or(Vector2::zero())));
+							added.insert((pos, uv, normal), i);
+							if normal.is_none() {
+								smooth.push(i);
+							}
+							i
+						},
+					};
+					face_is.push(i);
+				}
+				
+				// Triangulate as a fan
+				for k in 1..face_is.len() - 1 {
+					is.extend(&[face_is[0], face_is[k], face_is[k + 1]]);
+				}
+			},
+			_ => {},
+		}
+	}
+	
+	if !smooth.is_empty() {
+		// Only overwrite the normals of the vertices that weren't given one
+		let mut smoothed = vs.clone();
+		util::compute_smooth_normals(&mut smoothed, &is);
+		for &i in smooth.iter() {
+			vs[i as usize].set_normal(smoothed[i as usize].normal());
+		}
+	}
+	Ok((vs, is))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	pub fn test_quad_is_triangulated() {
+		let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+		let (vs, is) = parse_obj(src).unwrap();
+		assert_eq!(4, vs.len());
+		assert_eq!(vec![0, 1, 2, 0, 2, 3], is);
+		for v in vs.iter() {
+			assert_eq!(Vector3::z(), v.normal());
+		}
+	}
+	
+	#[test]
+	pub fn test_shared_vertices_are_reused() {
+		let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\nf 1//1 3//1 4//1\n";
+		let (vs, is) = parse_obj(src).unwrap();
+		assert_eq!(4, vs.len());
+		assert_eq!(6, is.len());
+	}
+	
+	#[test]
+	pub fn test_smooth_normals_without_vn() {
+		// A square pyramid with its tip up, and no normals
+		let src = "v -1 0 -1\nv 1 0 -1\nv 1 0 1\nv -1 0 1\nv 0 1 0\nf 1 5 2\nf 2 5 3\nf 3 5 4\nf 4 5 1\n";
+		let (vs, is) = parse_obj(src).unwrap();
+		// The faces share the vertices, instead of each having their own
+		assert_eq!(5, vs.len());
+		assert_eq!(12, is.len());
+		// The tip is the average of the sides, so points straight up
+		let tip = vs[4].normal();
+		assert!((tip - Vector3::y()).norm() < 1.0e-5, "{:?}", tip);
+		for v in vs[..4].iter() {
+			let n = v.normal();
+			assert!((n.norm() - 1.0).abs() < 1.0e-5, "{:?}", n);
+			// The corners point outwards and up
+			assert!(n.y > 0.0 && n.x * v.pos().x > 0.0 && n.z * v.pos().z > 0.0, "{:?}", n);
+		}
+	}
+	
+	#[test]
+	pub fn test_negative_indices() {
+		let src = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.25 0.75\nf -3/-1 -2/-1 -1/-1\nv 0 0 1\nf -4 -3 -1\n";
+		let (vs, is) = parse_obj(src).unwrap();
+		assert_eq!(vec![0, 1, 2, 3, 4, 5], is);
+		assert_eq!(Vector3::new(0.0, 0.0, 0.0), vs[0].pos());
+		assert_eq!(Vector3::new(1.0, 0.0, 0.0), vs[1].pos());
+		assert_eq!(Vector3::new(0.0, 1.0, 0.0), vs[2].pos());
+		// Negative indices are relative to the vertices defined so far
+		assert_eq!(Vector3::new(0.0, 0.0, 0.0), vs[3].pos());
+		assert_eq!(Vector3::new(1.0, 0.0, 0.0), vs[4].pos());
+		assert_eq!(Vector3::new(0.0, 0.0, 1.0), vs[5].pos());
+	}
+	
+	#[test]
+	pub fn test_malformed() {
+		// Index out of range
+		assert!(parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n").is_err());
+		// Index 0 is not valid, as indices are 1-based
+		assert!(parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n").is_err());
+		// Too few vertices in a face
+		assert!(parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n").is_err());
+		// Too few numbers in a vertex
+		assert!(parse_obj("v 0 0\n").is_err());
+		// Invalid numbers
+		assert!(parse_obj("v 0 zero 0\n").is_err());
+		assert!(parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 x\n").is_err());
+	}
+	
+	#[test]
+	pub fn test_comments_and_unknown_lines_are_ignored() {
+		let src = "# A triangle\nmtllib tri.mtl\no tri\nv 0 0 0 # origin\nv 1 0 0\nv 0 1 0\ns off\nf 1 2 3\n";
+		let (vs, is) = parse_obj(src).unwrap();
+		assert_eq!(3, vs.len());
+		assert_eq!(3, is.len());
+	}
+}
@@ -1,7 +1,5 @@
 use prelude::*;
-use std::mem;
 use std::rc::Rc;
-use std::process::exit;
 
 use glium::{IndexBuffer, VertexBuffer};
 use glium::index;
@@ -9,6 +7,36 @@ use glium::index;
 use render::{Render, Color};
 use util;
 
+/// The radii below which each sphere detail is used by `sphere_detail_for_radius`. Spheres at least as big as the
+/// last radius use `MAX_SPHERE_DETAIL`.
+const SPHERE_DETAIL_RADII: [f32; 6] = [0.09, 0.18, 0.35, 0.7, 1.4, 2.8];
+
+/// The highest sphere detail used by `sphere_detail_for_radius`. More detailed spheres have too many vertices to be
+/// indexed by a `u16`.
+pub const MAX_SPHERE_DETAIL: u32 = 6;
+
+/// Gets the sphere detail to use for a sphere of the given radius, so that its triangles are roughly the same size
+/// whatever the radius.
+/// 
+/// Each detail level halves the length of the edges, so doubling the radius adds one detail level. A sphere with a
+/// radius of 1 has a detail of 4:
+/// 
+/// | Radius       | Detail |
+/// |--------------|--------|
+/// | < 0.09       | 0      |
+/// | 0.09 to 0.18 | 1      |
+/// | 0.18 to 0.35 | 2      |
+/// | 0.35 to 0.7  | 3      |
+/// | 0.7 to 1.4   | 4      |
+/// | 1.4 to 2.8   | 5      |
+/// | >= 2.8       | 6      |
+pub fn sphere_detail_for_radius(radius: f32) -> u32 {
+	SPHERE_DETAIL_RADII.iter()
+		.position(|&r| radius < r)
+		.map(|i| i as u32)
+		.unwrap_or(MAX_SPHERE_DETAIL)
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct SimpleVertex {
 	pub pos: [f32; 3],
@@ -18,7 +46,7 @@ implement_vertex!(SimpleVertex, pos);
 impl From<Vector3<f32>> for SimpleVertex {
 	fn from(v: Vector3<f32>) -> SimpleVertex {
 		SimpleVertex{
-			pos: unsafe { mem::transmute(v) },
+			pos: [v.x, v.y, v.z],
 		}
 	}
 }
@@ -34,9 +62,9 @@ pub struct SimpleMesh {
 	index_buffer: IndexBuffer<u16>,
 }
 impl SimpleMesh {
-	/// Render the mesh
-	pub fn render(&self, r: &mut Render, model: Matrix4<f32>, color: Color) {
-		r.render_simple(&self.vertex_buffer, &self.index_buffer, model, color);
+	/// Render the mesh, in wireframe if `wireframe` is true.
+	pub fn render(&self, r: &mut Render, model: Matrix4<f32>, color: Color, wireframe: bool) {
+		r.render_simple(&self.vertex_buffer, &self.index_buffer, model, color, wireframe);
 	}
 	
 	/// Construct a new mesh that is an approximation of a sphere.
@@ -45,61 +73,116 @@ impl SimpleMesh {
 	/// *Be warned:* The number of faces is proportional to 2^detail.
 	///
 	/// Detail 0 is the same as a dodecahedron.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
 	pub fn sphere(ctx: &Rc<Context>, detail: u32) -> SimpleMesh {
+		util::exit_on_err(SimpleMesh::try_sphere(ctx, detail))
+	}
+	
+	/// Like `sphere`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_sphere(ctx: &Rc<Context>, detail: u32) -> Result<SimpleMesh, String> {
 		let mut vs: Vec<SimpleVertex> = Vec::new();
 		let mut is: Vec<u16> = Vec::new();
 		
 		SimpleMesh::gen_sphere(&mut vs, &mut is, detail);
-		SimpleMesh::from_vecs(ctx, vs, is)
+		SimpleMesh::from_data(ctx, &vs, &is)
+	}
+	
+	/// Construct a new mesh that is an approximation of a sphere, with a detail that suits a sphere of `radius`.
+	/// See `sphere_detail_for_radius` for how the detail is chosen.
+	/// 
+	/// The mesh still has a radius of 1, so it should be scaled by `radius`, e.g. with `ColoredMesh::with_scale`.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
+	pub fn sphere_for_radius(ctx: &Rc<Context>, radius: f32) -> SimpleMesh {
+		SimpleMesh::sphere(ctx, sphere_detail_for_radius(radius))
+	}
+	
+	/// Like `sphere_for_radius`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_sphere_for_radius(ctx: &Rc<Context>, radius: f32) -> Result<SimpleMesh, String> {
+		SimpleMesh::try_sphere(ctx, sphere_detail_for_radius(radius))
 	}
 	
 	/// Construct a new mesh that is a dodecahedron.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
 	pub fn dodecahedron(ctx: &Rc<Context>) -> SimpleMesh {
+		util::exit_on_err(SimpleMesh::try_dodecahedron(ctx))
+	}
+	
+	/// Like `dodecahedron`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_dodecahedron(ctx: &Rc<Context>) -> Result<SimpleMesh, String> {
 		let mut vs: Vec<SimpleVertex> = Vec::new();
 		let mut is: Vec<u16> = Vec::new();
 		
 		SimpleMesh::gen_dodec(&mut vs, &mut is, 0);
-		SimpleMesh::from_vecs(ctx, vs, is)
+		SimpleMesh::from_data(ctx, &vs, &is)
 	}
 	
 	/// Construct a cuboid from it's half extents.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
 	pub fn cuboid(ctx: &Rc<Context>, half_extents: Vector3<f32>) -> SimpleMesh {
+		util::exit_on_err(SimpleMesh::try_cuboid(ctx, half_extents))
+	}
+	
+	/// Like `cuboid`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_cuboid(ctx: &Rc<Context>, half_extents: Vector3<f32>) -> Result<SimpleMesh, String> {
 		let mut vs: Vec<SimpleVertex> = Vec::new();
 		let mut is: Vec<u16> = Vec::new();
 		
 		SimpleMesh::gen_cuboid(&mut vs, &mut is, half_extents);
-		SimpleMesh::from_vecs(ctx, vs, is)
+		SimpleMesh::from_data(ctx, &vs, &is)
+	}
+	
+	/// Construct a capped cylinder, centred on the origin and aligned to the Y axis.
+	/// 
+	/// `segments` is the number of radial subdivisions, and must be at least 3.
+	/// This matches the ncollide shape `Cylinder::new(height / 2.0, radius)`.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
+	pub fn cylinder(ctx: &Rc<Context>, radius: f32, height: f32, segments: u32) -> SimpleMesh {
+		util::exit_on_err(SimpleMesh::try_cylinder(ctx, radius, height, segments))
+	}
+	
+	/// Like `cylinder`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_cylinder(ctx: &Rc<Context>, radius: f32, height: f32, segments: u32) -> Result<SimpleMesh, String> {
+		let mut vs: Vec<SimpleVertex> = Vec::new();
+		let mut is: Vec<u16> = Vec::new();
+		
+		SimpleMesh::gen_cylinder(&mut vs, &mut is, radius, height, segments);
+		SimpleMesh::from_data(ctx, &vs, &is)
 	}
 	
 	/// Construct a cube with size 1.0 on all sides.
+	/// 
+	/// Exits if the mesh could not be uploaded to OpenGL.
 	pub fn cube(ctx: &Rc<Context>) -> SimpleMesh {
+		util::exit_on_err(SimpleMesh::try_cube(ctx))
+	}
+	
+	/// Like `cube`, but returns an `Err` if the mesh could not be uploaded to OpenGL.
+	pub fn try_cube(ctx: &Rc<Context>) -> Result<SimpleMesh, String> {
 		let mut vs: Vec<SimpleVertex> = Vec::new();
 		let mut is: Vec<u16> = Vec::new();
 		
 		SimpleMesh::gen_cube(&mut vs, &mut is);
-		SimpleMesh::from_vecs(ctx, vs, is)
-	}
-	
-	fn from_vecs(ctx: &Rc<Context>, vs: Vec<SimpleVertex>, is: Vec<u16>) -> SimpleMesh {
-		let vs = match VertexBuffer::immutable(ctx, &vs) {
-			Ok(vs) => vs,
-			Err(e) => {
-				error!("Could not create vertex buffer: {:?}", e);
-				exit(1);
-			},
-		};
-		let is = match IndexBuffer ::immutable(ctx, index::PrimitiveType::TrianglesList, &is) {
-			Ok(is) => is,
-			Err(e) => {
-				error!("Could not create index buffer: {:?}", e);
-				exit(1);
-			},
-		};
-		
-		SimpleMesh {
+		SimpleMesh::from_data(ctx, &vs, &is)
+	}
+	
+	/// Constructs a mesh from a list of vertices, and a list of triangles as indices into the vertices.
+	/// 
+	/// The triangles should be in counter-clockwise order when looking at their front faces.
+	pub fn from_data(ctx: &Rc<Context>, vertices: &[SimpleVertex], indices: &[u16]) -> Result<SimpleMesh, String> {
+		let vs = VertexBuffer::immutable(ctx, vertices)
+			.map_err(|e| format!("Could not create vertex buffer: {:?}", e))?;
+		let is = IndexBuffer::immutable(ctx, index::PrimitiveType::TrianglesList, indices)
+			.map_err(|e| format!("Could not create index buffer: {:?}", e))?;
+		
+		Ok(SimpleMesh {
 			vertex_buffer: vs,
 			index_buffer : is,
-		}
+		})
 	}
 	
 	fn gen_cube(vs: &mut Vec<SimpleVertex>, is: &mut Vec<u16>) {
@@ -134,6 +217,38 @@ impl SimpleMesh {
 		push_quad(is, i, 2, 3, 7, 6); // D
 	}
 	
+	fn gen_cylinder(vs: &mut Vec<SimpleVertex>, is: &mut Vec<u16>, radius: f32, height: f32, segments: u32) {
+		const PI: f32 = ::std::f32::consts::PI;
+		let segments = segments.max(3) as u16;
+		let half = height / 2.0;
+		let i = vs.len() as u16;
+		
+		// Top and bottom rings, interleaved
+		for seg in 0..segments {
+			let angle = seg as f32 / segments as f32 * 2.0 * PI;
+			let (x, z) = (angle.cos() * radius, angle.sin() * radius);
+			vs.push(Vector3::new(x,  half, z).into());
+			vs.push(Vector3::new(x, -half, z).into());
+		}
+		// Cap centres
+		let top = i + segments * 2;
+		let bottom = top + 1;
+		vs.push(Vector3::new(0.0,  half, 0.0).into());
+		vs.push(Vector3::new(0.0, -half, 0.0).into());
+		
+		for seg in 0..segments {
+			let next = (seg + 1) % segments;
+			let (t0, b0) = (i + seg * 2, i + seg * 2 + 1);
+			let (t1, b1) = (i + next * 2, i + next * 2 + 1);
+			// Side
+			is.extend(&[t0, t1, b0]);
+			is.extend(&[b0, t1, b1]);
+			// Caps
+			is.extend(&[top, t1, t0]);
+			is.extend(&[bottom, b0, b1]);
+		}
+	}
+	
 	fn gen_sphere(vs: &mut Vec<SimpleVertex>, is: &mut Vec<u16>, detail: u32) {
 		// Generate dodecohedron
 		SimpleMesh::gen_dodec(vs, is, detail);
@@ -228,3 +343,47 @@ impl SimpleMesh {
 		&self.index_buffer
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	pub fn test_cylinder_vertex_count() {
+		for &segments in &[3, 8, 32] {
+			let mut vs = Vec::new();
+			let mut is = Vec::new();
+			SimpleMesh::gen_cylinder(&mut vs, &mut is, 1.0, 2.0, segments);
+			// Two rings and two cap centres
+			assert_eq!(2 * segments as usize + 2, vs.len());
+			// Two triangles on the side and one on each cap per segment
+			assert_eq!(4 * segments as usize * 3, is.len());
+		}
+	}
+	
+	#[test]
+	pub fn test_sphere_detail_for_radius() {
+		assert_eq!(0, sphere_detail_for_radius(0.0));
+		assert_eq!(0, sphere_detail_for_radius(0.05));
+		assert_eq!(4, sphere_detail_for_radius(1.0));
+		assert_eq!(MAX_SPHERE_DETAIL, sphere_detail_for_radius(1000.0));
+		let mut last = 0;
+		for i in 0..100 {
+			let detail = sphere_detail_for_radius(i as f32 * 0.05);
+			assert!(detail >= last);
+			last = detail;
+		}
+		
+		// The most detailed sphere can still be indexed by a u16
+		let mut vs = Vec::new();
+		let mut is = Vec::new();
+		SimpleMesh::gen_sphere(&mut vs, &mut is, MAX_SPHERE_DETAIL);
+		assert!(vs.len() <= u16::max_value() as usize + 1);
+	}
+	
+	#[test]
+	pub fn test_vertex_from_vector() {
+		let v = SimpleVertex::from(Vector3::new(1.0, -2.0, 3.5));
+		assert_eq!([1.0, -2.0, 3.5], v.pos);
+	}
+}
@@ -1,4 +1,5 @@
 use prelude::*;
+use std::ops::{Add, Mul};
 
 use util;
 
@@ -52,6 +53,7 @@ impl Light {
 	
 	/// Constructs a new spotlight
 	/// 
+	/// `dir` is the direction the spotlight points in, and doesn't need to be normalized.
 	/// `cutoff` is how wide the spotlight is, in radians.
 	/// `exponent` is how 'focused' the spotlight is.
 	pub fn new_spotlight(pos: Vector3<f32>, dir: Vector3<f32>, diffuse: Vector4<f32>, specular: Vector4<f32>,
@@ -66,7 +68,7 @@ impl Light {
 			quadratic_attenuation: quadratic_attenuation,
 			spot_cutoff   : cutoff,
 			spot_exponent : exponent,
-			spot_direction: dir,
+			spot_direction: dir.try_normalize(1.0e-6).unwrap_or_else(Vector3::zero),
 		}
 	}
 	
@@ -92,6 +94,21 @@ impl Material {
 			shininess: shininess,
 		}
 	}
+	/// A rough material that has no specular highlights, e.g. chalk or cloth.
+	pub fn matte(color: Color) -> Material {
+		let c = color.into_vector4();
+		Material::new(c, c * 0.9, Vector4::new(0.0, 0.0, 0.0, c.w), 1.0)
+	}
+	/// A smooth material with white specular highlights, e.g. plastic or painted wood.
+	pub fn plastic(color: Color) -> Material {
+		let c = color.into_vector4();
+		Material::new(c, c, Vector4::new(0.5, 0.5, 0.5, c.w), 32.0)
+	}
+	/// A shiny material with sharp specular highlights tinted by its color, e.g. gold or steel.
+	pub fn metal(color: Color) -> Material {
+		let c = color.into_vector4();
+		Material::new(c * 0.3, c * 0.5, c, 96.0)
+	}
 	/// Returns a copy of the material, but with ambient reflection `r`.
 	pub fn with_ambient(mut self, r: Vector4<f32>) -> Material {
 		self.ambient = r;
@@ -107,6 +124,11 @@ impl Material {
 		self.specular = r;
 		self
 	}
+	/// Returns a copy of the material, but with shininess `shininess`.
+	pub fn with_shininess(mut self, shininess: f32) -> Material {
+		self.shininess = shininess;
+		self
+	}
 	/// Returns a copy of the material, but with ambient, diffuse and specular reflection scaled by a color.
 	pub fn with_scale_rgba(mut self, scale: Vector4<f32>) -> Material {
 		self.ambient = self.ambient.component_mul(&scale);
@@ -116,39 +138,152 @@ impl Material {
 	}
 }
 
-/// RGB Color
-#[derive(Copy, Clone, Debug)]
+/// A rectangle on the screen, in pixels.
+/// 
+/// `x` and `y` are the top-left corner of the rectangle, relative to the top-left of the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScreenRect {
+	pub x: f32,
+	pub y: f32,
+	pub w: f32,
+	pub h: f32,
+}
+impl ScreenRect {
+	pub fn new(x: f32, y: f32, w: f32, h: f32) -> ScreenRect {
+		ScreenRect { x, y, w, h }
+	}
+}
+
+/// RGBA Color
+/// 
+/// Components are usually between 0 and 1, but colors produced by arithmetic (`+`, `*`) are not clamped,
+/// and so may be outside of this range.
+/// 
+/// Colors are opaque (`a = 1`) unless constructed with `with_alpha`.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Color {
 	r: f32,
 	g: f32,
 	b: f32,
+	a: f32,
 }
 impl Color {
-	pub const BLACK : Color = Color { r: 0.0, g: 0.0, b: 0.0 };
-	pub const WHITE : Color = Color { r: 1.0, g: 1.0, b: 1.0 };
+	pub const BLACK : Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+	pub const WHITE : Color = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
 	
-	pub const RED   : Color = Color { r: 1.0, g: 0.0, b: 0.0 };
-	pub const GREEN : Color = Color { r: 0.0, g: 1.0, b: 0.0 };
-	pub const BLUE  : Color = Color { r: 0.0, g: 0.0, b: 1.0 };
+	pub const RED   : Color = Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+	pub const GREEN : Color = Color { r: 0.0, g: 1.0, b: 0.0, a: 1.0 };
+	pub const BLUE  : Color = Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 };
 	
-	pub const YELLOW: Color = Color { r: 1.0, g: 1.0, b: 0.0 };
-	pub const CYAN  : Color = Color { r: 0.0, g: 1.0, b: 1.0 };
-	pub const PINK  : Color = Color { r: 1.0, g: 0.0, b: 1.0 };
+	pub const YELLOW: Color = Color { r: 1.0, g: 1.0, b: 0.0, a: 1.0 };
+	pub const CYAN  : Color = Color { r: 0.0, g: 1.0, b: 1.0, a: 1.0 };
+	pub const PINK  : Color = Color { r: 1.0, g: 0.0, b: 1.0, a: 1.0 };
 	
 	pub fn new(r: f32, g: f32, b: f32) -> Color {
 		Color {
 			r: r,
 			g: g,
 			b: b,
+			a: 1.0,
 		}
 	}
+	/// Returns the color with its alpha set to `a`, where 0 is fully transparent and 1 is opaque.
+	pub fn with_alpha(self, a: f32) -> Color {
+		Color { a, ..self }
+	}
+	/// Gets the alpha of the color, where 0 is fully transparent and 1 is opaque.
+	pub fn alpha(self) -> f32 {
+		self.a
+	}
+	/// Returns true if the color is not fully opaque.
+	pub fn is_transparent(self) -> bool {
+		self.a < 1.0
+	}
 	/// Constructs a new color with `r`, `g` and `b` being the same.
 	pub fn uniform(v: f32) -> Color {
 		Color::new(v, v, v)
 	}
+	/// Constructs a new color from a `0xRRGGBB` value, e.g. `0xFF8800` is orange.
+	/// 
+	/// Any bits above the lowest 24 are ignored.
+	pub fn from_hex(hex: u32) -> Color {
+		let component = |shift: u32| ((hex >> shift) & 0xFF) as f32 / 255.0;
+		Color::new(component(16), component(8), component(0))
+	}
+	/// Parses a color from a hex string, e.g. `"#ff8800"`. The leading `#` is optional.
+	pub fn from_hex_str(s: &str) -> Result<Color, String> {
+		let hex = s.trim();
+		let hex = if hex.starts_with('#') { &hex[1..] } else { hex };
+		if hex.len() != 6 || !hex.chars().all(|c| c.is_digit(16)) {
+			return Err(format!("Invalid hex color: {:?}", s));
+		}
+		u32::from_str_radix(hex, 16)
+			.map(Color::from_hex)
+			.map_err(|e| format!("Invalid hex color: {:?}: {}", s, e))
+	}
+	/// Constructs a new color from a hue in degrees, and a saturation and value from 0 to 1.
+	pub fn from_hsv(h_deg: f32, s: f32, v: f32) -> Color {
+		let h = ((h_deg % 360.0) + 360.0) % 360.0 / 60.0;
+		let c = v * s;
+		let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+		let (r, g, b) = match h as u32 {
+			0 => (c, x, 0.0),
+			1 => (x, c, 0.0),
+			2 => (0.0, c, x),
+			3 => (0.0, x, c),
+			4 => (x, 0.0, c),
+			_ => (c, 0.0, x),
+		};
+		let m = v - c;
+		Color::new(r + m, g + m, b + m)
+	}
+	/// Gets the color-blind-safe replacements for `RED`, `GREEN` and `BLUE`, in that order.
+	/// 
+	/// These are taken from the Okabe-Ito palette, and can be told apart with the common forms of color blindness.
+	pub fn colorblind_safe_palette() -> [Color; 3] {
+		[
+			Color::new(0.835, 0.369, 0.0  ), // Vermillion
+			Color::new(0.0  , 0.620, 0.451), // Bluish green
+			Color::new(0.0  , 0.447, 0.698), // Blue
+		]
+	}
+	/// Remaps `RED`, `GREEN` and `BLUE` to their color-blind-safe replacements. Other colors are returned unchanged.
+	/// 
+	/// The alpha of the color is kept.
+	pub fn to_colorblind_safe(self) -> Color {
+		let palette = Color::colorblind_safe_palette();
+		let opaque = self.with_alpha(1.0);
+		if opaque == Color::RED {
+			palette[0].with_alpha(self.a)
+		} else if opaque == Color::GREEN {
+			palette[1].with_alpha(self.a)
+		} else if opaque == Color::BLUE {
+			palette[2].with_alpha(self.a)
+		} else {
+			self
+		}
+	}
+	/// Linearly interpolates between `self` and `other`, including the alpha. `t = 0` gives `self`, and `t = 1` gives `other`.
+	pub fn lerp(self, other: Color, t: f32) -> Color {
+		let a = self.a + (other.a - self.a) * t;
+		Color::from(util::lerp(self.into(), other.into(), t)).with_alpha(a)
+	}
+	/// Clamps each component of the color to between 0 and 1.
+	pub fn clamp(self) -> Color {
+		let clamp = |v: f32| v.max(0.0).min(1.0);
+		Color::new(clamp(self.r), clamp(self.g), clamp(self.b)).with_alpha(clamp(self.a))
+	}
+	/// Gets the RGB components of the color.
 	pub fn into_array(self) -> [f32; 3] {
 		self.into()
 	}
+	/// Gets the RGBA components of the color.
+	pub fn into_rgba_array(self) -> [f32; 4] {
+		[self.r, self.g, self.b, self.a]
+	}
+	pub fn into_vector4(self) -> Vector4<f32> {
+		Vector4::new(self.r, self.g, self.b, self.a)
+	}
 	pub fn into_vector3(self) -> Vector3<f32> {
 		self.into()
 	}
@@ -175,3 +310,68 @@ impl Into<Vector3<f32>> for Color {
 		Vector3::new(self.r, self.g, self.b)
 	}
 }
+
+/// Adds the RGB components of the colors. The alpha of the left-hand side is kept.
+impl Add for Color {
+	type Output = Color;
+	
+	fn add(self, rhs: Color) -> Color {
+		Color::new(self.r + rhs.r, self.g + rhs.g, self.b + rhs.b).with_alpha(self.a)
+	}
+}
+/// Scales the RGB components of the color. The alpha is kept.
+impl Mul<f32> for Color {
+	type Output = Color;
+	
+	fn mul(self, rhs: f32) -> Color {
+		Color::new(self.r * rhs, self.g * rhs, self.b * rhs).with_alpha(self.a)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn assert_color_close(expected: Color, actual: Color) {
+		let (e, a): ([f32; 3], [f32; 3]) = (expected.into(), actual.into());
+		for i in 0..3 {
+			assert!((e[i] - a[i]).abs() < 1e-6, "expected {:?}, got {:?}", expected, actual);
+		}
+	}
+	
+	#[test]
+	pub fn test_lerp() {
+		let a = Color::new(0.0, 0.2, 1.0);
+		let b = Color::new(1.0, 0.4, 0.0);
+		assert_color_close(a, a.lerp(b, 0.0));
+		assert_color_close(Color::new(0.5, 0.3, 0.5), a.lerp(b, 0.5));
+		assert_color_close(b, a.lerp(b, 1.0));
+		assert_eq!(0.75, Color::RED.lerp(Color::RED.with_alpha(0.5), 0.5).alpha());
+	}
+	
+	#[test]
+	pub fn test_from_hex() {
+		assert_color_close(Color::RED, Color::from_hex(0xFF0000));
+		assert_color_close(Color::new(1.0, 136.0 / 255.0, 0.0), Color::from_hex(0xFF8800));
+		assert_color_close(Color::uniform(128.0 / 255.0), Color::from_hex_str("#808080").unwrap());
+		assert_color_close(Color::CYAN, Color::from_hex_str("00ffff").unwrap());
+		assert!(Color::from_hex_str("#ff88").is_err());
+		assert!(Color::from_hex_str("#gg8800").is_err());
+	}
+	
+	#[test]
+	pub fn test_from_hsv() {
+		assert_color_close(Color::RED, Color::from_hsv(0.0, 1.0, 1.0));
+		assert_color_close(Color::GREEN, Color::from_hsv(120.0, 1.0, 1.0));
+		assert_color_close(Color::BLUE, Color::from_hsv(240.0, 1.0, 1.0));
+		assert_color_close(Color::RED, Color::from_hsv(360.0, 1.0, 1.0));
+		assert_color_close(Color::uniform(0.5), Color::from_hsv(75.0, 0.0, 0.5));
+	}
+	
+	#[test]
+	pub fn test_arithmetic() {
+		assert_color_close(Color::new(1.0, 0.5, 1.5), Color::RED + Color::uniform(0.5) + Color::BLUE);
+		assert_color_close(Color::new(0.5, 0.5, 0.0), Color::YELLOW * 0.5);
+		assert_color_close(Color::new(1.0, 0.5, 1.0), Color::new(1.0, 0.5, 1.5).clamp());
+	}
+}
@@ -7,6 +7,6 @@ mod misc;
 
 pub use self::render::*;
 pub use self::camera::Camera;
-pub use self::mesh::{LitVertex, LitMesh, SimpleVertex, SimpleMesh, RenderableMesh, ColoredMesh, EmptyMesh};
-pub use self::font::FontRender;
-pub use self::misc::{Color, Light, Material};
+pub use self::mesh::{LitVertex, LitMesh, SimpleVertex, SimpleMesh, sphere_detail_for_radius, MAX_SPHERE_DETAIL, RenderableMesh, ColoredMesh, EmptyMesh, LodMesh, InstanceBatch};
+pub use self::font::{FontRender, FontId, DEFAULT_FONT, TextAlign, TextOrigin};
+pub use self::misc::{Color, Light, Material, ScreenRect};
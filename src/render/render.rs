@@ -3,26 +3,35 @@ use std::rc::Rc;
 use std::cell::Ref;
 
 use glium::{
+	Blend, BlendingFunction, LinearBlendingFactor,
 	Depth, DepthTest,
-	PolygonMode, BackfaceCullingMode,
+	PolygonMode, PolygonOffset, BackfaceCullingMode,
 	DrawParameters,
 	Frame,
 	Program,
 	Texture2d,
 	IndexBuffer, VertexBuffer,
+	index::{NoIndices, PrimitiveType},
 	Surface,
+	Vertex,
 	backend::{
 		Facade,
 		glutin::Display,
 	},
-	uniforms::UniformsStorage,
+	framebuffer::SimpleFrameBuffer,
+	texture::{Cubemap, DepthTexture2d},
+	uniforms::{Uniforms, UniformsStorage, UniformValue, MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction},
 };
 use glutin::{Api, ContextBuilder, EventsLoop, GlProfile, GlRequest, GlWindow, Robustness, WindowBuilder, Window};
+use glutin::dpi::LogicalSize;
+
+use na::{self, Orthographic3};
 
 use util;
 use vfs;
+use collision::Aabb;
 use settings::Settings;
-use render::{FontRender, Camera, Color, SimpleVertex, LitVertex, Light, Material};
+use render::{FontRender, FontId, DEFAULT_FONT, TextAlign, TextOrigin, Camera, Color, SimpleVertex, SimpleMesh, LitVertex, Light, Material, ScreenRect};
 
 cfg_if! {
 	if #[cfg(target_os = "windows")] {
@@ -60,7 +69,155 @@ fn focus_window(win: &Window) -> Result<(), ()> {
 }
 
 const SIMPLE_SHADER_NAME: &'static str = "simple";
+const SIMPLE_INSTANCED_SHADER_NAME: &'static str = "simple_instanced";
 const PHONG_SHADER_NAME: &'static str = "phong";
+const SHADOW_SHADER_NAME: &'static str = "shadow";
+const OVERLAY_SHADER_NAME: &'static str = "overlay";
+const SKYBOX_SHADER_NAME: &'static str = "skybox";
+
+/// Gets the vertices of the triangles of a cube from -1 to 1 on each axis, for drawing the skybox on.
+fn skybox_cube() -> Vec<SimpleVertex> {
+	let mut vs = Vec::with_capacity(36);
+	for axis in 0..3 {
+		for &side in [-1.0, 1.0].iter() {
+			let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+			let corner = |a: f32, b: f32| {
+				let mut p = Vector3::zero();
+				p[axis] = side;
+				p[u] = a;
+				p[v] = b;
+				SimpleVertex::from(p)
+			};
+			vs.extend_from_slice(&[
+				corner(-1.0, -1.0), corner(1.0, -1.0), corner(1.0, 1.0),
+				corner(-1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0),
+			]);
+		}
+	}
+	vs
+}
+
+/// The width and height of the shadow map, in pixels.
+const SHADOW_MAP_SIZE: u32 = 2048;
+/// The half-width of the area around the camera that casts shadows, in world units.
+const SHADOW_EXTENT: f32 = 50.0;
+/// How far away from the camera the shadow map is rendered from, in world units.
+const SHADOW_DISTANCE: f32 = 100.0;
+
+/// The depth texture that shadows are rendered into.
+/// 
+/// The shadow map is always `SHADOW_MAP_SIZE`, so it never has to be rebuilt.
+struct ShadowMap {
+	texture: DepthTexture2d,
+}
+impl ShadowMap {
+	fn new(ctx: &Rc<Context>, size: u32) -> Result<ShadowMap, String> {
+		let texture = DepthTexture2d::empty(ctx, size, size)
+			.map_err(|e| format!("Error creating shadow map: {}", e))?;
+		Ok(ShadowMap { texture })
+	}
+	
+	/// Creates a framebuffer that draws into the shadow map.
+	/// 
+	/// This is cheap, as glium caches the OpenGL framebuffer object for each set of attachments.
+	fn framebuffer<'a>(&'a self, ctx: &Rc<Context>) -> Result<SimpleFrameBuffer<'a>, String> {
+		SimpleFrameBuffer::depth_only(ctx, &self.texture)
+			.map_err(|e| format!("Error creating shadow map framebuffer: {}", e))
+	}
+}
+
+/// A vertex of a textured quad drawn in screen space.
+#[derive(Copy, Clone, Debug)]
+struct OverlayVertex {
+	pos: [f32; 2],
+	uv: [f32; 2],
+}
+implement_vertex!(OverlayVertex, pos, uv);
+
+/// The per-instance attributes of a simple mesh drawn with `render_simple_instanced`.
+#[derive(Copy, Clone, Debug)]
+struct SimpleInstance {
+	model: [[f32; 4]; 4],
+	instance_color: [f32; 4],
+}
+implement_vertex!(SimpleInstance, model, instance_color);
+
+/// The maximum number of lights that can light a scene at once.
+/// 
+/// Any lights after the first `MAX_LIGHTS` are ignored. This must match `MAX_LIGHTS` in the phong shader.
+pub const MAX_LIGHTS: usize = 8;
+
+/// The names of the uniforms of a light in the phong shader.
+struct LightUniformNames {
+	pos: String,
+	diffuse: String,
+	specular: String,
+	constant_attenuation: String,
+	linear_attenuation: String,
+	quadratic_attenuation: String,
+	spot_cutoff: String,
+	spot_exponent: String,
+	spot_direction: String,
+}
+impl LightUniformNames {
+	/// Gets the names of the uniforms of the light at index `i` in the light arrays.
+	fn new(i: usize) -> LightUniformNames {
+		LightUniformNames {
+			pos     : format!("light_pos[{}]", i),
+			diffuse : format!("light_diffuse[{}]", i),
+			specular: format!("light_specular[{}]", i),
+			constant_attenuation : format!("light_constant_attenuation[{}]", i),
+			linear_attenuation   : format!("light_linear_attenuation[{}]", i),
+			quadratic_attenuation: format!("light_quadratic_attenuation[{}]", i),
+			spot_cutoff   : format!("light_spot_cutoff[{}]", i),
+			spot_exponent : format!("light_spot_exponent[{}]", i),
+			spot_direction: format!("light_spot_direction[{}]", i),
+		}
+	}
+}
+
+/// Uniforms with an array of lights appended to them.
+struct LightUniforms<'l, U> {
+	uniforms: U,
+	lights: &'l [Light],
+	names: &'l [LightUniformNames],
+}
+impl<'l, U: Uniforms> Uniforms for LightUniforms<'l, U> {
+	fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+		self.uniforms.visit_values(&mut f);
+		f("light_count", UniformValue::SignedInt(self.lights.len() as i32));
+		for (light, names) in self.lights.iter().zip(self.names.iter()) {
+			f(&names.pos     , UniformValue::Vec4(*light.pos.as_ref()));
+			f(&names.diffuse , UniformValue::Vec4(*light.diffuse.as_ref()));
+			f(&names.specular, UniformValue::Vec4(*light.specular.as_ref()));
+			f(&names.constant_attenuation , UniformValue::Float(light.constant_attenuation));
+			f(&names.linear_attenuation   , UniformValue::Float(light.linear_attenuation));
+			f(&names.quadratic_attenuation, UniformValue::Float(light.quadratic_attenuation));
+			f(&names.spot_cutoff   , UniformValue::Float(light.spot_cutoff));
+			f(&names.spot_exponent , UniformValue::Float(light.spot_exponent));
+			f(&names.spot_direction, UniformValue::Vec3(*light.spot_direction.as_ref()));
+		}
+	}
+}
+
+/// The minimum vertical field of view of a perspective projection, in degrees.
+pub const MIN_FOV_DEGREES: f32 = 30.0;
+/// The maximum vertical field of view of a perspective projection, in degrees.
+pub const MAX_FOV_DEGREES: f32 = 120.0;
+
+/// How the scene is projected onto the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProjectionMode {
+	/// A perspective projection with a vertical field of view of `fov_deg` degrees.
+	Perspective { fov_deg: f32 },
+	/// An orthographic projection that shows `height` world units vertically. The width depends on the aspect ratio.
+	Orthographic { height: f32 },
+}
+impl Default for ProjectionMode {
+	fn default() -> ProjectionMode {
+		ProjectionMode::Perspective { fov_deg: 90.0 }
+	}
+}
 
 /// Render handler.
 pub struct Render {
@@ -73,14 +230,60 @@ pub struct Render {
 	
 	/// Projection matrix
 	projection: Matrix4<f32>,
+	projection_mode: ProjectionMode,
+	/// Distance to the near clip plane
+	near: f32,
+	/// Distance to the far clip plane
+	far: f32,
 	camera: Camera,
 	
 	ambient_light: Vector4<f32>,
-	light: Light,
+	lights: Vec<Light>,
+	light_uniform_names: Vec<LightUniformNames>,
 	wireframe_mode: bool,
+	/// The depth bias of the entity currently being drawn, as `(factor, units)`
+	entity_depth_bias: Option<(f32, f32)>,
+	/// If `RED`, `GREEN` and `BLUE` should be drawn with a color-blind-safe palette
+	colorblind_mode: bool,
 	simple_shader: Program,
+	simple_instanced_shader: Program,
 	phong_shader: Program,
+	shadow_shader: Program,
+	overlay_shader: Program,
+	skybox_shader: Program,
+	/// The cubemap drawn behind the scene, if any
+	skybox: Option<Cubemap>,
+	/// A cube around the origin that the skybox is drawn on
+	skybox_vertices: VertexBuffer<SimpleVertex>,
+	/// The color the screen is cleared to at the start of each frame
+	clear_color: Color,
+	/// Depth of the scene from the main light's point of view
+	shadow_map: ShadowMap,
+	/// If shadows should be rendered at all
+	shadows_enabled: bool,
+	/// If lit surfaces are lit in linear space, treating textures and the screen as sRGB
+	srgb: bool,
+	/// If the context waits for vsync when swapping buffers
+	vsync: bool,
+	/// The number of MSAA samples the context was built with
+	msaa_samples: u16,
+	/// The title of the window
+	title: String,
+	/// The transform from world space to the main light's clip space, if the main light casts shadows this frame
+	light_space: Option<Matrix4<f32>>,
+	/// If the draws are currently going to the shadow map
+	shadow_pass: bool,
+	/// If entities outside of the camera's view should be skipped
+	frustum_culling: bool,
+	/// The number of entities culled so far this frame
+	culled: u32,
+	/// The number of entities culled in the last frame
+	last_culled: u32,
 	font_render: FontRender,
+	/// The number of draw calls issued so far this frame
+	draw_calls: u32,
+	/// The number of draw calls issued in the last frame
+	last_draw_calls: u32,
 }
 impl Render {
 	/// Constructs a new `Render` object.
@@ -88,37 +291,47 @@ impl Render {
 	/// In doing so it opens a window, loads the necessary shaders and initializes the font renderer.
 	pub fn new(events_loop: &EventsLoop, camera: Camera, settings: &Settings) -> Result<Render, String> {
 		// Setup window settings
-		let win_builder = WindowBuilder::new()
-			.with_dimensions((settings.w, settings.h).into())
-			.with_title("NEAT")
+		let win_builder = || Render::window_builder((settings.w, settings.h).into(), &settings.title)
 			.with_visibility(false);
 		
-		// Setup OpenGL context settings
-		let ctx_builder = ContextBuilder::new()
-			.with_depth_buffer(8)
-			.with_vsync(settings.vsync)
-			.with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
-			.with_gl_profile(GlProfile::Core)
-			.with_gl_robustness(Robustness::TryRobustLoseContextOnReset);
-		
-		// Build OpenGL window
-		let gl_window = GlWindow::new(win_builder, ctx_builder, &events_loop)
-			.map_err(|e| format!("Error building window: {}", e))?;
+		// Build OpenGL window, without MSAA if the requested number of samples isn't supported
+		let mut msaa_samples = settings.msaa_samples;
+		let gl_window = match GlWindow::new(win_builder(), Render::context_builder(settings.vsync, msaa_samples), &events_loop) {
+			Ok(w) => w,
+			Err(e) if msaa_samples > 0 => {
+				warn!("Could not build window with {}x MSAA, retrying without MSAA: {}", msaa_samples, e);
+				msaa_samples = 0;
+				GlWindow::new(win_builder(), Render::context_builder(settings.vsync, msaa_samples), &events_loop)
+					.map_err(|e| format!("Error building window: {}", e))?
+			},
+			Err(e) => return Err(format!("Error building window: {}", e)),
+		};
 		
 		// Build display
 		let display = Display::from_gl_window(gl_window)
 			.map_err(|e| format!("Error building OpenGL context: {}", e))?;
 		
 		// Build & clear framebuffer
+		let clear_color = Color::BLACK.with_alpha(0.0);
 		let mut frame = display.draw();
-		Render::clear_frame(&mut frame);
+		Render::clear_frame(&mut frame, clear_color);
 		frame.finish().ok();
 		let frame = display.draw();
 		let ctx = display.get_context().clone();
 		
 		// Load shaders
 		let simple_shader = vfs::load_shader(&ctx, SIMPLE_SHADER_NAME);
+		let simple_instanced_shader = vfs::load_shader(&ctx, SIMPLE_INSTANCED_SHADER_NAME);
 		let phong_shader = vfs::load_shader(&ctx, PHONG_SHADER_NAME);
+		let shadow_shader = vfs::load_shader(&ctx, SHADOW_SHADER_NAME);
+		let overlay_shader = vfs::load_shader(&ctx, OVERLAY_SHADER_NAME);
+		let skybox_shader = vfs::load_shader(&ctx, SKYBOX_SHADER_NAME);
+		
+		let skybox_vertices = VertexBuffer::immutable(&ctx, &skybox_cube())
+			.map_err(|e| format!("Error creating skybox vertex buffer: {}", e))?;
+		
+		// Create shadow map
+		let shadow_map = ShadowMap::new(&ctx, SHADOW_MAP_SIZE)?;
 		
 		// Setup font renderer
 		let font_render = FontRender::new(ctx.clone());
@@ -129,37 +342,247 @@ impl Render {
 			frame,
 			
 			projection: Matrix4::one(),
+			projection_mode: ProjectionMode::default(),
+			near: 0.001,
+			far: 1000.0,
 			camera,
 			
 			ambient_light: Vector4::zero(),
-			light: Light::off(),
+			lights: Vec::new(),
+			light_uniform_names: (0..MAX_LIGHTS).map(LightUniformNames::new).collect(),
 			wireframe_mode: false,
+			entity_depth_bias: None,
+			colorblind_mode: false,
 			simple_shader: simple_shader,
+			simple_instanced_shader,
 			phong_shader: phong_shader,
+			shadow_shader,
+			overlay_shader,
+			skybox_shader,
+			skybox: None,
+			skybox_vertices,
+			clear_color,
+			shadow_map,
+			shadows_enabled: settings.shadows,
+			srgb: settings.srgb,
+			vsync: settings.vsync,
+			msaa_samples,
+			title: settings.title.clone(),
+			light_space: None,
+			shadow_pass: false,
+			frustum_culling: settings.frustum_culling,
+			culled: 0,
+			last_culled: 0,
 			font_render: font_render,
+			draw_calls: 0,
+			last_draw_calls: 0,
 		};
-		r.resize();
+		r.set_clip_planes(settings.near_plane, settings.far_plane).ok();
+		r.set_projection_mode(ProjectionMode::Perspective { fov_deg: settings.fov_degrees });
 		Ok(r)
 	}
 	
-	/// Clears the color and depth buffers of `frame`
-	fn clear_frame(frame: &mut Frame) {
-		frame.clear_color(0.0, 0.0, 0.0, 0.0);
+	/// Gets the settings of a window with the inner size `dimensions`, titled `title`.
+	fn window_builder(dimensions: LogicalSize, title: &str) -> WindowBuilder {
+		WindowBuilder::new()
+			.with_dimensions(dimensions)
+			.with_title(title)
+	}
+	
+	/// Gets the settings of an OpenGL context with `msaa_samples` samples per pixel, or no MSAA if it is 0.
+	fn context_builder(vsync: bool, msaa_samples: u16) -> ContextBuilder<'static> {
+		// The sRGB conversion is done in the shaders, so the framebuffer shouldn't convert the output again
+		let builder = ContextBuilder::new()
+			.with_srgb(false)
+			.with_depth_buffer(8)
+			.with_vsync(vsync)
+			.with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+			.with_gl_profile(GlProfile::Core)
+			.with_gl_robustness(Robustness::TryRobustLoseContextOnReset);
+		if msaa_samples > 0 { builder.with_multisampling(msaa_samples) } else { builder }
+	}
+	
+	/// Returns true if the context waits for vsync when swapping buffers.
+	pub fn vsync(&self) -> bool {
+		self.vsync
+	}
+	
+	/// Turns vsync on or off.
+	/// 
+	/// Vsync can't be changed on an existing OpenGL context, so this recreates the window and its context. The new
+	/// context shares its resources with the old one, so meshes, textures and shaders carry on working, but the window
+	/// is reopened at its default position, and anything drawn so far this frame is lost.
+	/// 
+	/// Returns an `Err` if the window could not be recreated.
+	pub fn set_vsync(&mut self, events_loop: &EventsLoop, vsync: bool) -> Result<(), String> {
+		if vsync == self.vsync {
+			return Ok(());
+		}
+		let dimensions = self.window().get_inner_size()
+			.ok_or_else(|| "Error rebuilding window: the window has been closed".to_string())?;
+		
+		self.frame.set_finish().ok();
+		let result = self.display.rebuild(
+			Render::window_builder(dimensions, &self.title),
+			Render::context_builder(vsync, self.msaa_samples),
+			events_loop);
+		self.frame = self.display.draw();
+		Render::clear_frame(&mut self.frame, self.clear_color);
+		result.map_err(|e| format!("Error rebuilding window: {}", e))?;
+		
+		self.vsync = vsync;
+		Ok(())
+	}
+	
+	/// Clears the color buffer of `frame` to `color`, and clears the depth buffer.
+	fn clear_frame(frame: &mut Frame, color: Color) {
+		let c = color.into_rgba_array();
+		frame.clear_color(c[0], c[1], c[2], c[3]);
 		frame.clear_depth(1.0);
 	}
 	
+	/// Sets the color that the screen is cleared to at the start of each frame. (Default = transparent black).
+	/// 
+	/// If there is a skybox, it is drawn over the clear color.
+	pub fn set_clear_color(&mut self, color: Color) {
+		self.clear_color = color;
+	}
+	
+	/// Sets the cubemap that is drawn behind the scene, or `None` to just clear to the clear color.
+	/// 
+	/// See `vfs::load_cubemap`.
+	pub fn set_skybox(&mut self, skybox: Option<Cubemap>) {
+		self.skybox = skybox;
+	}
+	
+	/// Draws the skybox, if there is one. This is done by `render_scene` before the scene is drawn.
+	/// 
+	/// The skybox is drawn at the maximum depth, so anything drawn afterwards appears in front of it.
+	pub fn draw_skybox(&mut self) {
+		let skybox = match self.skybox {
+			Some(ref skybox) => skybox,
+			None => return,
+		};
+		
+		// Remove the translation from the view, so that the skybox is always around the camera
+		let mut view = self.camera.view_matrix();
+		view[(0, 3)] = 0.0;
+		view[(1, 3)] = 0.0;
+		view[(2, 3)] = 0.0;
+		let vp = self.projection * view;
+		
+		self.draw_calls += 1;
+		self.frame.draw(
+			&self.skybox_vertices,
+			NoIndices(PrimitiveType::TrianglesList),
+			&self.skybox_shader,
+			&uniform! {
+				vp    : *vp.as_ref(),
+				skybox: skybox.sampled().magnify_filter(MagnifySamplerFilter::Linear),
+			},
+			&DrawParameters {
+				depth: Depth {
+					test: DepthTest::IfLessOrEqual,
+					write: false,
+					..Default::default()
+				},
+				..Default::default()
+			}
+		).map_err(|e| error!("Draw failed: {:?}", e)).ok();
+	}
+	
 	pub fn set_ambient_light(&mut self, ambient_light: Vector4<f32>) {
 		self.ambient_light = ambient_light;
 	}
 	
+	/// Sets the light of the scene. This replaces all of the current lights.
 	pub fn set_light(&mut self, light: Light) {
-		self.light = light;
+		self.set_lights(vec![light]);
+	}
+	
+	/// Sets the lights of the scene.
+	/// 
+	/// Only the first `MAX_LIGHTS` lights are used, the rest are ignored.
+	pub fn set_lights(&mut self, mut lights: Vec<Light>) {
+		if lights.len() > MAX_LIGHTS {
+			trace!("Ignoring {} lights over the limit of {}", lights.len() - MAX_LIGHTS, MAX_LIGHTS);
+			lights.truncate(MAX_LIGHTS);
+		}
+		self.lights = lights;
 	}
 	
 	pub fn set_wireframe_mode(&mut self, mode: bool) {
 		self.wireframe_mode = mode;
 	}
 	
+	/// Sets the depth bias of the following draws, as `(factor, units)`, or `None` for no bias.
+	/// 
+	/// This is used to resolve flicker between coplanar surfaces of individual entities.
+	pub fn set_entity_depth_bias(&mut self, bias: Option<(f32, f32)>) {
+		self.entity_depth_bias = bias;
+	}
+	
+	/// Sets if the standard `RED`, `GREEN` and `BLUE` colors should be remapped to a color-blind-safe palette
+	/// when drawing simple meshes.
+	/// 
+	/// See `Color::colorblind_safe_palette`.
+	pub fn set_colorblind_mode(&mut self, mode: bool) {
+		self.colorblind_mode = mode;
+	}
+	
+	/// Gets the polygon mode that a draw should use. The global wireframe mode overrides `wireframe`.
+	fn polygon_mode(&self, wireframe: bool) -> PolygonMode {
+		if self.wireframe_mode || wireframe { PolygonMode::Line } else { PolygonMode::Fill }
+	}
+	
+	/// Gets the polygon offset that the next draw should use.
+	fn polygon_offset(&self) -> PolygonOffset {
+		match self.entity_depth_bias {
+			Some((factor, units)) => PolygonOffset {
+				factor,
+				units,
+				point: true,
+				line: true,
+				fill: true,
+			},
+			None => Default::default(),
+		}
+	}
+	
+	/// Sets if the main directional light should cast shadows.
+	pub fn set_shadows_enabled(&mut self, enabled: bool) {
+		self.shadows_enabled = enabled;
+	}
+	
+	pub fn shadows_enabled(&self) -> bool {
+		self.shadows_enabled
+	}
+	
+	/// Sets if lighting is calculated in linear space, treating textures and the screen as sRGB encoded.
+	/// 
+	/// Light and material colors are treated as sRGB encoded too, so toggling this only changes how the lighting is
+	/// blended, not the colors themselves. Unlit meshes drawn with the simple shaders (simple.frag and
+	/// simple_instanced.frag) pass their sRGB vertex colors through unchanged, as they aren't lit.
+	pub fn set_srgb(&mut self, srgb: bool) {
+		self.srgb = srgb;
+	}
+	
+	/// Returns true if lighting is calculated in linear space. See `set_srgb`.
+	pub fn srgb(&self) -> bool {
+		self.srgb
+	}
+	
+	/// Gets the current projection matrix.
+	pub fn projection(&self) -> Matrix4<f32> {
+		self.projection
+	}
+	
+	/// Gets the matrix that transforms from world space to clip space, i.e. the projection matrix multiplied by the
+	/// camera's view matrix.
+	pub fn view_projection(&self) -> Matrix4<f32> {
+		self.projection * self.camera.view_matrix()
+	}
+	
 	pub fn camera(&self) -> &Camera {
 		&self.camera
 	}
@@ -179,27 +602,273 @@ impl Render {
 	/// an error message is returned.
 	pub fn reload_shaders(&mut self) -> Result<(), String> {
 		let simple = vfs::try_load_shader(&self.ctx, SIMPLE_SHADER_NAME)?;
+		let simple_instanced = vfs::try_load_shader(&self.ctx, SIMPLE_INSTANCED_SHADER_NAME)?;
 		let phong  = vfs::try_load_shader(&self.ctx, PHONG_SHADER_NAME)?;
+		let shadow = vfs::try_load_shader(&self.ctx, SHADOW_SHADER_NAME)?;
+		let overlay = vfs::try_load_shader(&self.ctx, OVERLAY_SHADER_NAME)?;
+		let skybox = vfs::try_load_shader(&self.ctx, SKYBOX_SHADER_NAME)?;
 		
 		self.simple_shader = simple;
+		self.simple_instanced_shader = simple_instanced;
 		self.phong_shader = phong;
+		self.shadow_shader = shadow;
+		self.overlay_shader = overlay;
+		self.skybox_shader = skybox;
 		Ok(())
 	}
 	
+	/// Loads the font `name` from the `fonts/` folder, so that text can be drawn with it using `draw_str_font`.
+	pub fn load_font(&mut self, name: &str) -> FontId {
+		self.font_render.load_font(name)
+	}
+	
+	/// Like `load_font`, but returns an `Err` if the font could not be loaded.
+	pub fn try_load_font(&mut self, name: &str) -> Result<FontId, String> {
+		self.font_render.try_load_font(name)
+	}
+	
+	/// Sets where the origin of the coordinates passed to `draw_str` and `draw_str_color` is.
+	pub fn set_text_origin(&mut self, origin: TextOrigin) {
+		self.font_render.set_origin(origin);
+	}
+	
 	/// Draws the `s` on the screen at [`x`, `y`] with pt size `scale` in white.
 	pub fn draw_str(&mut self, s: &str, x: f32, y: f32, scale: f32) {
 		self.draw_str_color(s, x, y, scale, Color::WHITE);
 	}
 	/// Draws the `s` on the screen at [`x`, `y`] with pt size `scale` in `color`.
 	pub fn draw_str_color(&mut self, s: &str, x: f32, y: f32, scale: f32, color: Color) {
+		self.draw_str_font(DEFAULT_FONT, s, x, y, scale, color);
+	}
+	/// Draws the `s` on the screen in `font` at [`x`, `y`] with pt size `scale` in `color`.
+	pub fn draw_str_font(&mut self, font: FontId, s: &str, x: f32, y: f32, scale: f32, color: Color) {
+		let (screen_w, screen_h) = self.frame.get_dimensions();
+		self.font_render.draw_str(&mut self.frame, font, s, x, y, screen_w as f32, screen_h as f32, scale, color);
+	}
+	/// Draws the `s` on the screen at [`x`, `y`] with pt size `scale` in `color`, with each line aligned
+	/// horizontally relative to `x` by `align`.
+	pub fn draw_str_aligned(&mut self, s: &str, x: f32, y: f32, scale: f32, color: Color, align: TextAlign) {
+		let (screen_w, screen_h) = self.frame.get_dimensions();
+		self.font_render.draw_str_aligned(&mut self.frame, DEFAULT_FONT, s, x, y, screen_w as f32, screen_h as f32, scale, color, align);
+	}
+	/// Draws the `s` on the screen at [`x`, `y`] with pt size `scale` in `color`, wrapping lines on whitespace
+	/// so that they are no wider than `max_width` pixels.
+	pub fn draw_str_wrapped(&mut self, s: &str, x: f32, y: f32, max_width: f32, scale: f32, color: Color) {
+		let (screen_w, screen_h) = self.frame.get_dimensions();
+		self.font_render.draw_str_wrapped(&mut self.frame, DEFAULT_FONT, s, x, y, max_width, screen_w as f32, screen_h as f32, scale, color);
+	}
+	
+	/// Gets a rectangle covering the whole screen.
+	pub fn screen_rect(&self) -> ScreenRect {
+		let (w, h) = self.frame.get_dimensions();
+		ScreenRect::new(0.0, 0.0, w as f32, h as f32)
+	}
+	
+	/// Draws `tex` on the screen as a `w` by `h` pixel image, with its top-left corner at [`x`, `y`] pixels from the
+	/// top-left of the screen.
+	/// 
+	/// The texture is alpha-blended over whatever has already been drawn. Useful for HUD images like crosshairs.
+	pub fn draw_texture_2d(&mut self, tex: &Texture2d, x: f32, y: f32, w: f32, h: f32) {
+		self.draw_texture_2d_tinted(tex, x, y, w, h, Color::WHITE);
+	}
+	
+	/// Draws `tex` on the screen like `draw_texture_2d`, multiplied by `tint`. The alpha of `tint` fades the texture.
+	pub fn draw_texture_2d_tinted(&mut self, tex: &Texture2d, x: f32, y: f32, w: f32, h: f32, tint: Color) {
+		self.draw_overlay_texture(tex, ScreenRect::new(x, y, w, h), tint);
+	}
+	
+	/// Draws `texture` stretched over `rect` on the screen, multiplied by `tint`.
+	/// 
+	/// The texture is alpha-blended over whatever has already been drawn, so this should be called after the
+	/// scene and the HUD have been drawn. For example, a vignette can be drawn over `screen_rect()`.
+	pub fn draw_overlay_texture(&mut self, texture: &Texture2d, rect: ScreenRect, tint: Color) {
+		// 0--1
+		// |  |
+		// 2--3
+		let vs = [
+			OverlayVertex { pos: [rect.x         , rect.y         ], uv: [0.0, 0.0] },
+			OverlayVertex { pos: [rect.x + rect.w, rect.y         ], uv: [1.0, 0.0] },
+			OverlayVertex { pos: [rect.x         , rect.y + rect.h], uv: [0.0, 1.0] },
+			OverlayVertex { pos: [rect.x + rect.w, rect.y + rect.h], uv: [1.0, 1.0] },
+		];
+		let vs = match VertexBuffer::immutable(&self.ctx, &vs) {
+			Ok(vs) => vs,
+			Err(e) => {
+				error!("Could not create vertex buffer: {:?}", e);
+				return;
+			},
+		};
+		
+		// Calculate matrix from pixels to normalized device coordinates
+		let (w, h) = self.frame.get_dimensions();
+		let mut mat = Matrix4::one();
+		mat = mat * util::mat4_scale(Vector3::new(1.0, -1.0, 1.0));
+		mat = mat * util::mat4_translation(Vector3::new(-1.0, -1.0, 0.0));
+		mat = mat * util::mat4_scale(Vector3::new(2.0 / w as f32, 2.0 / h as f32, 1.0));
+		
+		self.draw_calls += 1;
+		self.frame.draw(
+			&vs,
+			NoIndices(PrimitiveType::TriangleStrip),
+			&self.overlay_shader,
+			&uniform! {
+				tex : texture,
+				tint: tint.into_rgba_array(),
+				mat : *mat.as_ref(),
+			},
+			&DrawParameters {
+				blend: Blend {
+					color: BlendingFunction::Addition {
+						source:      LinearBlendingFactor::SourceAlpha,
+						destination: LinearBlendingFactor::OneMinusSourceAlpha,
+					},
+					alpha: BlendingFunction::Addition {
+						source:      LinearBlendingFactor::SourceAlpha,
+						destination: LinearBlendingFactor::OneMinusSourceAlpha,
+					},
+					..Default::default()
+				},
+				..Default::default()
+			}
+		).map_err(|e| error!("Draw failed: {:?}", e)).ok();
+	}
+	
+	/// Returns true if any part of `aabb` could be seen by the camera.
+	pub fn is_visible(&self, aabb: Aabb) -> bool {
+		aabb.in_frustum(&self.view_projection())
+	}
+	
+	/// Returns true if something with the bounds `aabb` should be skipped this draw, because frustum culling is on and
+	/// the camera can't see it. Culled objects are counted, see `culled_count`.
+	/// 
+	/// Nothing is culled from the shadow map, as objects that can't be seen can still cast visible shadows.
+	pub fn cull(&mut self, aabb: Aabb) -> bool {
+		if !self.frustum_culling || self.shadow_pass || self.is_visible(aabb) {
+			return false;
+		}
+		self.culled += 1;
+		true
+	}
+	
+	/// Returns true if entities outside of the camera's view are skipped when rendering.
+	pub fn frustum_culling(&self) -> bool {
+		self.frustum_culling
+	}
+	
+	/// Sets if entities outside of the camera's view are skipped when rendering.
+	pub fn set_frustum_culling(&mut self, culling: bool) {
+		self.frustum_culling = culling;
+	}
+	
+	/// Projects a point in the world onto the screen, in pixels relative to the top-left of the screen.
+	/// 
+	/// Returns `None` if the point is behind the camera.
+	pub fn world_to_screen(&self, pos: Vector3<f32>) -> Option<Vector2<f32>> {
+		let clip = self.view_projection() * Vector4::new(pos.x, pos.y, pos.z, 1.0);
+		if clip.w <= 0.0 {
+			return None;
+		}
+		let (w, h) = self.frame.get_dimensions();
+		let ndc = Vector2::new(clip.x / clip.w, clip.y / clip.w);
+		Some(Vector2::new((ndc.x + 1.0) / 2.0 * w as f32, (1.0 - ndc.y) / 2.0 * h as f32))
+	}
+	
+	/// Draws `s` centred above the point `pos` in the world, with pt size `scale` in `color`.
+	/// 
+	/// The text is always facing the camera and is the same size no matter how far away it is.
+	/// Nothing is drawn if the point is behind the camera.
+	pub fn draw_str_world(&mut self, s: &str, pos: Vector3<f32>, scale: f32, color: Color) {
+		if let Some(screen) = self.world_to_screen(pos) {
+			let (w, h) = self.measure_str(s, scale);
+			let origin = self.font_render.origin();
+			self.font_render.set_origin(TextOrigin::TopLeft);
+			self.draw_str_color(s, screen.x - w / 2.0, screen.y - h, scale, color);
+			self.font_render.set_origin(origin);
+		}
+	}
+	
+	/// Measures the size of `s` drawn at pt size `scale`, as `(width, height)` in pixels.
+	pub fn measure_str(&self, s: &str, scale: f32) -> (f32, f32) {
+		self.font_render.measure_str(DEFAULT_FONT, s, scale)
+	}
+	
+	/// Draws `s` on the screen at [`x`, `y`] with pt size `scale` in `text_color`, on top of a panel in `bg_color`.
+	/// 
+	/// The panel has `padding` pixels of space around the text on each side.
+	pub fn draw_str_panel(&mut self, s: &str, x: f32, y: f32, scale: f32, text_color: Color, bg_color: Color, padding: f32) {
 		let (screen_w, screen_h) = self.frame.get_dimensions();
-		self.font_render.draw_str(&mut self.frame, s, x, y, screen_w as f32, screen_h as f32, scale, color);
+		self.font_render.draw_str_panel(&mut self.frame, DEFAULT_FONT, s, x, y, screen_w as f32, screen_h as f32, scale, text_color, bg_color, padding);
 	}
 	
 	/// Resizes the renderer to the current framebuffer's dimensions.
 	pub fn resize(&mut self) {
 		let (w, h) = self.frame.get_dimensions();
-		self.projection = Perspective3::new(w as f32 / h as f32, util::to_rad(90.0), 0.001, 1000.0).to_homogeneous();
+		let aspect = w as f32 / h as f32;
+		self.projection = match self.projection_mode {
+			ProjectionMode::Perspective { fov_deg } => {
+				Perspective3::new(aspect, util::to_rad(fov_deg), self.near, self.far).to_homogeneous()
+			},
+			ProjectionMode::Orthographic { height } => {
+				let (hw, hh) = (height * aspect / 2.0, height / 2.0);
+				Orthographic3::new(-hw, hw, -hh, hh, self.near, self.far).to_homogeneous()
+			},
+		};
+	}
+	
+	/// Gets the distances to the near and far clip planes, as `(near, far)`.
+	pub fn clip_planes(&self) -> (f32, f32) {
+		(self.near, self.far)
+	}
+	
+	/// Sets the distances to the near and far clip planes, and recalculates the projection matrix.
+	/// 
+	/// `near` must be greater than 0, and `far` must be greater than `near`. Otherwise, the clip planes are not
+	/// changed and an error is logged and returned.
+	/// 
+	/// Depth precision depends on the ratio of `far` to `near`, so `near` should be as large as possible.
+	pub fn set_clip_planes(&mut self, near: f32, far: f32) -> Result<(), String> {
+		if !(near > 0.0) || !(far > near) {
+			let e = format!("invalid clip planes: near = {}, far = {}", near, far);
+			error!("Could not set clip planes: {}", e);
+			return Err(e);
+		}
+		self.near = near;
+		self.far = far;
+		self.resize();
+		Ok(())
+	}
+	
+	/// Gets the current projection mode.
+	pub fn projection_mode(&self) -> ProjectionMode {
+		self.projection_mode
+	}
+	
+	/// Sets the projection mode, and recalculates the projection matrix.
+	/// 
+	/// The field of view of a perspective projection is clamped to between `MIN_FOV_DEGREES` and `MAX_FOV_DEGREES`.
+	pub fn set_projection_mode(&mut self, mode: ProjectionMode) {
+		self.projection_mode = match mode {
+			ProjectionMode::Perspective { fov_deg } if fov_deg < MIN_FOV_DEGREES || fov_deg > MAX_FOV_DEGREES => {
+				let clamped = na::clamp(fov_deg, MIN_FOV_DEGREES, MAX_FOV_DEGREES);
+				warn!("Field of view out of range: {} degrees, using {} degrees", fov_deg, clamped);
+				ProjectionMode::Perspective { fov_deg: clamped }
+			},
+			mode => mode,
+		};
+		self.resize();
+	}
+	
+	/// Gets the vertical field of view, in degrees, if the projection is a perspective projection.
+	pub fn fov(&self) -> Option<f32> {
+		match self.projection_mode {
+			ProjectionMode::Perspective { fov_deg } => Some(fov_deg),
+			ProjectionMode::Orthographic { .. } => None,
+		}
+	}
+	
+	/// Switches to a perspective projection with a vertical field of view of `fov_deg` degrees.
+	pub fn set_fov(&mut self, fov_deg: f32) {
+		self.set_projection_mode(ProjectionMode::Perspective { fov_deg });
 	}
 	
 	/// Tries to grab the focus of the window
@@ -238,41 +907,327 @@ impl Render {
 		trace!("Swapping buffers...");
 		self.frame.set_finish().ok();
 		self.frame = self.display.draw();
-		Render::clear_frame(&mut self.frame);
+		Render::clear_frame(&mut self.frame, self.clear_color);
+		self.last_draw_calls = self.draw_calls + self.font_render.take_draw_calls();
+		self.draw_calls = 0;
+		self.last_culled = self.culled;
+		self.culled = 0;
+	}
+	
+	/// Gets the number of entities that were culled in the last frame. See `cull`.
+	pub fn culled_count(&self) -> u32 {
+		self.last_culled
+	}
+	
+	/// Gets the number of draw calls issued in the last frame, including the shadow pass and text.
+	pub fn draw_call_count(&self) -> u32 {
+		self.last_draw_calls
 	}
 	
-	/// Executes all opengl commands in the queue. Use only for debugging purposes.
+	/// Executes all opengl commands in the queue, and waits for them to finish.
+	/// 
+	/// This stalls until the GPU has caught up, so should only be used for debugging, or in low latency mode.
 	pub fn flush(&mut self) {
 		self.ctx.finish();
 	}
 	
+	/// Gets the transform from world space to the main light's clip space, if the main light can cast shadows.
+	/// 
+	/// Only directional lights cast shadows. The shadow map covers the area around the camera.
+	fn calc_light_space(&self) -> Option<Matrix4<f32>> {
+		let light = self.lights.first()?;
+		if light.pos.w != 0.0 {
+			return None;
+		}
+		let dir = Vector3::new(light.pos.x, light.pos.y, light.pos.z);
+		let len = dir.norm();
+		if len == 0.0 {
+			return None;
+		}
+		let dir = dir / len;
+		
+		let target = Point3::from_coordinates(self.camera.pos());
+		let eye = target + dir * SHADOW_DISTANCE;
+		let up = if dir.y.abs() > 0.99 { Vector3::x() } else { Vector3::y() };
+		let view = Matrix4::look_at_rh(&eye, &target, &up);
+		let projection = Orthographic3::new(-SHADOW_EXTENT, SHADOW_EXTENT, -SHADOW_EXTENT, SHADOW_EXTENT, 0.1, SHADOW_DISTANCE * 2.0).to_homogeneous();
+		Some(projection * view)
+	}
+	
+	/// Renders a scene, with shadows if they are enabled.
+	/// 
+	/// `draw` should draw all of the objects in the scene using `render_simple` and `render_lit`. If the main light
+	/// casts shadows it is called twice: once to render the depth of the scene from the light's point of view, and
+	/// then again to render the scene to the screen.
+	pub fn render_scene<F: FnMut(&mut Render)>(&mut self, mut draw: F) {
+		self.light_space = if self.shadows_enabled { self.calc_light_space() } else { None };
+		if self.light_space.is_some() {
+			match self.shadow_map.framebuffer(&self.ctx) {
+				Ok(mut framebuffer) => framebuffer.clear_depth(1.0),
+				Err(e) => {
+					error!("{}", e);
+					self.light_space = None;
+				},
+			}
+		}
+		if self.light_space.is_some() {
+			self.shadow_pass = true;
+			draw(self);
+			self.shadow_pass = false;
+		}
+		self.draw_skybox();
+		draw(self);
+	}
+	
+	/// Draws a list of vertices into the shadow map.
+	fn render_shadow<V: Vertex>(&mut self, vs: &VertexBuffer<V>, is: &IndexBuffer<u16>, model: Matrix4<f32>) {
+		let light_space = match self.light_space {
+			Some(m) => m,
+			None => return,
+		};
+		let mvp = light_space * model;
+		
+		let mut framebuffer = match self.shadow_map.framebuffer(&self.ctx) {
+			Ok(framebuffer) => framebuffer,
+			Err(e) => {
+				error!("{}", e);
+				return;
+			},
+		};
+		self.draw_calls += 1;
+		framebuffer.draw(
+			vs,
+			is,
+			&self.shadow_shader,
+			&uniform! {
+				mvp: *mvp.as_ref(),
+			},
+			&DrawParameters {
+				depth: Depth {
+					test: DepthTest::IfLess,
+					write: true,
+					..Default::default()
+				},
+				backface_culling: BackfaceCullingMode::CullClockwise,
+				..Default::default()
+			}
+		).map_err(|e| error!("Shadow draw failed: {}", e)).ok();
+	}
+	
 	/// Render a simple list of vertices in a specified color.
-	pub fn render_simple(&mut self, vs: &VertexBuffer<SimpleVertex>, is: &IndexBuffer<u16>, model: Matrix4<f32>, col: Color) {
-		let mvp = self.projection * self.camera.view_matrix() * model;
+	/// 
+	/// If the color is transparent, it is alpha blended with what has already been drawn, and doesn't write to the
+	/// depth buffer. Transparent objects are not sorted by depth, so overlapping transparent objects may composite in
+	/// the wrong order. Drawing them after all of the opaque objects gives the best results.
+	/// 
+	/// If `wireframe` is true, only the edges of the triangles are drawn. Everything is drawn in wireframe when the global
+	/// wireframe mode is on.
+	pub fn render_simple(&mut self, vs: &VertexBuffer<SimpleVertex>, is: &IndexBuffer<u16>, model: Matrix4<f32>, col: Color, wireframe: bool) {
+		if self.shadow_pass {
+			self.render_shadow(vs, is, model);
+			return;
+		}
 		
+		let mvp = self.view_projection() * model;
+		let col = if self.colorblind_mode { col.to_colorblind_safe() } else { col };
+		let transparent = col.is_transparent();
+		
+		self.draw_calls += 1;
 		self.frame.draw(
 			vs,
 			is,
 			&self.simple_shader,
 			&uniform! {
 				mvp  : *mvp.as_ref(),
-				color: col.into_array(),
+				color: col.into_rgba_array(),
 			},
 			&DrawParameters {
 				depth: Depth {
 					test: DepthTest::IfLess,
-					write: true,
+					write: !transparent,
 					..Default::default()
 				},
-				polygon_mode: if self.wireframe_mode { PolygonMode::Line } else { PolygonMode::Fill },
+				blend: if transparent {
+					Blend {
+						color: BlendingFunction::Addition {
+							source:      LinearBlendingFactor::SourceAlpha,
+							destination: LinearBlendingFactor::OneMinusSourceAlpha,
+						},
+						alpha: BlendingFunction::Addition {
+							source:      LinearBlendingFactor::SourceAlpha,
+							destination: LinearBlendingFactor::OneMinusSourceAlpha,
+						},
+						..Default::default()
+					}
+				} else {
+					Default::default()
+				},
+				polygon_mode: self.polygon_mode(wireframe),
+				polygon_offset: self.polygon_offset(),
+				backface_culling: BackfaceCullingMode::CullClockwise,
+				..Default::default()
+			}
+		).map_err(|e| error!("Draw failed: {:?}", e)).ok();
+	}
+	
+	/// Renders many copies of `mesh` in one draw call. Each instance has its own model matrix and color.
+	/// 
+	/// This is much faster than calling `render_simple` for each instance when there are lots of them.
+	/// If any of the instances are transparent, they are all blended like in `render_simple`.
+	pub fn render_simple_instanced(&mut self, mesh: &SimpleMesh, instances: &[(Matrix4<f32>, Color)]) {
+		if instances.is_empty() {
+			return;
+		}
+		if self.shadow_pass {
+			for &(model, _) in instances {
+				self.render_shadow(mesh.vertices(), mesh.indices(), model);
+			}
+			return;
+		}
+		
+		let colorblind_mode = self.colorblind_mode;
+		let instance_data: Vec<_> = instances.iter().map(|&(model, col)| {
+			let col = if colorblind_mode { col.to_colorblind_safe() } else { col };
+			SimpleInstance {
+				model: *model.as_ref(),
+				instance_color: col.into_rgba_array(),
+			}
+		}).collect();
+		let transparent = instances.iter().any(|&(_, col)| col.is_transparent());
+		let instance_buffer = match VertexBuffer::immutable(&self.ctx, &instance_data) {
+			Ok(b) => b,
+			Err(e) => {
+				error!("Could not create instance buffer: {:?}", e);
+				return;
+			},
+		};
+		let per_instance = match instance_buffer.per_instance() {
+			Ok(p) => p,
+			Err(e) => {
+				error!("Instancing is not supported: {:?}", e);
+				return;
+			},
+		};
+		let vp = self.view_projection();
+		
+		self.draw_calls += 1;
+		self.frame.draw(
+			(mesh.vertices(), per_instance),
+			mesh.indices(),
+			&self.simple_instanced_shader,
+			&uniform! {
+				vp: *vp.as_ref(),
+			},
+			&DrawParameters {
+				depth: Depth {
+					test: DepthTest::IfLess,
+					write: !transparent,
+					..Default::default()
+				},
+				blend: if transparent {
+					Blend {
+						color: BlendingFunction::Addition {
+							source:      LinearBlendingFactor::SourceAlpha,
+							destination: LinearBlendingFactor::OneMinusSourceAlpha,
+						},
+						alpha: BlendingFunction::Addition {
+							source:      LinearBlendingFactor::SourceAlpha,
+							destination: LinearBlendingFactor::OneMinusSourceAlpha,
+						},
+						..Default::default()
+					}
+				} else {
+					Default::default()
+				},
+				polygon_mode: self.polygon_mode(false),
+				polygon_offset: self.polygon_offset(),
 				backface_culling: BackfaceCullingMode::CullClockwise,
 				..Default::default()
 			}
 		).map_err(|e| error!("Draw failed: {:?}", e)).ok();
 	}
 	
+	/// Draws a line from `a` to `b` in `color`.
+	pub fn draw_line(&mut self, a: Vector3<f32>, b: Vector3<f32>, color: Color) {
+		self.draw_lines(&[(a, b)], color);
+	}
+	
+	/// Draws a list of line segments in `color`.
+	/// 
+	/// The lines are depth tested against the scene, and are not drawn into the shadow map.
+	pub fn draw_lines(&mut self, segments: &[(Vector3<f32>, Vector3<f32>)], color: Color) {
+		if self.shadow_pass || segments.is_empty() {
+			return;
+		}
+		
+		let mut vs = Vec::with_capacity(segments.len() * 2);
+		for &(a, b) in segments {
+			vs.push(SimpleVertex::from(a));
+			vs.push(SimpleVertex::from(b));
+		}
+		let vs = match VertexBuffer::immutable(&self.ctx, &vs) {
+			Ok(vs) => vs,
+			Err(e) => {
+				error!("Could not create vertex buffer: {:?}", e);
+				return;
+			},
+		};
+		
+		let mvp = self.view_projection();
+		let color = if self.colorblind_mode { color.to_colorblind_safe() } else { color };
+		
+		self.draw_calls += 1;
+		self.frame.draw(
+			&vs,
+			NoIndices(PrimitiveType::LinesList),
+			&self.simple_shader,
+			&uniform! {
+				mvp  : *mvp.as_ref(),
+				color: color.into_rgba_array(),
+			},
+			&DrawParameters {
+				depth: Depth {
+					test: DepthTest::IfLess,
+					write: true,
+					..Default::default()
+				},
+				..Default::default()
+			}
+		).map_err(|e| error!("Draw failed: {:?}", e)).ok();
+	}
+	
+	/// Draws the edges of an axis-aligned bounding box in `color`.
+	pub fn draw_aabb(&mut self, aabb: Aabb, color: Color) {
+		let min = aabb.min;
+		let max = aabb.max;
+		let corner = |x: bool, y: bool, z: bool| Vector3::new(
+			if x { max.x } else { min.x },
+			if y { max.y } else { min.y },
+			if z { max.z } else { min.z },
+		);
+		let mut segments = Vec::with_capacity(12);
+		for &a in &[false, true] {
+			for &b in &[false, true] {
+				// Edges along x, y and z
+				segments.push((corner(false, a, b), corner(true, a, b)));
+				segments.push((corner(a, false, b), corner(a, true, b)));
+				segments.push((corner(a, b, false), corner(a, b, true)));
+			}
+		}
+		self.draw_lines(&segments, color);
+	}
+	
 	/// Render a lit, textured surface.
-	pub fn render_lit(&mut self, vs: &VertexBuffer<LitVertex>, is: &IndexBuffer<u16>, model: Matrix4<f32>, texture: &Texture2d, material: &Material) {
+	/// 
+	/// `wrap` is how the texture is sampled outside of the 0 to 1 range of uvs.
+	/// 
+	/// If `wireframe` is true, only the edges of the triangles are drawn.
+	pub fn render_lit(&mut self, vs: &VertexBuffer<LitVertex>, is: &IndexBuffer<u16>, model: Matrix4<f32>, texture: &Texture2d, wrap: SamplerWrapFunction, material: &Material, wireframe: bool) {
+		if self.shadow_pass {
+			self.render_shadow(vs, is, model);
+			return;
+		}
+		
 		let m = model;
 		let v = self.camera.view_matrix();
 		let p = self.projection;
@@ -284,29 +1239,28 @@ impl Render {
 		let uniforms = uniforms.add("model"     , *m.as_ref());
 		let uniforms = uniforms.add("v_inv"     , *v_inv.as_ref());
 		let uniforms = uniforms.add("normal_mat", *util::mat4_upper_left(normal_mat).as_ref());
-		let uniforms = uniforms.add("tex", texture);
+		let uniforms = uniforms.add("tex", texture.sampled().wrap_function(wrap));
 		let uniforms = uniforms.add("ambient", *self.ambient_light.as_ref());
-		/*
-		let light_buf = UniformBuffer::immutable(&self.ctx, [light]);
-		let material_buf = UniformBuffer::immutable(&self.ctx, [material]);
-		
-		let uniforms = uniforms.add("light", light_buf);
-		let uniforms = uniforms.add("material", material_buf);*/
-		let uniforms = uniforms.add("light_pos", *self.light.pos.as_ref());
-		let uniforms = uniforms.add("light_diffuse" , *self.light.diffuse.as_ref());
-		let uniforms = uniforms.add("light_specular", *self.light.specular.as_ref());
-		let uniforms = uniforms.add("light_constant_attenuation" , self.light.constant_attenuation);
-		let uniforms = uniforms.add("light_linear_attenuation"   , self.light.linear_attenuation);
-		let uniforms = uniforms.add("light_quadratic_attenuation", self.light.quadratic_attenuation);
-		let uniforms = uniforms.add("light_spot_cutoff"   , self.light.spot_cutoff);
-		let uniforms = uniforms.add("light_spot_exponent" , self.light.spot_exponent);
-		let uniforms = uniforms.add("light_spot_direction", *self.light.spot_direction.as_ref());
 		
 		let uniforms = uniforms.add("material_ambient"  , *material.ambient.as_ref());
 		let uniforms = uniforms.add("material_diffuse"  , *material.diffuse.as_ref());
 		let uniforms = uniforms.add("material_specular" , *material.specular.as_ref());
 		let uniforms = uniforms.add("material_shininess", material.shininess);
 		
+		let uniforms = uniforms.add("shadows_enabled", self.light_space.is_some());
+		let uniforms = uniforms.add("srgb", self.srgb);
+		let uniforms = uniforms.add("light_space", *self.light_space.unwrap_or(Matrix4::one()).as_ref());
+		let uniforms = uniforms.add("shadow_map", self.shadow_map.texture.sampled()
+			.magnify_filter(MagnifySamplerFilter::Nearest)
+			.minify_filter(MinifySamplerFilter::Nearest));
+		
+		let uniforms = LightUniforms {
+			uniforms,
+			lights: &self.lights,
+			names: &self.light_uniform_names,
+		};
+		
+		self.draw_calls += 1;
 		self.frame.draw(
 			vs,
 			is,
@@ -318,7 +1272,8 @@ impl Render {
 					write: true,
 					..Default::default()
 				},
-				polygon_mode: if self.wireframe_mode { PolygonMode::Line } else { PolygonMode::Fill },
+				polygon_mode: self.polygon_mode(wireframe),
+				polygon_offset: self.polygon_offset(),
 				backface_culling: BackfaceCullingMode::CullClockwise,
 				..Default::default()
 			}
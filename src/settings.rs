@@ -1,29 +1,166 @@
 //! Handles the game settings
 use std::env::args;
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashSet, HashMap};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use glutin::VirtualKeyCode;
 use simplelog::LogLevelFilter;
 
+use game::KeyboardState;
+use toml::{self, value::Table, Value};
+
+/// The default number of physics ticks per second.
+pub const DEFAULT_PHYSICS_HZ: u32 = 120;
+
+macro_rules! key_names {
+	($($key:ident),* $(,)*) => {
+		/// All of the keys that can be named in config files.
+		#[allow(dead_code)]
+		const NAMED_KEYS: &'static [VirtualKeyCode] = &[$(VirtualKeyCode::$key),*];
+		
+		/// Gets a key from its name, as used in config files. The names are the same as the `VirtualKeyCode` variants,
+		/// e.g. `"W"`, `"F1"` or `"LShift"`.
+		pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+			match name {
+				$(stringify!($key) => Some(VirtualKeyCode::$key),)*
+				_ => None,
+			}
+		}
+		
+		/// Gets the name of a key, as used in config files, or `None` if the key can't be named in a config file.
+		pub fn key_name(key: VirtualKeyCode) -> Option<&'static str> {
+			match key {
+				$(VirtualKeyCode::$key => Some(stringify!($key)),)*
+				_ => None,
+			}
+		}
+	}
+}
+
+key_names! {
+	Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+	A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+	Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15,
+	Snapshot, Scroll, Pause, Insert, Home, Delete, End, PageDown, PageUp,
+	Left, Up, Right, Down, Back, Return, Space, Tab, Capital, Numlock,
+	Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+	Add, Subtract, Multiply, Divide, Decimal, NumpadEnter,
+	Apostrophe, Backslash, Comma, Equals, Grave, LBracket, RBracket, Minus, Period, Semicolon, Slash,
+	LAlt, LControl, LShift, LWin, RAlt, RControl, RShift, RWin,
+}
+
+/// An action that can be bound to a key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+	Forward,
+	Backward,
+	Left,
+	Right,
+	Up,
+	Down,
+	Pause,
+	Step,
+	Wireframe,
+}
+impl Action {
+	/// All of the actions.
+	pub const ALL: [Action; 9] = [
+		Action::Forward, Action::Backward, Action::Left, Action::Right, Action::Up, Action::Down,
+		Action::Pause, Action::Step, Action::Wireframe,
+	];
+}
+
 /// Game settings
 pub struct Settings {
 	/// Initial width of the window
 	pub w: u32,
 	/// Initial height of the window
 	pub h: u32,
+	/// The title of the window. (Default = "NEAT")
+	pub title: String,
 	/// If vsync is enabled
 	pub vsync    : bool,
+	/// If low latency mode is enabled. This waits for each frame to be displayed before reading the input for the
+	/// next frame, so that frames don't queue up in the driver, at the cost of some throughput.
+	/// 
+	/// With debug logging enabled, the average time from reading the input to each frame being finished by the GPU
+	/// is logged every second. Measuring this waits for every frame, even with low latency mode off.
+	pub low_latency: bool,
+	/// If shadows are rendered for the main directional light
+	pub shadows  : bool,
+	/// If lighting is calculated in linear space, treating textures and the screen as sRGB encoded. Turning this off
+	/// makes lit surfaces darker in the midtones. (Default = true)
+	pub srgb: bool,
+	/// If entities outside of the camera's view are skipped when rendering. (Default = true)
+	pub frustum_culling: bool,
+	/// The number of samples per pixel used for multisample anti-aliasing, or 0 to disable it. (Default = 0)
+	/// 
+	/// This must be a power of two. Other values are rounded down when the settings are loaded.
+	/// If the window can't be created with this many samples, MSAA is disabled.
+	pub msaa_samples: u16,
+	/// The vertical field of view of the camera, in degrees. This is clamped to between 30 and 120 degrees.
+	pub fov_degrees: f32,
+	/// The distance to the near clip plane, in world units. Must be greater than 0.
+	pub near_plane: f32,
+	/// The distance to the far clip plane, in world units. Must be greater than the near plane.
+	pub far_plane: f32,
+	/// How fast the camera rotates when the mouse is moved. (Default = 1.0)
+	pub mouse_sensitivity: f32,
+	/// If moving the mouse up makes the camera look down
+	pub invert_mouse_y: bool,
+	/// How fast the camera moves, in metres per second. (Default = 4.0)
+	pub move_speed: f32,
+	/// How much faster the camera moves while the sprint key is held. (Default = 3.0)
+	pub sprint_multiplier: f32,
+	/// How far the gamepad sticks have to be pushed from the centre before they do anything, from 0 to 1.
+	/// (Default = 0.15)
+	pub gamepad_deadzone: f32,
+	/// How fast the camera turns when the right gamepad stick is pushed all the way, in pixels of mouse movement per
+	/// second. This is scaled by `mouse_sensitivity` too. (Default = 600.0)
+	pub gamepad_look_speed: f32,
 	/// If the game is currently paused
 	pub paused   : bool,
 	/// If dev mode is on
 	pub dev      : bool,
+	/// How many physics ticks are simulated per second. Must be greater than 0. (Default = 120)
+	pub physics_hz: u32,
+	/// The maximum amount of lag that is caught up on, in milliseconds. If the simulation falls further behind than
+	/// this, the extra time is skipped, so at most `physics_hz * max_lag_ms / 1000` ticks are simulated in one
+	/// frame. (Default = 500)
+	pub max_lag_ms: u32,
+	/// If the frame time statistics are drawn below the FPS counter
+	pub show_stats: bool,
+	/// A stutter is warned about if more than this many physics iterations are needed in one frame
+	pub stutter_warn_iterations: u32,
+	/// The game is only rendered after at least this many physics ticks, which frees up time for the simulation to
+	/// keep up on slow devices. (Default = 1)
+	/// 
+	/// The game is always rendered when it is paused, or when the window needs redrawing. With vsync on, skipped
+	/// frames don't wait for the display, so the simulation can tick more often. The FPS counter only counts the
+	/// frames that are rendered.
+	pub render_every_n_ticks: u32,
+	/// The seed of the RNG used to generate scenes. If `None`, a random seed is chosen and logged, so that the scene
+	/// can be reproduced with `--seed`.
+	pub seed: Option<u64>,
+	/// If set, the input of each frame is recorded to this file, so that it can be replayed with `replay_input`.
+	pub record_input: Option<PathBuf>,
+	/// If set, the input of each frame is read from this file, as recorded with `record_input`, instead of from the
+	/// window. The scene is generated with the recorded seed, and each frame is simulated for the same number of
+	/// physics ticks as when it was recorded, so the simulation plays out exactly the same. The game exits once the
+	/// recording ends.
+	pub replay_input: Option<PathBuf>,
 	/// Where the log file will be located
 	pub log_file : PathBuf,
 	/// The log level for the terminal output
 	pub term_log_level: LogLevelFilter,
 	/// The log level for the file output
 	pub file_log_level: LogLevelFilter,
+	
 	/// Forwards key
 	pub forward  : VirtualKeyCode,
 	/// Backwards key
@@ -36,6 +173,8 @@ pub struct Settings {
 	pub up       : VirtualKeyCode,
 	/// Move down key
 	pub down     : VirtualKeyCode,
+	/// The key to hold to move faster
+	pub sprint   : Option<VirtualKeyCode>,
 	/// The key to pause/resume the simulation
 	pub physics_pause   : Option<VirtualKeyCode>,
 	/// The key to step the simulation
@@ -44,21 +183,82 @@ pub struct Settings {
 	pub wireframe_toggle: Option<VirtualKeyCode>,
 	/// The key to reload the shaders
 	pub reload_shaders  : Option<VirtualKeyCode>,
+	/// The key to reload the textures
+	pub reload_textures : Option<VirtualKeyCode>,
+	/// The key to toggle vsync
+	pub vsync_toggle    : Option<VirtualKeyCode>,
 	/// The key used to reset the simulation
 	pub reset_state     : Option<VirtualKeyCode>,
 }
 impl Settings {
+	/// Gets the key bound to `action`, if there is one.
+	pub fn binding(&self, action: Action) -> Option<VirtualKeyCode> {
+		match action {
+			Action::Forward   => Some(self.forward),
+			Action::Backward  => Some(self.backward),
+			Action::Left      => Some(self.left),
+			Action::Right     => Some(self.right),
+			Action::Up        => Some(self.up),
+			Action::Down      => Some(self.down),
+			Action::Pause     => self.physics_pause,
+			Action::Step      => self.physics_step,
+			Action::Wireframe => self.wireframe_toggle,
+		}
+	}
+	
+	/// Binds `action` to the key `code`, replacing the key it was bound to.
+	pub fn rebind(&mut self, action: Action, code: VirtualKeyCode) {
+		match action {
+			Action::Forward   => self.forward = code,
+			Action::Backward  => self.backward = code,
+			Action::Left      => self.left = code,
+			Action::Right     => self.right = code,
+			Action::Up        => self.up = code,
+			Action::Down      => self.down = code,
+			Action::Pause     => self.physics_pause = Some(code),
+			Action::Step      => self.physics_step = Some(code),
+			Action::Wireframe => self.wireframe_toggle = Some(code),
+		}
+	}
+	
+	/// Returns true if the key bound to `action` is pressed.
+	pub fn is_action_pressed(&self, action: Action, keyboard_state: &KeyboardState) -> bool {
+		self.binding(action).map(|code| keyboard_state.is_pressed(&code)).unwrap_or(false)
+	}
+	
 	/// Gets game settings from args passed to executable.
 	/// 
 	/// # Usage
 	/// - `-v` : Causes the game to be verbose
 	/// - `-p` : The game will start paused.
+	/// - `--width <n>` : Sets the initial width of the window.
+	/// - `--height <n>` : Sets the initial height of the window.
+	/// - `--title <string>` : Sets the title of the window.
+	/// - `--no-vsync` : Disables vsync.
+	/// - `--no-shadows` : Disables shadows.
+	/// - `--no-culling` : Disables frustum culling.
+	/// - `--no-srgb` : Calculates lighting without converting from sRGB to linear space.
+	/// - `--msaa <n>` : Enables anti-aliasing with `n` samples per pixel.
+	/// - `--low-latency` : Enables low latency mode.
+	/// - `--fov <deg>` : Sets the vertical field of view of the camera.
+	/// - `--sensitivity <f>` : Sets the mouse sensitivity.
+	/// - `--invert-y` : Inverts the Y axis of the mouse.
+	/// - `--stats` : Shows the frame time statistics.
+	/// - `--seed <u64>` : Sets the seed used to generate the scene.
+	/// - `--record-input <path>` : Records the input of each frame to a file.
+	/// - `--replay-input <path>` : Replays the input recorded to a file.
+	/// - `--config <path>` : Loads the settings from a config file first. See `from_file`. The other args override
+	///   the settings in the file.
 	pub fn from_args() -> Settings {
 		const LONG_START: &'static str = "--";
 		const SHORT_START: &'static str = "-";
+		// Long args that take a value, as either '--name value' or '--name=value'
+		const VALUE_ARGS: &'static [&'static str] = &["width", "height", "title", "fov", "sensitivity", "seed", "msaa", "config", "record-input", "replay-input"];
 		
 		// Args starting with '--'
 		let mut long_args  = HashSet::<String>::new();
+		// '--fov 70' => {'fov': '70'}
+		let mut value_args = HashMap::<String, String>::new();
 		// '-a' => {'a'}
 		// '-a -b' => {'a', 'b'}
 		// '-ab -c' => {'a', 'b', 'c'}
@@ -66,9 +266,20 @@ impl Settings {
 		// Other args, in order
 		let mut other_args = Vec::<String>::new();
 		
-		for arg in args().skip(1) {
+		let mut args = args().skip(1);
+		while let Some(arg) = args.next() {
 			if arg.starts_with(LONG_START) {
-				long_args.insert((&arg[LONG_START.len()..]).into());
+				let name = &arg[LONG_START.len()..];
+				if let Some(i) = name.find('=') {
+					value_args.insert(name[..i].into(), name[i + 1..].into());
+				} else if VALUE_ARGS.contains(&name) {
+					match args.next() {
+						Some(value) => { value_args.insert(name.into(), value); },
+						None => println!("Missing value for argument: {}{}", LONG_START, name),
+					}
+				} else {
+					long_args.insert(name.into());
+				}
 			} else if arg.starts_with(SHORT_START) {
 				for c in (&arg[SHORT_START.len()..]).chars() {
 					short_args.insert(c);
@@ -80,47 +291,473 @@ impl Settings {
 		
 		println!("short_args: {:?}", short_args);
 		println!("long_args : {:?}", long_args );
+		println!("value_args: {:?}", value_args);
 		println!("other_args: {:?}", other_args);
 		
-		let (term_log_level, file_log_level) = if short_args.contains(&'V') {
-				(LogLevelFilter::Trace, LogLevelFilter::Trace)
-			} else if short_args.contains(&'v') {
-				(LogLevelFilter::Debug, LogLevelFilter::Trace)
-			} else {
-				(<Settings as Default>::default().term_log_level, <Settings as Default>::default().file_log_level)
-			};
+		/// Parses the value of a value arg, if it was given.
+		fn parse_value_arg<T>(value_args: &HashMap<String, String>, name: &str) -> Option<T> where T: FromStr, T::Err: Display {
+			match value_args.get(name).map(|v| v.parse::<T>()) {
+				Some(Ok(value)) => Some(value),
+				Some(Err(e)) => {
+					println!("Invalid value for argument {}{}: {}", LONG_START, name, e);
+					None
+				},
+				None => None,
+			}
+		}
 		
-		Settings {
-			paused   : short_args.contains(&'p'),
-			vsync    : !long_args.contains("no-vsync"),
-			term_log_level: term_log_level,
-			file_log_level: file_log_level,
-			.. Default::default()
+		// The settings in the config file are overridden by the other args
+		let mut settings = match value_args.get("config") {
+			Some(path) => Settings::from_file(Path::new(path)).unwrap_or_else(|e| {
+				println!("{}", e);
+				Settings::default()
+			}),
+			None => Settings::default(),
+		};
+		
+		if short_args.contains(&'V') {
+			settings.term_log_level = LogLevelFilter::Trace;
+			settings.file_log_level = LogLevelFilter::Trace;
+		} else if short_args.contains(&'v') {
+			settings.term_log_level = LogLevelFilter::Debug;
+			settings.file_log_level = LogLevelFilter::Trace;
+		}
+		if short_args.contains(&'p') {
+			settings.paused = true;
+		}
+		match parse_value_arg(&value_args, "width") {
+			Some(0) => println!("Invalid value for argument {}width: must be greater than 0", LONG_START),
+			Some(w) => settings.w = w,
+			None => {},
+		}
+		match parse_value_arg(&value_args, "height") {
+			Some(0) => println!("Invalid value for argument {}height: must be greater than 0", LONG_START),
+			Some(h) => settings.h = h,
+			None => {},
+		}
+		if let Some(title) = value_args.get("title") {
+			settings.title = title.clone();
+		}
+		if long_args.contains("no-vsync") {
+			settings.vsync = false;
+		}
+		if long_args.contains("low-latency") {
+			settings.low_latency = true;
+		}
+		if long_args.contains("no-shadows") {
+			settings.shadows = false;
+		}
+		if long_args.contains("no-culling") {
+			settings.frustum_culling = false;
+		}
+		if long_args.contains("no-srgb") {
+			settings.srgb = false;
+		}
+		if long_args.contains("invert-y") {
+			settings.invert_mouse_y = true;
+		}
+		if long_args.contains("stats") {
+			settings.show_stats = true;
+		}
+		if let Some(fov) = parse_value_arg(&value_args, "fov") {
+			settings.fov_degrees = fov;
 		}
+		if let Some(sensitivity) = parse_value_arg(&value_args, "sensitivity") {
+			settings.mouse_sensitivity = sensitivity;
+		}
+		if let Some(seed) = parse_value_arg(&value_args, "seed") {
+			settings.seed = Some(seed);
+		}
+		if let Some(samples) = parse_value_arg(&value_args, "msaa") {
+			settings.msaa_samples = msaa_samples_or_warn(samples);
+		}
+		if let Some(path) = value_args.get("record-input") {
+			settings.record_input = Some(PathBuf::from(path));
+		}
+		if let Some(path) = value_args.get("replay-input") {
+			settings.replay_input = Some(PathBuf::from(path));
+		}
+		settings
+	}
+	
+	/// Loads the settings from the TOML file at `path`.
+	/// 
+	/// The keys in the file have the same names as the fields of `Settings`. The key bindings are in a `[keys]` table,
+	/// with the names given by `key_name`, e.g. `forward = "W"`. Optional key bindings can be disabled with `"None"`.
+	/// Missing keys are given their default values.
+	/// 
+	/// ```toml
+	/// w = 1280
+	/// h = 720
+	/// fov_degrees = 75.0
+	/// term_log_level = "debug"
+	/// 
+	/// [keys]
+	/// forward = "Up"
+	/// physics_pause = "P"
+	/// ```
+	/// 
+	/// Returns an `Err` if the file could not be read, or it contains invalid settings.
+	pub fn from_file(path: &Path) -> Result<Settings, String> {
+		fn inner_try(path: &Path) -> Result<Settings, String> {
+			let mut src = String::new();
+			File::open(path)
+				.and_then(|mut f| f.read_to_string(&mut src))
+				.map_err(|e| format!("unreadable file '{}': {}", path.display(), e))?;
+			let value = src.parse::<Value>().map_err(|e| format!("invalid TOML: {}", e))?;
+			let table = value.as_table().ok_or_else(|| "expected a table".to_string())?;
+			
+			let mut s = Settings::default();
+			read_value(table, "w", &mut s.w)?;
+			read_value(table, "h", &mut s.h)?;
+			read_value(table, "title", &mut s.title)?;
+			if s.w == 0 || s.h == 0 {
+				return Err(format!("invalid window size {}x{}: must be greater than 0", s.w, s.h));
+			}
+			read_value(table, "vsync", &mut s.vsync)?;
+			read_value(table, "low_latency", &mut s.low_latency)?;
+			read_value(table, "shadows", &mut s.shadows)?;
+			read_value(table, "srgb", &mut s.srgb)?;
+			read_value(table, "frustum_culling", &mut s.frustum_culling)?;
+			read_value(table, "msaa_samples", &mut s.msaa_samples)?;
+			s.msaa_samples = msaa_samples_or_warn(s.msaa_samples);
+			read_value(table, "fov_degrees", &mut s.fov_degrees)?;
+			read_value(table, "near_plane", &mut s.near_plane)?;
+			read_value(table, "far_plane", &mut s.far_plane)?;
+			read_value(table, "mouse_sensitivity", &mut s.mouse_sensitivity)?;
+			read_value(table, "invert_mouse_y", &mut s.invert_mouse_y)?;
+			read_value(table, "move_speed", &mut s.move_speed)?;
+			read_value(table, "sprint_multiplier", &mut s.sprint_multiplier)?;
+			read_value(table, "gamepad_deadzone", &mut s.gamepad_deadzone)?;
+			read_value(table, "gamepad_look_speed", &mut s.gamepad_look_speed)?;
+			read_value(table, "paused", &mut s.paused)?;
+			read_value(table, "dev", &mut s.dev)?;
+			read_value(table, "physics_hz", &mut s.physics_hz)?;
+			read_value(table, "max_lag_ms", &mut s.max_lag_ms)?;
+			read_value(table, "show_stats", &mut s.show_stats)?;
+			read_value(table, "stutter_warn_iterations", &mut s.stutter_warn_iterations)?;
+			read_value(table, "render_every_n_ticks", &mut s.render_every_n_ticks)?;
+			read_value(table, "seed", &mut s.seed)?;
+			read_value(table, "record_input", &mut s.record_input)?;
+			read_value(table, "replay_input", &mut s.replay_input)?;
+			read_value(table, "log_file", &mut s.log_file)?;
+			read_value(table, "term_log_level", &mut s.term_log_level)?;
+			read_value(table, "file_log_level", &mut s.file_log_level)?;
+			
+			if let Some(keys) = table.get("keys") {
+				let keys = keys.as_table().ok_or_else(|| "invalid value for 'keys': expected a table".to_string())?;
+				read_key(keys, "forward", &mut s.forward)?;
+				read_key(keys, "backward", &mut s.backward)?;
+				read_key(keys, "left", &mut s.left)?;
+				read_key(keys, "right", &mut s.right)?;
+				read_key(keys, "up", &mut s.up)?;
+				read_key(keys, "down", &mut s.down)?;
+				read_optional_key(keys, "sprint", &mut s.sprint)?;
+				read_optional_key(keys, "physics_pause", &mut s.physics_pause)?;
+				read_optional_key(keys, "physics_step", &mut s.physics_step)?;
+				read_optional_key(keys, "wireframe_toggle", &mut s.wireframe_toggle)?;
+				read_optional_key(keys, "reload_shaders", &mut s.reload_shaders)?;
+				read_optional_key(keys, "reload_textures", &mut s.reload_textures)?;
+				read_optional_key(keys, "vsync_toggle", &mut s.vsync_toggle)?;
+				read_optional_key(keys, "reset_state", &mut s.reset_state)?;
+			}
+			Ok(s)
+		}
+		inner_try(path).map_err(|e| format!("could not load settings from '{}': {}", path.display(), e))
+	}
+	
+	/// Saves the settings to a TOML file at `path`, in the format read by `from_file`.
+	/// 
+	/// Returns an `Err` if the file could not be written, or a key is bound that can't be named in a config file.
+	pub fn save(&self, path: &Path) -> Result<(), String> {
+		fn inner_try(settings: &Settings, path: &Path) -> Result<(), String> {
+			let src = settings.to_toml()?;
+			File::create(path)
+				.and_then(|f| BufWriter::new(f).write_all(src.as_bytes()))
+				.map_err(|e| format!("unwritable file '{}': {}", path.display(), e))
+		}
+		inner_try(self, path).map_err(|e| format!("could not save settings to '{}': {}", path.display(), e))
+	}
+	
+	/// Serializes the settings to TOML.
+	fn to_toml(&self) -> Result<String, String> {
+		fn level(level: LogLevelFilter) -> Value {
+			Value::String(level.to_string().to_lowercase())
+		}
+		fn key(name: &str, key: VirtualKeyCode) -> Result<Value, String> {
+			key_name(key)
+				.map(|k| Value::String(k.into()))
+				.ok_or_else(|| format!("key for '{}' can't be saved: {:?}", name, key))
+		}
+		fn optional_key(name: &str, k: Option<VirtualKeyCode>) -> Result<Value, String> {
+			match k {
+				Some(k) => key(name, k),
+				None => Ok(Value::String("None".into())),
+			}
+		}
+		
+		let mut table = Table::new();
+		table.insert("w".into(), Value::Integer(self.w as i64));
+		table.insert("h".into(), Value::Integer(self.h as i64));
+		table.insert("title".into(), Value::String(self.title.clone()));
+		table.insert("vsync".into(), Value::Boolean(self.vsync));
+		table.insert("low_latency".into(), Value::Boolean(self.low_latency));
+		table.insert("shadows".into(), Value::Boolean(self.shadows));
+		table.insert("srgb".into(), Value::Boolean(self.srgb));
+		table.insert("frustum_culling".into(), Value::Boolean(self.frustum_culling));
+		table.insert("msaa_samples".into(), Value::Integer(self.msaa_samples as i64));
+		table.insert("fov_degrees".into(), Value::Float(self.fov_degrees as f64));
+		table.insert("near_plane".into(), Value::Float(self.near_plane as f64));
+		table.insert("far_plane".into(), Value::Float(self.far_plane as f64));
+		table.insert("mouse_sensitivity".into(), Value::Float(self.mouse_sensitivity as f64));
+		table.insert("invert_mouse_y".into(), Value::Boolean(self.invert_mouse_y));
+		table.insert("move_speed".into(), Value::Float(self.move_speed as f64));
+		table.insert("sprint_multiplier".into(), Value::Float(self.sprint_multiplier as f64));
+		table.insert("gamepad_deadzone".into(), Value::Float(self.gamepad_deadzone as f64));
+		table.insert("gamepad_look_speed".into(), Value::Float(self.gamepad_look_speed as f64));
+		table.insert("paused".into(), Value::Boolean(self.paused));
+		table.insert("dev".into(), Value::Boolean(self.dev));
+		table.insert("physics_hz".into(), Value::Integer(self.physics_hz as i64));
+		table.insert("max_lag_ms".into(), Value::Integer(self.max_lag_ms as i64));
+		table.insert("show_stats".into(), Value::Boolean(self.show_stats));
+		table.insert("stutter_warn_iterations".into(), Value::Integer(self.stutter_warn_iterations as i64));
+		table.insert("render_every_n_ticks".into(), Value::Integer(self.render_every_n_ticks as i64));
+		if let Some(seed) = self.seed {
+			// TOML integers are signed, so large seeds are stored as their two's complement
+			table.insert("seed".into(), Value::Integer(seed as i64));
+		}
+		if let Some(ref path) = self.record_input {
+			table.insert("record_input".into(), Value::String(path.to_string_lossy().into_owned()));
+		}
+		if let Some(ref path) = self.replay_input {
+			table.insert("replay_input".into(), Value::String(path.to_string_lossy().into_owned()));
+		}
+		table.insert("log_file".into(), Value::String(self.log_file.to_string_lossy().into_owned()));
+		table.insert("term_log_level".into(), level(self.term_log_level));
+		table.insert("file_log_level".into(), level(self.file_log_level));
+		
+		let mut keys = Table::new();
+		keys.insert("forward".into(), key("forward", self.forward)?);
+		keys.insert("backward".into(), key("backward", self.backward)?);
+		keys.insert("left".into(), key("left", self.left)?);
+		keys.insert("right".into(), key("right", self.right)?);
+		keys.insert("up".into(), key("up", self.up)?);
+		keys.insert("down".into(), key("down", self.down)?);
+		keys.insert("sprint".into(), optional_key("sprint", self.sprint)?);
+		keys.insert("physics_pause".into(), optional_key("physics_pause", self.physics_pause)?);
+		keys.insert("physics_step".into(), optional_key("physics_step", self.physics_step)?);
+		keys.insert("wireframe_toggle".into(), optional_key("wireframe_toggle", self.wireframe_toggle)?);
+		keys.insert("reload_shaders".into(), optional_key("reload_shaders", self.reload_shaders)?);
+		keys.insert("reload_textures".into(), optional_key("reload_textures", self.reload_textures)?);
+		keys.insert("vsync_toggle".into(), optional_key("vsync_toggle", self.vsync_toggle)?);
+		keys.insert("reset_state".into(), optional_key("reset_state", self.reset_state)?);
+		table.insert("keys".into(), Value::Table(keys));
+		
+		toml::to_string(&Value::Table(table)).map_err(|e| format!("could not serialize settings: {}", e))
+	}
+}
+
+/// A value that can be read from a config file.
+trait ConfigValue: Sized {
+	fn from_toml(value: &Value) -> Option<Self>;
+}
+impl ConfigValue for bool {
+	fn from_toml(value: &Value) -> Option<bool> {
+		value.as_bool()
+	}
+}
+impl ConfigValue for u32 {
+	fn from_toml(value: &Value) -> Option<u32> {
+		value.as_integer().and_then(|i| if i >= 0 && i <= u32::max_value() as i64 { Some(i as u32) } else { None })
+	}
+}
+impl ConfigValue for u16 {
+	fn from_toml(value: &Value) -> Option<u16> {
+		value.as_integer().and_then(|i| if i >= 0 && i <= u16::max_value() as i64 { Some(i as u16) } else { None })
 	}
 }
+impl ConfigValue for u64 {
+	fn from_toml(value: &Value) -> Option<u64> {
+		value.as_integer().map(|i| i as u64)
+	}
+}
+impl<T: ConfigValue> ConfigValue for Option<T> {
+	fn from_toml(value: &Value) -> Option<Option<T>> {
+		T::from_toml(value).map(Some)
+	}
+}
+impl ConfigValue for f32 {
+	fn from_toml(value: &Value) -> Option<f32> {
+		value.as_float().or_else(|| value.as_integer().map(|i| i as f64)).map(|f| f as f32)
+	}
+}
+impl ConfigValue for String {
+	fn from_toml(value: &Value) -> Option<String> {
+		value.as_str().map(String::from)
+	}
+}
+impl ConfigValue for PathBuf {
+	fn from_toml(value: &Value) -> Option<PathBuf> {
+		value.as_str().map(PathBuf::from)
+	}
+}
+impl ConfigValue for LogLevelFilter {
+	fn from_toml(value: &Value) -> Option<LogLevelFilter> {
+		value.as_str().and_then(|s| s.parse().ok())
+	}
+}
+
+/// Rounds `samples` down to a power of two, as MSAA can only use a power of two samples per pixel, and warns if it had
+/// to be rounded. 0 stays as 0, which disables MSAA.
+fn msaa_samples_or_warn(samples: u16) -> u16 {
+	let rounded = if samples == 0 { 0 } else { 1 << (15 - samples.leading_zeros()) };
+	if rounded != samples {
+		println!("Invalid number of MSAA samples: {} is not a power of two, using {} instead", samples, rounded);
+	}
+	rounded
+}
+
+/// Reads the value of `name` from `table` into `value`, if it is in the table.
+fn read_value<T: ConfigValue>(table: &Table, name: &str, value: &mut T) -> Result<(), String> {
+	if let Some(v) = table.get(name) {
+		*value = T::from_toml(v).ok_or_else(|| format!("invalid value for '{}': {}", name, v))?;
+	}
+	Ok(())
+}
+
+/// Reads the key binding `name` from `table` into `key`, if it is in the table.
+fn read_key(table: &Table, name: &str, key: &mut VirtualKeyCode) -> Result<(), String> {
+	if let Some(v) = table.get(name) {
+		*key = v.as_str().and_then(key_from_name)
+			.ok_or_else(|| format!("invalid key for '{}': {}", name, v))?;
+	}
+	Ok(())
+}
+
+/// Reads the optional key binding `name` from `table` into `key`, if it is in the table.
+fn read_optional_key(table: &Table, name: &str, key: &mut Option<VirtualKeyCode>) -> Result<(), String> {
+	match table.get(name) {
+		Some(&Value::String(ref s)) if s == "None" => *key = None,
+		Some(v) => {
+			*key = Some(v.as_str().and_then(key_from_name)
+				.ok_or_else(|| format!("invalid key for '{}': {}", name, v))?);
+		},
+		None => {},
+	}
+	Ok(())
+}
 impl Default for Settings {
 	fn default() -> Settings {
 		Settings {
 			w: 800,
 			h: 600,
+			title: "NEAT".into(),
 			vsync    : true,
+			low_latency: false,
+			shadows  : true,
+			srgb: true,
+			frustum_culling: true,
+			msaa_samples: 0,
+			fov_degrees: 90.0,
+			near_plane: 0.001,
+			far_plane: 1000.0,
+			mouse_sensitivity: 1.0,
+			invert_mouse_y: false,
+			move_speed: 4.0,
+			sprint_multiplier: 3.0,
+			gamepad_deadzone: 0.15,
+			gamepad_look_speed: 600.0,
 			paused   : false,
 			dev      : true,
+			physics_hz: DEFAULT_PHYSICS_HZ,
+			max_lag_ms: 500,
+			show_stats: false,
+			stutter_warn_iterations: 4,
+			render_every_n_ticks: 1,
+			seed: None,
+			record_input: None,
+			replay_input: None,
 			log_file : PathBuf::from("log.txt"),
 			term_log_level: LogLevelFilter::Info,
 			file_log_level: LogLevelFilter::Debug,
+			
 			forward  : VirtualKeyCode::W,
 			backward : VirtualKeyCode::S,
 			left     : VirtualKeyCode::A,
 			right    : VirtualKeyCode::D,
 			up       : VirtualKeyCode::Q,
 			down     : VirtualKeyCode::E,
+			sprint   : Some(VirtualKeyCode::LShift),
 			physics_pause   : Some(VirtualKeyCode::F1),
 			physics_step    : Some(VirtualKeyCode::F2),
 			wireframe_toggle: Some(VirtualKeyCode::F3),
 			reload_shaders  : Some(VirtualKeyCode::F4),
 			reset_state     : Some(VirtualKeyCode::F5),
+			reload_textures : Some(VirtualKeyCode::F6),
+			vsync_toggle    : Some(VirtualKeyCode::F7),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	
+	#[test]
+	pub fn test_key_names_round_trip() {
+		for &key in NAMED_KEYS.iter() {
+			let name = key_name(key).unwrap();
+			assert_eq!(Some(key), key_from_name(name));
 		}
+		assert_eq!(None, key_from_name("NotAKey"));
+	}
+	
+	#[test]
+	pub fn test_save_then_load_is_idempotent() {
+		let mut settings = Settings::default();
+		settings.w = 1280;
+		settings.title = "Test".into();
+		settings.fov_degrees = 75.0;
+		settings.near_plane = 0.01;
+		settings.term_log_level = LogLevelFilter::Trace;
+		settings.forward = VirtualKeyCode::Up;
+		settings.physics_pause = None;
+		
+		let path = env::temp_dir().join("neat_test_settings.toml");
+		settings.save(&path).unwrap();
+		let loaded = Settings::from_file(&path).unwrap();
+		assert_eq!(settings.to_toml().unwrap(), loaded.to_toml().unwrap());
+		assert_eq!(1280, loaded.w);
+		assert_eq!("Test", loaded.title);
+		assert_eq!(0.01, loaded.near_plane);
+		assert_eq!(VirtualKeyCode::Up, loaded.forward);
+		assert_eq!(None, loaded.physics_pause);
+		
+		// Saving the loaded settings again gives the same file
+		loaded.save(&path).unwrap();
+		let reloaded = Settings::from_file(&path).unwrap();
+		assert_eq!(loaded.to_toml().unwrap(), reloaded.to_toml().unwrap());
+	}
+	
+	#[test]
+	pub fn test_zero_window_size_is_invalid() {
+		let path = env::temp_dir().join("neat_test_settings_zero_size.toml");
+		File::create(&path).and_then(|mut f| f.write_all(b"w = 0\n")).unwrap();
+		assert!(Settings::from_file(&path).is_err());
+	}
+	
+	#[test]
+	pub fn test_msaa_samples_are_rounded_down() {
+		assert_eq!(0, msaa_samples_or_warn(0));
+		assert_eq!(1, msaa_samples_or_warn(1));
+		assert_eq!(4, msaa_samples_or_warn(4));
+		assert_eq!(4, msaa_samples_or_warn(6));
+		assert_eq!(8, msaa_samples_or_warn(15));
+		assert_eq!(32768, msaa_samples_or_warn(u16::max_value()));
+		
+		let path = env::temp_dir().join("neat_test_settings_msaa.toml");
+		File::create(&path).and_then(|mut f| f.write_all(b"msaa_samples = 3\n")).unwrap();
+		assert_eq!(2, Settings::from_file(&path).unwrap().msaa_samples);
 	}
 }
@@ -1,5 +1,46 @@
 //! Utility functions
 use prelude::*;
+use std::cell::RefCell;
+use std::process::exit;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use render::LitVertex;
+
+thread_local! {
+	/// The RNG used to generate scenes.
+	static SCENE_RNG: RefCell<StdRng> = RefCell::new(StdRng::from_seed([0; 32]));
+}
+
+/// Unwraps `result`, or logs the error and exits if it is an `Err`.
+/// 
+/// This is used by the functions that exit on failure, which wrap `try_` functions that return the error instead.
+pub fn exit_on_err<T>(result: Result<T, String>) -> T {
+	match result {
+		Ok(value) => value,
+		Err(e) => {
+			error!("{}", e);
+			exit(1);
+		}
+	}
+}
+
+/// Seeds the RNG used to generate scenes. Generating a scene after seeding with the same seed gives the same scene.
+pub fn seed_scene_rng(seed: u64) {
+	let mut bytes = [0; 32];
+	for (i, b) in bytes.iter_mut().take(8).enumerate() {
+		*b = (seed >> (i * 8)) as u8;
+	}
+	SCENE_RNG.with(|rng| *rng.borrow_mut() = StdRng::from_seed(bytes));
+}
+
+/// Calls `f` with the RNG used to generate scenes. See `seed_scene_rng`.
+/// 
+/// Scene generators should use this rather than `rand::thread_rng`, so that scenes can be reproduced.
+pub fn with_scene_rng<F, T>(f: F) -> T where F: FnOnce(&mut StdRng) -> T {
+	SCENE_RNG.with(|rng| f(&mut *rng.borrow_mut()))
+}
 
 /// Linearly interpolate `a` and `b`.
 pub fn lerp(a: Vector3<f32>, b: Vector3<f32>, s: f32) -> Vector3<f32> {
@@ -40,6 +81,38 @@ pub fn mat4_upper_left(v: Matrix4<f32>) -> Matrix3<f32> {
 		)
 }
 
+/// Replaces the normals of `vertices` with smooth normals, for the triangles in `indices`.
+/// 
+/// The normal of each vertex is the average of the face normals of the triangles that use it, weighted by the area
+/// of each triangle. The triangles should be in counter-clockwise order when looking at their front faces.
+/// Vertices that aren't used by any triangle, or are only used by degenerate ones, are given a zero normal.
+/// 
+/// Only triangles that share vertices are smoothed together, so vertices should be shared between the faces that
+/// should look smooth.
+/// 
+/// Panics if an index is out of range of `vertices`.
+pub fn compute_smooth_normals(vertices: &mut [LitVertex], indices: &[u16]) {
+	for v in vertices.iter_mut() {
+		v.set_normal(Vector3::zero());
+	}
+	for tri in indices.chunks(3) {
+		if tri.len() < 3 {
+			break;
+		}
+		let (a, b, c) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+		// The length of the cross product is twice the area of the triangle
+		let face_normal = (vertices[b].pos() - vertices[a].pos()).cross(&(vertices[c].pos() - vertices[a].pos()));
+		for &i in &[a, b, c] {
+			let normal = vertices[i].normal() + face_normal;
+			vertices[i].set_normal(normal);
+		}
+	}
+	for v in vertices.iter_mut() {
+		let normal = v.normal().try_normalize(1.0e-12).unwrap_or_else(Vector3::zero);
+		v.set_normal(normal);
+	}
+}
+
 /// Converts a 4x4 matrix into a human-readable string.
 #[allow(dead_code)]
 fn mat4_to_string(m: Matrix4<f32>) -> String {
@@ -72,4 +145,28 @@ mod tests {
 		let ret = Vector3::new(ret.x, ret.y, ret.z) * ret.w;
 		assert_eq!(Vector3::new(2.0, 8.0, -97.0), ret);
 	}
+	
+	#[test]
+	pub fn test_compute_smooth_normals() {
+		// A regular tetrahedron centred on the origin, so the smooth normal of each vertex points away from the centre
+		let corners = [
+			Vector3::new( 1.0,  1.0,  1.0),
+			Vector3::new( 1.0, -1.0, -1.0),
+			Vector3::new(-1.0,  1.0, -1.0),
+			Vector3::new(-1.0, -1.0,  1.0),
+		];
+		let mut vs: Vec<LitVertex> = corners.iter()
+			.map(|&p| LitVertex::new(p, Vector3::new(0.0, 1.0, 0.0), Vector2::zero()))
+			.collect();
+		// Unused vertex
+		vs.push(LitVertex::new(Vector3::new(5.0, 5.0, 5.0), Vector3::new(0.0, 1.0, 0.0), Vector2::zero()));
+		let is = [0, 1, 2, 0, 3, 1, 0, 2, 3, 1, 3, 2];
+		compute_smooth_normals(&mut vs, &is);
+		
+		for (v, p) in vs.iter().zip(corners.iter()) {
+			let expected = p.normalize();
+			assert!((v.normal() - expected).norm() < 1e-5, "{:?} != {:?}", v.normal(), expected);
+		}
+		assert_eq!(Vector3::zero(), vs[4].normal());
+	}
 }
@@ -1,6 +1,6 @@
 //! Virtual File System.
 //!
-//! Handles the loading of shaders, textures and fonts.
+//! Handles the loading of shaders, textures, fonts and models.
 use prelude::*;
 use std::io::prelude::*;
 use std::io;
@@ -8,19 +8,52 @@ use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::process::exit;
 use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
 use glium::*;
-use glium::texture::RawImage2d;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::program::SourceCode;
+use glium::texture::{RawImage2d, Cubemap, CubeLayer};
+use glium::uniforms::MagnifySamplerFilter;
 use rusttype::{Font, FontCollection};
 use image::{self, DynamicImage, ConvertBuffer};
 
+use render::{LitMesh, Material};
+
+/// The environment variable that overrides the base directory.
+pub const ASSETS_DIR_VAR: &str = "NEAT_ASSETS_DIR";
+
+thread_local! {
+	/// The base directory set by `set_base_dir`.
+	static BASE_DIR: RefCell<Option<PathBuf>> = RefCell::new(None);
+}
+
+/// Sets the base directory for all of the vfs operations on this thread, overriding `NEAT_ASSETS_DIR`.
+/// 
+/// This is useful when embedding the engine, or running tests without copying the assets next to the executable.
+pub fn set_base_dir<P: Into<PathBuf>>(path: P) {
+	BASE_DIR.with(|dir| *dir.borrow_mut() = Some(path.into()));
+}
+
 /// Gets the base directory for all of the vfs operations.
+/// 
+/// This is the directory set by `set_base_dir`, or else the `NEAT_ASSETS_DIR` environment variable, or else the
+/// `assets/` folder next to the current executable.
 fn try_get_base_dir() -> Result<PathBuf, String> {
-	let mut path = ::std::env::current_exe()
-		.map_err(|e| format!("unable to locate current executable: {}", e))?;
-	
-	path.pop();
-	path.push("assets");
+	let path = match BASE_DIR.with(|dir| dir.borrow().clone()) {
+		Some(path) => path,
+		None => match ::std::env::var_os(ASSETS_DIR_VAR) {
+			Some(path) => PathBuf::from(path),
+			None => {
+				let mut path = ::std::env::current_exe()
+					.map_err(|e| format!("unable to locate current executable: {}", e))?;
+				path.pop();
+				path.push("assets");
+				path
+			},
+		},
+	};
 	assert_is_dir(&path)?;
 	Ok(path)
 }
@@ -69,6 +102,98 @@ fn try_read_file_string<P: AsRef<Path>>(path: P) -> Result<String, String> {
 		.map_err(|e| format!("unreadable file '{}': {}", path.display(), e))
 }
 
+/// Gets the copy of the asset at `path` (relative to the base directory) that is embedded in the binary, if there is one.
+/// 
+/// The shaders used by the renderer, the default font and `white.png` are embedded, so that the engine can still run
+/// if the `assets/` folder is missing.
+fn embedded_asset(path: &str) -> Option<&'static [u8]> {
+	macro_rules! embed {
+		($($path:expr),*) => {
+			match path {
+				$($path => Some(&include_bytes!(concat!("../assets/", $path))[..]),)*
+				_ => None,
+			}
+		}
+	}
+	embed!(
+		"shaders/simple.vert", "shaders/simple.frag",
+		"shaders/simple_instanced.vert", "shaders/simple_instanced.frag",
+		"shaders/phong.vert", "shaders/phong.frag",
+		"shaders/shadow.vert", "shaders/shadow.frag",
+		"shaders/overlay.vert", "shaders/overlay.frag",
+		"shaders/skybox.vert", "shaders/skybox.frag",
+		"shaders/font.vert", "shaders/font.frag",
+		"shaders/panel.vert", "shaders/panel.frag",
+		"fonts/consolas.ttf",
+		"textures/white.png"
+	)
+}
+
+/// Trys to read the asset `name` in the `dir` folder, falling back to the embedded copy if it can't be read.
+///
+/// Returns a custom error message on failure.
+fn try_read_asset_bytes(dir: &str, name: &str) -> Result<Vec<u8>, String> {
+	fn try_read(dir: &str, name: &str) -> Result<Vec<u8>, String> {
+		let dir_path = try_get_base_dir()?.join(dir);
+		assert_is_dir(&dir_path)?;
+		try_read_file_bytes(dir_path.join(name))
+	}
+	match try_read(dir, name) {
+		Ok(bytes) => {
+			trace!("Loaded '{}/{}' from the assets folder", dir, name);
+			Ok(bytes)
+		},
+		Err(e) => match embedded_asset(&format!("{}/{}", dir, name)) {
+			Some(bytes) => {
+				warn!("{}: using the embedded copy of '{}/{}'", e, dir, name);
+				Ok(bytes.to_vec())
+			},
+			None => Err(e),
+		},
+	}
+}
+
+/// Trys to read the asset `name` in the `dir` folder as a string, falling back to the embedded copy if it can't be
+/// read.
+///
+/// Returns a custom error message on failure.
+fn try_read_asset_string(dir: &str, name: &str) -> Result<String, String> {
+	let bytes = try_read_asset_bytes(dir, name)?;
+	String::from_utf8(bytes)
+		.map_err(|e| format!("unreadable file '{}/{}': {}", dir, name, e))
+}
+
+/// Trys to read the asset `name` in the `dir` folder as a string, if it exists in the assets folder or is embedded.
+///
+/// Returns `Ok(None)` if there is no such asset, and a custom error message if it could not be read.
+fn try_read_optional_asset_string(dir: &str, name: &str) -> Result<Option<String>, String> {
+	let exists = try_get_base_dir().map(|base_dir| base_dir.join(dir).join(name).exists()).unwrap_or(false);
+	if exists || embedded_asset(&format!("{}/{}", dir, name)).is_some() {
+		try_read_asset_string(dir, name).map(Some)
+	} else {
+		Ok(None)
+	}
+}
+
+/// Tries to open an arbitrary data file from the `assets/` folder for reading.
+/// 
+/// This is useful for large files that should be streamed rather than loaded all at once.
+pub fn try_open_data_file<P: AsRef<Path>>(path: P) -> Result<File, String> {
+	fn inner_try(path: &Path) -> Result<File, String> {
+		let base_dir = try_get_base_dir()?;
+		let path = base_dir.join(path);
+		if !path.exists() {
+			return Err(format!("file does not exist: '{}'", path.display()));
+		} else if !path.is_file() {
+			return Err(format!("not a file: '{}'", path.display()));
+		}
+		File::open(&path).map_err(|e| format!("unreadable file '{}': {}", path.display(), e))
+	}
+	let path = path.as_ref();
+	inner_try(path)
+		.map_err(|e| format!("could not open data file '{}': {}", path.display(), e))
+}
+
 /// Tries to load an arbitrary data file from the `assets/` folder as bytes.
 pub fn try_load_data_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, String> {
 	fn inner_try(path: &Path) -> Result<Vec<u8>, String> {
@@ -93,14 +218,12 @@ pub fn try_load_data_string<P: AsRef<Path>>(path: P) -> Result<String, String> {
 
 /// Loads the shader `name` from the `shaders/` folder.
 /// 
-/// If it finds a file with the name of the shader and the extension
-/// - `.vert` it will load it as a vertex shader.
-/// - `.frag` it will load it as a fragment shader.
-/// - TODO: More shader types
+/// The shader stages are detected in the same way as `try_load_shader`.
 /// 
 /// Exits if
 /// - the vertex shader could not be found/compiled.
 /// - the fragment shader could not be found/compiled.
+/// - any of the optional stages that exist could not be compiled.
 pub fn load_shader(ctx: &Rc<Context>, name: &str) -> Program {
 	match try_load_shader(ctx, name) {
 		Ok(program) => program,
@@ -114,26 +237,57 @@ pub fn load_shader(ctx: &Rc<Context>, name: &str) -> Program {
 /// Loads the shader `name` from the `shaders/` folder.
 /// 
 /// If it finds a file with the name of the shader and the extension
-/// - `.vert` it will load it as a vertex shader
-/// - `.frag` it will load it as a fragment shader
-/// - TODO: More shader types
+/// - `.vert` it will load it as a vertex shader (required)
+/// - `.tesc` it will load it as a tessellation control shader (optional)
+/// - `.tese` it will load it as a tessellation evaluation shader (optional)
+/// - `.geom` it will load it as a geometry shader (optional)
+/// - `.frag` it will load it as a fragment shader (required)
+/// 
+/// Each stage can include other files in the `shaders/` folder with `#include "file.glsl"` on a line of its own.
+/// Includes are expanded recursively, and each file is only included once per stage.
 /// 
-/// Returns an `Err` if the shader cannot be found or is invalid.
+/// The shaders used by the renderer are embedded in the binary, and are used if they can't be read from the
+/// `shaders/` folder.
+/// 
+/// Returns an `Err` if the shader cannot be found, includes itself, or is invalid.
 pub fn try_load_shader(ctx: &Rc<Context>, name: &str) -> Result<Program, String> {
 	fn inner_try(ctx: &Rc<Context>, name: &str) -> Result<Program, String> {
-		let base_dir = try_get_base_dir()?;
-		
 		let name = String::from(name);
 		
-		let shaders_dir = base_dir.join("shaders");
-		assert_is_dir(&shaders_dir)?;
-		
-		let vert = try_read_file_string(shaders_dir.join(name.clone() + ".vert"))?;
-		
-		let frag = try_read_file_string(shaders_dir.join(name.clone() + ".frag"))?;
+		// All of the files included by any stage, for the log
+		let mut included = Vec::new();
+		let mut preprocess = |stage: &str, src: String| -> Result<String, String> {
+			let mut read = |file: &str| try_read_asset_string("shaders", file);
+			preprocess_shader_stage(&(name.clone() + stage), &src, &mut read, &mut included)
+		};
+		let vert = preprocess(".vert", try_read_asset_string("shaders", &(name.clone() + ".vert"))?)?;
+		let tesc = match try_read_optional_asset_string("shaders", &(name.clone() + ".tesc"))? {
+			Some(src) => Some(preprocess(".tesc", src)?),
+			None => None,
+		};
+		let tese = match try_read_optional_asset_string("shaders", &(name.clone() + ".tese"))? {
+			Some(src) => Some(preprocess(".tese", src)?),
+			None => None,
+		};
+		let geom = match try_read_optional_asset_string("shaders", &(name.clone() + ".geom"))? {
+			Some(src) => Some(preprocess(".geom", src)?),
+			None => None,
+		};
+		let frag = preprocess(".frag", try_read_asset_string("shaders", &(name.clone() + ".frag"))?)?;
 		
-		debug!("Compiling shader '{}'...", name);
-		match Program::from_source(ctx, &vert, &frag, None) {
+		if included.is_empty() {
+			debug!("Compiling shader '{}'...", name);
+		} else {
+			debug!("Compiling shader '{}' (including {})...", name, included.join(", "));
+		}
+		let source = SourceCode {
+			vertex_shader: &vert,
+			tessellation_control_shader: tesc.as_ref().map(|s| s.as_str()),
+			tessellation_evaluation_shader: tese.as_ref().map(|s| s.as_str()),
+			geometry_shader: geom.as_ref().map(|s| s.as_str()),
+			fragment_shader: &frag,
+		};
+		match Program::new(ctx, source) {
 			Ok(p) => Ok(p),
 			Err(e) => Err(format!("compilation error:\n{}", e)),
 		}
@@ -141,6 +295,59 @@ pub fn try_load_shader(ctx: &Rc<Context>, name: &str) -> Result<Program, String>
 	inner_try(ctx, name).map_err(|e| format!("cannot load shader '{}': {}", name, e))
 }
 
+/// Expands the `#include "file"` directives in one stage of a shader, like `preprocess_shader`.
+/// 
+/// Each stage is compiled on its own, so a file included by more than one stage has to be expanded in each of them.
+/// The files that are included are added to `all_included`, if they aren't already in it.
+fn preprocess_shader_stage<F>(file: &str, src: &str, read: &mut F, all_included: &mut Vec<String>) -> Result<String, String>
+		where F: FnMut(&str) -> Result<String, String> {
+	let mut included = Vec::new();
+	let out = preprocess_shader(file, src, read, &mut Vec::new(), &mut included)?;
+	for include in included {
+		if !all_included.contains(&include) {
+			all_included.push(include);
+		}
+	}
+	Ok(out)
+}
+
+/// Expands the `#include "file"` directives in the shader source `src`, which was read from `file`.
+/// 
+/// `read` is called to read each included file. `stack` holds the files that are currently being expanded, and is used
+/// to detect circular includes. Files in `included` are skipped, and each file that is included is added to it.
+fn preprocess_shader<F>(file: &str, src: &str, read: &mut F, stack: &mut Vec<String>, included: &mut Vec<String>) -> Result<String, String>
+		where F: FnMut(&str) -> Result<String, String> {
+	stack.push(file.into());
+	let mut out = String::with_capacity(src.len());
+	for (i, line) in src.lines().enumerate() {
+		let directive = line.trim();
+		if !directive.starts_with("#include") {
+			out.push_str(line);
+			out.push('\n');
+			continue;
+		}
+		
+		let arg = directive["#include".len()..].trim();
+		if arg.len() < 2 || !arg.starts_with('"') || !arg.ends_with('"') {
+			return Err(format!("{}:{}: expected `#include \"file\"`, found `{}`", file, i + 1, directive));
+		}
+		let include = &arg[1..arg.len() - 1];
+		if stack.iter().any(|f| f == include) {
+			return Err(format!("{}:{}: circular include: {} -> {}", file, i + 1, stack.join(" -> "), include));
+		}
+		if included.iter().any(|f| f == include) {
+			continue;
+		}
+		included.push(include.into());
+		
+		let include_src = read(include)
+			.map_err(|e| format!("{}:{}: cannot include '{}': {}", file, i + 1, include, e))?;
+		out.push_str(&preprocess_shader(include, &include_src, read, stack, included)?);
+	}
+	stack.pop();
+	Ok(out)
+}
+
 /// Loads the font `name` at `index` from a file in the `fonts/` folder.
 /// 
 /// Exits if the font is not valid.
@@ -156,14 +363,12 @@ pub fn load_font(name: &str, index: usize) -> Font<'static> {
 
 /// Loads the font `name` at `index` from a file in the `fonts/` folder.
 /// 
+/// The default font is embedded in the binary, and is used if it can't be read from the `fonts/` folder.
+/// 
 /// Returns an `Err` if the font is not valid.
 pub fn try_load_font(name: &str, index: usize) -> Result<Font<'static>, String> {
 	fn inner_try(name: &str, index: usize) -> Result<Font<'static>, String> {
-		let base_dir = try_get_base_dir()?;
-		let fonts_dir = base_dir.join("fonts");
-		assert_is_dir(&fonts_dir)?;
-		let font_path = fonts_dir.join(name);
-		let bytes = try_read_file_bytes(&font_path)?;
+		let bytes = try_read_asset_bytes("fonts", name)?;
 		
 		let collection = FontCollection::from_bytes(bytes)
 			.map_err(|e| format!("invalid font: {}", e))?;
@@ -191,24 +396,235 @@ pub fn load_texture(ctx: &Rc<Context>, name: &str) -> Texture2d {
 /// Returns an `Err` if the texture could not be found, the texture was invalid, or it could not be uploaded to OpenGL.
 pub fn try_load_texture(ctx: &Rc<Context>, name: &str) -> Result<Texture2d, String> {
 	fn inner_try(ctx: &Rc<Context>, name: &str) -> Result<Texture2d, String> {
-		let base_dir = try_get_base_dir()?;
-		let textures_dir = base_dir.join("textures");
-		assert_is_dir(&textures_dir)?;
-		let texture_path = textures_dir.join(name);
-		let bytes = try_read_file_bytes(&texture_path)?;
-		
-		let img = image::load_from_memory(&bytes).map_err(|e| format!("{}", e))?;
-		let img_buffer = match img {
-			DynamicImage::ImageLuma8(img)  => img.convert(),
-			DynamicImage::ImageLumaA8(img) => img.convert(),
-			DynamicImage::ImageRgb8(img)   => img.convert(),
-			DynamicImage::ImageRgba8(img)  => img,
-		};
+		let img = try_load_texture_image(name)?;
 		
 		// Upload to OpenGL
-		let dimensions = img_buffer.dimensions();
-		let img = RawImage2d::from_raw_rgba(img_buffer.into_raw(), dimensions);
 		Texture2d::new(ctx, img).map_err(|e| format!("{}", e))
 	}
 	inner_try(ctx, name).map_err(|e| format!("cannot load texture '{}': {}", name, e))
 }
+
+/// Loads a cubemap from six square textures in the `textures/` folder, e.g. for a skybox.
+/// 
+/// `names` are the faces in the order +X, -X, +Y, -Y, +Z, -Z.
+/// 
+/// Exits if the cubemap could not be loaded.
+pub fn load_cubemap(ctx: &Rc<Context>, names: [&str; 6]) -> Cubemap {
+	match try_load_cubemap(ctx, names) {
+		Ok(cubemap) => cubemap,
+		Err(e) => {
+			error!("{}", e);
+			exit(1);
+		}
+	}
+}
+
+/// Loads a cubemap from six square textures in the `textures/` folder, e.g. for a skybox.
+/// 
+/// `names` are the faces in the order +X, -X, +Y, -Y, +Z, -Z.
+/// 
+/// Returns an `Err` if any of the textures could not be loaded, or they are not all square and the same size.
+pub fn try_load_cubemap(ctx: &Rc<Context>, names: [&str; 6]) -> Result<Cubemap, String> {
+	fn inner_try(ctx: &Rc<Context>, names: [&str; 6]) -> Result<Cubemap, String> {
+		let mut faces = Vec::with_capacity(6);
+		for name in names.iter() {
+			faces.push(try_load_texture(ctx, name)?);
+		}
+		
+		let size = faces[0].width();
+		for (face, name) in faces.iter().zip(names.iter()) {
+			if (face.width(), face.height()) != (size, size) {
+				return Err(format!("face '{}' is {}x{}, but should be {}x{}", name, face.width(), face.height(), size, size));
+			}
+		}
+		
+		let cubemap = Cubemap::empty(ctx, size).map_err(|e| format!("{}", e))?;
+		let layers = [CubeLayer::PositiveX, CubeLayer::NegativeX, CubeLayer::PositiveY, CubeLayer::NegativeY, CubeLayer::PositiveZ, CubeLayer::NegativeZ];
+		for (face, &layer) in faces.iter().zip(layers.iter()) {
+			let target = SimpleFrameBuffer::new(ctx, cubemap.main_level().image(layer)).map_err(|e| format!("{}", e))?;
+			let rect = BlitTarget { left: 0, bottom: 0, width: size as i32, height: size as i32 };
+			face.as_surface().blit_whole_color_to(&target, &rect, MagnifySamplerFilter::Linear);
+		}
+		Ok(cubemap)
+	}
+	inner_try(ctx, names).map_err(|e| format!("cannot load cubemap {:?}: {}", names, e))
+}
+
+/// Re-reads the texture `name` from a file in the `textures/` folder and uploads it into `existing`.
+/// 
+/// Everything that shares `existing` will see the new texture.
+/// 
+/// Returns an `Err` if the texture could not be found, the texture was invalid, or its dimensions have changed.
+pub fn reload_texture(name: &str, existing: &Texture2d) -> Result<(), String> {
+	fn inner_try(name: &str, existing: &Texture2d) -> Result<(), String> {
+		let img = try_load_texture_image(name)?;
+		
+		let (width, height) = (existing.width(), existing.height());
+		if (img.width, img.height) != (width, height) {
+			return Err(format!("dimensions changed from {}x{} to {}x{}", width, height, img.width, img.height));
+		}
+		existing.write(Rect { left: 0, bottom: 0, width, height }, img);
+		Ok(())
+	}
+	inner_try(name, existing).map_err(|e| format!("cannot reload texture '{}': {}", name, e))
+}
+
+/// Reloads all of the textures in the texture cache from disk.
+/// 
+/// Textures that keep their dimensions are uploaded in place, so meshes using them update immediately.
+/// Textures whose dimensions have changed are replaced in the cache, and only meshes built afterwards will use them.
+/// 
+/// Returns the number of textures reloaded, or the first error encountered.
+pub fn reload_cached_textures(ctx: &Rc<Context>) -> Result<usize, String> {
+	let cached: Vec<(String, Rc<Texture2d>)> = TEXTURE_CACHE.with(|cache| {
+		cache.borrow().iter().map(|(name, texture)| (name.clone(), texture.clone())).collect()
+	});
+	for &(ref name, ref texture) in cached.iter() {
+		if let Err(e) = reload_texture(name, texture) {
+			warn!("{}: replacing it in the cache", e);
+			let texture = Rc::new(try_load_texture(ctx, name)?);
+			TEXTURE_CACHE.with(|cache| cache.borrow_mut().insert(name.clone(), texture));
+		}
+	}
+	Ok(cached.len())
+}
+
+/// Reads the texture `name` from a file in the `textures/` folder, and converts it to RGBA.
+/// 
+/// `white.png` is embedded in the binary, and is used if it can't be read from the `textures/` folder.
+fn try_load_texture_image(name: &str) -> Result<RawImage2d<'static, u8>, String> {
+	let bytes = try_read_asset_bytes("textures", name)?;
+	
+	let img = image::load_from_memory(&bytes).map_err(|e| format!("{}", e))?;
+	let img_buffer = match img {
+		DynamicImage::ImageLuma8(img)  => img.convert(),
+		DynamicImage::ImageLumaA8(img) => img.convert(),
+		DynamicImage::ImageRgb8(img)   => img.convert(),
+		DynamicImage::ImageRgba8(img)  => img,
+	};
+	
+	let dimensions = img_buffer.dimensions();
+	Ok(RawImage2d::from_raw_rgba(img_buffer.into_raw(), dimensions))
+}
+
+thread_local! {
+	/// Textures that have already been uploaded to OpenGL, keyed by filename.
+	static TEXTURE_CACHE: RefCell<HashMap<String, Rc<Texture2d>>> = RefCell::new(HashMap::new());
+}
+
+/// Loads the texture `name` from a file in the `textures/` folder, or returns the texture from the cache if it has
+/// already been loaded.
+/// 
+/// Exits if the texture could not be found, the texture was invalid, or it could not be uploaded to OpenGL.
+pub fn load_texture_cached(ctx: &Rc<Context>, name: &str) -> Rc<Texture2d> {
+	match try_load_texture_cached(ctx, name) {
+		Ok(texture) => texture,
+		Err(e) => {
+			error!("{}", e);
+			exit(1);
+		}
+	}
+}
+
+/// Loads the texture `name` from a file in the `textures/` folder, or returns the texture from the cache if it has
+/// already been loaded.
+/// 
+/// Returns an `Err` if the texture could not be found, the texture was invalid, or it could not be uploaded to OpenGL.
+pub fn try_load_texture_cached(ctx: &Rc<Context>, name: &str) -> Result<Rc<Texture2d>, String> {
+	let cached = TEXTURE_CACHE.with(|cache| cache.borrow().get(name).cloned());
+	match cached {
+		// Textures can't be shared between contexts
+		Some(ref texture) if Rc::ptr_eq(texture.get_context(), ctx) => return Ok(texture.clone()),
+		_ => {}
+	}
+	
+	let texture = Rc::new(try_load_texture(ctx, name)?);
+	TEXTURE_CACHE.with(|cache| cache.borrow_mut().insert(name.into(), texture.clone()));
+	Ok(texture)
+}
+
+/// Clears the texture cache, so that textures are reloaded from disk the next time they are requested.
+/// 
+/// Textures that are still in use are kept alive until they are no longer referenced.
+pub fn clear_texture_cache() {
+	TEXTURE_CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+/// Loads the Wavefront OBJ model `name` from a file in the `models/` folder, with a texture and material.
+/// 
+/// Exits if the model could not be found, the model was invalid, or it could not be uploaded to OpenGL.
+pub fn load_obj(ctx: &Rc<Context>, name: &str, texture: Rc<Texture2d>, material: Material) -> LitMesh {
+	match try_load_obj(ctx, name, texture, material) {
+		Ok(mesh) => mesh,
+		Err(e) => {
+			error!("{}", e);
+			exit(1);
+		}
+	}
+}
+
+/// Loads the Wavefront OBJ model `name` from a file in the `models/` folder, with a texture and material.
+/// 
+/// Returns an `Err` if the model could not be found, the model was invalid, or it could not be uploaded to OpenGL.
+pub fn try_load_obj(ctx: &Rc<Context>, name: &str, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
+	fn inner_try(ctx: &Rc<Context>, name: &str, texture: Rc<Texture2d>, material: Material) -> Result<LitMesh, String> {
+		let base_dir = try_get_base_dir()?;
+		let models_dir = base_dir.join("models");
+		assert_is_dir(&models_dir)?;
+		let src = try_read_file_string(models_dir.join(name))?;
+		LitMesh::from_obj(ctx, &src, texture, material)
+	}
+	inner_try(ctx, name, texture, material).map_err(|e| format!("cannot load model '{}': {}", name, e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn preprocess(files: &[(&str, &str)], main: &str) -> Result<String, String> {
+		let files: HashMap<&str, &str> = files.iter().cloned().collect();
+		let mut read = |file: &str| files.get(file).map(|s| s.to_string()).ok_or_else(|| format!("no file '{}'", file));
+		preprocess_shader("main.frag", main, &mut read, &mut Vec::new(), &mut Vec::new())
+	}
+	
+	#[test]
+	pub fn test_include() {
+		let files = [("a.glsl", "#include \"b.glsl\"\na\n"), ("b.glsl", "b\n")];
+		let src = preprocess(&files, "#version 140\n  #include \"a.glsl\"\n#include \"b.glsl\"\nmain\n").unwrap();
+		assert_eq!("#version 140\nb\na\nmain\n", src);
+	}
+	
+	#[test]
+	pub fn test_circular_include() {
+		let files = [("a.glsl", "#include \"b.glsl\"\n"), ("b.glsl", "#include \"a.glsl\"\n")];
+		let e = preprocess(&files, "#include \"a.glsl\"\n").unwrap_err();
+		assert!(e.contains("circular include: main.frag -> a.glsl -> b.glsl -> a.glsl"), "{}", e);
+	}
+	
+	#[test]
+	pub fn test_embedded_asset() {
+		assert_eq!(Some(&include_bytes!("../assets/textures/white.png")[..]), embedded_asset("textures/white.png"));
+		assert!(embedded_asset("shaders/phong.frag").is_some());
+		assert!(embedded_asset("textures/test.png").is_none());
+	}
+	
+	#[test]
+	pub fn test_invalid_include() {
+		assert!(preprocess(&[], "#include <a.glsl>\n").is_err());
+		assert!(preprocess(&[], "#include \"a.glsl\"\n").is_err());
+	}
+	
+	#[test]
+	pub fn test_include_shared_between_stages() {
+		let mut read = |file: &str| match file {
+			"common.glsl" => Ok("common\n".to_string()),
+			_ => Err(format!("no file '{}'", file)),
+		};
+		let mut included = Vec::new();
+		let vert = preprocess_shader_stage("main.vert", "#include \"common.glsl\"\nvert\n", &mut read, &mut included).unwrap();
+		let frag = preprocess_shader_stage("main.frag", "#include \"common.glsl\"\nfrag\n", &mut read, &mut included).unwrap();
+		assert_eq!("common\nvert\n", vert);
+		assert_eq!("common\nfrag\n", frag);
+		assert_eq!(vec!["common.glsl".to_string()], included);
+	}
+}